@@ -0,0 +1,77 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::assignment;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub unit: String,
+}
+
+/// Export assignment handler's response payload, the unit's assignments of every experiment.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<assignment::Assignment>,
+}
+
+/// Handle function to export every variation kept for a unit in the channel.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = dep
+        .assignment_repo
+        .list(&channel_id, &params.unit, None)
+        .await?;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_assignment_store = assignment::MockStore::new();
+        mock_assignment_store
+            .expect_list()
+            .withf(|_, unit, id| unit == "unit-1" && id.is_none())
+            .return_once(|_, _, _| Ok(vec![]));
+
+        let mut dependency = Dependency::new(experiment_service::MockStore::new());
+        dependency.assignment_repo = Box::new(mock_assignment_store);
+        let data = web::Data::new(dependency);
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let params = web::Path::from(Params {
+            unit: "unit-1".to_owned(),
+        });
+
+        let resp = handle(req, params, data).await;
+        assert!(resp.is_ok());
+    }
+}
@@ -0,0 +1,103 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::assignment;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+    pub unit: String,
+}
+
+/// Get assignment handler's response payload, one assignment per context.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<assignment::Assignment>,
+}
+
+/// Handle function to look up the variations kept for a unit in an experiment.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let experiment = experiment_service::get(experiment_repo, &params.id, &channel_id).await?;
+
+    let data = dep
+        .assignment_repo
+        .list(&channel_id, &params.unit, experiment.id)
+        .await?;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+    use chrono::Utc;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = experiment_service::MockStore::new();
+        let mock_get_result = Ok(experiment_service::Experiment {
+            id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+            ..Default::default()
+        });
+        mock_store
+            .expect_get()
+            .return_once(move |_, _| mock_get_result);
+
+        let mut mock_assignment_store = assignment::MockStore::new();
+        mock_assignment_store
+            .expect_list()
+            .withf(|_, unit, id| {
+                unit == "unit-1" && id.as_deref() == Some("62bb13dfea2b3ea78771e305")
+            })
+            .return_once(|_, _, _| {
+                Ok(vec![assignment::Assignment {
+                    channel_id: String::default(),
+                    experiment_id: "62bb13dfea2b3ea78771e305".to_owned(),
+                    unit: "unit-1".to_owned(),
+                    context: String::default(),
+                    indicator: "control".to_owned(),
+                    assigned_at: Utc::now(),
+                }])
+            });
+
+        let mut dependency = Dependency::new(mock_store);
+        dependency.assignment_repo = Box::new(mock_assignment_store);
+        let data = web::Data::new(dependency);
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+            unit: "unit-1".to_owned(),
+        });
+
+        let resp = handle(req, params, data).await;
+        assert_eq!(resp.unwrap().data.len(), 1);
+    }
+}
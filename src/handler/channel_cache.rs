@@ -0,0 +1,111 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::cache;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+/// Cache stats handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct StatsResponsePayload {
+    data: cache::Stats,
+}
+
+/// Cache flush handler's response payload, the number of the cached reads dropped.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct FlushResponsePayload {
+    flushed: usize,
+}
+
+fn channel_of(req: &HttpRequest) -> Result<String, HandlerError> {
+    req.extensions()
+        .get::<Claims>()
+        .map(|ut| ut.channel_id.clone())
+        .ok_or(HandlerError::Unauthorize)
+}
+
+/// Handle function to show the counters of the experiment cache of the channel.
+pub async fn stats<ER: experiment_service::Store>(
+    req: HttpRequest,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<StatsResponsePayload>, CustomAPIError> {
+    let channel_id = channel_of(&req)?;
+
+    Ok(Json(StatsResponsePayload {
+        data: dep.experiment_cache.stats(&channel_id),
+    }))
+}
+
+/// Handle function to drop the cached experiments of the channel, e.g. after changing them
+/// in the database directly.
+pub async fn flush<ER: experiment_service::Store>(
+    req: HttpRequest,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<FlushResponsePayload>, CustomAPIError> {
+    let channel_id = channel_of(&req)?;
+
+    let flushed = dep.experiment_cache.flush(&channel_id);
+    println!(
+        "cache of channel {} flushed, {} reads dropped",
+        channel_id, flushed
+    );
+
+    Ok(Json(FlushResponsePayload { flushed }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::header::ContentType, test};
+    use mockall::predicate::eq;
+
+    #[actix_web::test]
+    async fn test_handler_flush() {
+        let mut mock_cache = cache::MockControl::new();
+        mock_cache
+            .expect_flush()
+            .with(eq("channel_a"))
+            .return_const(3_usize);
+
+        let mut dep = Dependency::new(experiment_service::MockStore::new());
+        dep.experiment_cache = Box::new(mock_cache);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+
+        let resp = flush(req, data).await;
+        assert_eq!(resp.unwrap().flushed, 3);
+    }
+
+    #[actix_web::test]
+    async fn test_handler_stats_without_cache() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let resp = stats(req, data).await;
+        assert!(!resp.unwrap().data.enabled);
+    }
+
+    #[actix_web::test]
+    async fn test_handler_unauthorized() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+
+        assert!(flush(req, data).await.is_err());
+    }
+}
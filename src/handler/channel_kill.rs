@@ -0,0 +1,109 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+/// Channel kill switch handler's request payload.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RequestPayload {
+    pub reason: String,
+}
+
+/// Channel kill switch handler's response payload, the experiments that have been killed
+/// and the ones an error left as they were.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<experiment_service::Experiment>,
+    failed: Vec<experiment_service::Failure>,
+}
+
+/// Handle function to force every experiment of the channel back to its control variation.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+
+    let channel_id: String;
+    let owner: serde_json::Value;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+        owner = serde_json::to_value(ut).unwrap_or_default();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let kill_switch = experiment_service::KillSwitch {
+        scope: experiment_service::KillScope::Channel,
+        reason: payload.into_inner().reason,
+        triggered_by: Some(owner),
+        triggered_at: Utc::now(),
+    };
+
+    let change =
+        experiment_service::kill_channel(experiment_repo, &channel_id, kill_switch.clone()).await?;
+
+    for experiment in change.changed.iter() {
+        publish_event(
+            dep.event_publisher.as_ref(),
+            event::Event::killed(experiment, &kill_switch),
+        )
+        .await;
+    }
+
+    Ok(Json(ResponsePayload {
+        data: change.changed,
+        failed: change.failed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = experiment_service::MockStore::new();
+        let ended = experiment_service::Experiment {
+            status: experiment_service::Status::Ended,
+            ..Default::default()
+        };
+        let mock_list_result = Ok(experiment_service::Page {
+            items: vec![experiment_service::Experiment::default(), ended],
+            next_cursor: None,
+            total: 2,
+        });
+        mock_store
+            .expect_list()
+            .return_once(move |_, _| mock_list_result);
+        mock_store
+            .expect_patch()
+            .times(1)
+            .returning(|_, _, _| Ok(Some(experiment_service::Experiment::default())));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let body = Json(RequestPayload {
+            reason: "incident".to_owned(),
+        });
+
+        let resp = handle(req, body, data).await;
+        assert_eq!(resp.unwrap().data.len(), 1);
+    }
+}
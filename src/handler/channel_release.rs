@@ -0,0 +1,107 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+/// Channel release handler's response payload, the experiments that have been released
+/// and the ones an error left as they were.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<experiment_service::Experiment>,
+    failed: Vec<experiment_service::Failure>,
+}
+
+/// Handle function to release the channel kill switch.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+
+    let channel_id: String;
+    let owner: serde_json::Value;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+        owner = serde_json::to_value(ut).unwrap_or_default();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let change = experiment_service::release_channel(experiment_repo, &channel_id).await?;
+
+    for experiment in change.changed.iter() {
+        publish_event(
+            dep.event_publisher.as_ref(),
+            event::Event::released(experiment, Some(owner.clone())),
+        )
+        .await;
+    }
+
+    Ok(Json(ResponsePayload {
+        data: change.changed,
+        failed: change.failed,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+    use chrono::Utc;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let kill_switch = |scope| experiment_service::KillSwitch {
+            scope,
+            reason: "incident".to_owned(),
+            triggered_by: None,
+            triggered_at: Utc::now(),
+        };
+        let by_channel = experiment_service::Experiment {
+            kill_switch: Some(kill_switch(experiment_service::KillScope::Channel)),
+            ..Default::default()
+        };
+        let by_experiment = experiment_service::Experiment {
+            kill_switch: Some(kill_switch(experiment_service::KillScope::Experiment)),
+            ..Default::default()
+        };
+
+        let mut mock_store = experiment_service::MockStore::new();
+        let mock_list_result = Ok(experiment_service::Page {
+            items: vec![by_channel, by_experiment],
+            next_cursor: None,
+            total: 2,
+        });
+        mock_store
+            .expect_list()
+            .return_once(move |_, _| mock_list_result);
+        mock_store
+            .expect_patch()
+            .withf(|_, _, patch| {
+                *patch
+                    == experiment_service::Patch::Release(Some(
+                        experiment_service::KillScope::Channel,
+                    ))
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(Some(experiment_service::Experiment::default())));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let resp = handle(req, data).await;
+        assert_eq!(resp.unwrap().data.len(), 1);
+    }
+}
@@ -0,0 +1,114 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::bandit;
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+/// Bandit freeze handler's request payload.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RequestPayload {
+    pub frozen: bool,
+}
+
+/// Bandit freeze handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: experiment_service::Experiment,
+}
+
+/// Handle function to freeze or unfreeze the weights of a bandit experiment.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = bandit::freeze(experiment_repo, &params.id, &channel_id, payload.frozen).await?;
+
+    publish_event(dep.event_publisher.as_ref(), event::Event::updated(&data)).await;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let experiment = experiment_service::Experiment {
+            classing: experiment_service::Classing {
+                strategy: bandit::STRATEGY.to_owned(),
+                persistent_mode: String::default(),
+                bandit: Some(bandit::Bandit {
+                    algorithm: bandit::Algorithm::ThompsonSampling,
+                    epsilon: bandit::default_epsilon(),
+                    min_weight: 0,
+                    max_weight: bandit::default_max_weight(),
+                    recompute_interval_secs: bandit::default_recompute_interval(),
+                    frozen: false,
+                    rewards: Default::default(),
+                    history: vec![],
+                    recomputed_at: None,
+                }),
+            },
+            ..Default::default()
+        };
+
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_patch()
+            .withf(|_, _, patch| *patch == experiment_service::Patch::Freeze(true))
+            .return_once(move |_, _, patch| {
+                let mut experiment = experiment;
+                patch.apply(&mut experiment);
+                Ok(Some(experiment))
+            });
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .withf(|e| e.kind == event::Kind::Updated)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut dep = Dependency::new(mock_store);
+        dep.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dep);
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let body = Json(RequestPayload { frozen: true });
+
+        let resp = handle(req, params, body, data).await;
+        assert!(resp.is_ok());
+    }
+}
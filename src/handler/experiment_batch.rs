@@ -0,0 +1,207 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::experiment_create;
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::batch;
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+/// Batch handler's request payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct RequestPayload {
+    pub operations: Vec<Operation>,
+}
+
+/// A change of the batch, an experiment is created from the same payload as the create
+/// handler's so the client cannot set its owner, dates or bandit state.
+#[derive(Deserialize, Serialize, Debug)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Create {
+        experiment: Box<experiment_create::RequestPayload>,
+    },
+    Update {
+        experiment: Box<experiment_service::Experiment>,
+    },
+    Delete {
+        id: String,
+    },
+    SetStatus {
+        id: String,
+        status: experiment_service::Status,
+    },
+}
+
+impl Operation {
+    /// Transform into the service's operation, the created experiments are owned by `owner`.
+    fn into_batch(self, owner: &serde_json::Value) -> batch::Operation {
+        match self {
+            Operation::Create { experiment } => {
+                let mut experiment: experiment_service::Experiment = (*experiment).into();
+                experiment.owner = Some(owner.clone());
+                batch::Operation::Create { experiment }
+            }
+            Operation::Update { experiment } => batch::Operation::Update {
+                experiment: *experiment,
+            },
+            Operation::Delete { id } => batch::Operation::Delete { id },
+            Operation::SetStatus { id, status } => batch::Operation::SetStatus { id, status },
+        }
+    }
+}
+
+/// Batch handler's response payload, the outcome per operation.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: batch::Report,
+}
+
+/// Handle function to apply many experiment changes all together.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let payload = payload.into_inner();
+
+    let channel_id: String;
+    let owner: serde_json::Value;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+        owner = serde_json::to_value(ut).unwrap_or_default();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let operations = payload
+        .operations
+        .into_iter()
+        .map(|op| op.into_batch(&owner))
+        .collect();
+    let data = batch::apply(experiment_repo, &channel_id, operations).await?;
+
+    for (experiment, from) in &data.status_changes {
+        publish_event(
+            dep.event_publisher.as_ref(),
+            event::Event::status_changed(
+                &experiment.id.clone().unwrap_or_default(),
+                &experiment.channel_id,
+                *from,
+                experiment.status,
+                Utc::now(),
+            ),
+        )
+        .await;
+    }
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_publishes_status_changes() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store.expect_get_fresh().returning(|id, _| {
+            Ok(experiment_service::Experiment {
+                id: Some(id.to_owned()),
+                name: "experiment".to_owned(),
+                status: experiment_service::Status::Running,
+                ..Default::default()
+            })
+        });
+        mock_store.expect_write_atomically().returning(|_| Ok(true));
+
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .withf(|e| {
+                e.kind == event::Kind::StatusChanged
+                    && e.experiment_id == "62bb13dfea2b3ea78771e305"
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut dependency = Dependency::new(mock_store);
+        dependency.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dependency);
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let body: RequestPayload = serde_json::from_value(serde_json::json!({
+            "operations": [{"op": "set_status", "id": "62bb13dfea2b3ea78771e305", "status": "paused"}]
+        }))
+        .unwrap();
+
+        let resp = handle(req, Json(body), data).await.unwrap();
+        assert!(resp.data.applied);
+    }
+
+    #[actix_web::test]
+    async fn test_handler_create_is_owned_by_the_caller() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_write_atomically()
+            .withf(|w| {
+                let owner = serde_json::to_value(Claims::default()).unwrap();
+                matches!(&w[0], experiment_service::Write::Insert(e)
+                    if e.owner == Some(owner)
+                        && e.created_at != Some("2001-01-01T00:00:00Z".parse().unwrap())
+                        && e.classing.bandit.as_ref().unwrap().rewards.is_empty())
+            })
+            .times(1)
+            .returning(|_| Ok(true));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        // the fields the create handler does not accept are ignored.
+        let body: RequestPayload = serde_json::from_value(serde_json::json!({
+            "operations": [{"op": "create", "experiment": {
+                "name": "checkout",
+                "description": "checkout flow",
+                "owner": {"id": "someone-else"},
+                "created_at": "2001-01-01T00:00:00Z",
+                "variances": [{
+                    "group_name": "control",
+                    "description": "",
+                    "indicator": "control",
+                    "weight": 100,
+                    "values": {},
+                }],
+                "classing": {
+                    "strategy": "bandit",
+                    "persistent_mode": "key",
+                    "bandit": {
+                        "algorithm": "epsilon_greedy",
+                        "rewards": {"control": {"trials": 10, "successes": 10}},
+                    },
+                },
+            }}]
+        }))
+        .unwrap();
+
+        let resp = handle(req, Json(body), data).await.unwrap();
+        assert!(resp.data.applied);
+    }
+}
@@ -1,23 +1,40 @@
 use std::collections::HashMap;
 
-use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use actix_web::{
+    http::header::{HeaderName, HeaderValue},
+    http::StatusCode,
+    web, HttpMessage, HttpRequest, HttpResponse,
+};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json;
 
-use super::{Claims, CustomAPIError, HandlerError};
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::bandit;
+use crate::service::event;
 use crate::service::experiment;
+use crate::service::factorial;
+use crate::service::idempotency;
 use crate::Dependency;
 
 /// Exeriment create handler's request payload struct
 #[derive(Deserialize, Serialize, Debug, Default)]
 pub struct RequestPayload {
+    /// Readable reference of the experiment, generated from the name when absent.
+    #[serde(default)]
+    pub key: Option<String>,
     pub name: String,
     pub description: String,
     pub active_interval: Option<Interval>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 
+    #[serde(default)]
     pub variances: Vec<Variance>,
     pub classing: Classing,
+    /// Generate the variances from the factors instead of listing them.
+    #[serde(default)]
+    pub factorial: Option<Factorial>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -32,26 +49,77 @@ pub struct Variance {
     pub values: HashMap<String, serde_json::Value>,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Factorial {
+    pub factors: Vec<Factor>,
+    #[serde(default)]
+    pub fraction: Fraction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Factor {
+    pub name: String,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Level {
+    pub name: String,
+    pub values: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Fraction {
+    #[default]
+    Full,
+    Half,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Classing {
     pub strategy: String,
     pub persistent_mode: String,
+    #[serde(default)]
+    pub bandit: Option<Bandit>,
+}
+
+/// Bandit settings, the weights of the variances are the initial weights.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bandit {
+    pub algorithm: BanditAlgorithm,
+    pub epsilon: Option<f64>,
+    pub min_weight: Option<i32>,
+    pub max_weight: Option<i32>,
+    pub recompute_interval_secs: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BanditAlgorithm {
+    ThompsonSampling,
+    EpsilonGreedy,
 }
 
-/// Implement to make the request payload can transform into the service's struct
-///
+// Implement to make the request payload can transform into the service's struct
+//
 
 impl From<RequestPayload> for experiment::Experiment {
     fn from(rp: RequestPayload) -> Self {
         Self {
             id: None,
+            key: rp.key.unwrap_or_default(),
             name: rp.name,
             description: rp.description,
             active_interval: rp.active_interval.map(|v| v.into()),
+            tags: rp.tags,
             variations: rp.variances.into_iter().map(|v| v.into()).collect(),
             classing: rp.classing.into(),
+            factorial: rp.factorial.map(|v| v.into()),
             owner: None,
             channel_id: String::default(),
+            status: experiment::Status::default(),
+            kill_switch: None,
             created_at: None,
             updated_at: None,
             deleted_at: None,
@@ -70,6 +138,28 @@ impl From<Classing> for experiment::Classing {
         Self {
             strategy: c.strategy,
             persistent_mode: c.persistent_mode,
+            bandit: c.bandit.map(|v| v.into()),
+        }
+    }
+}
+
+impl From<Bandit> for bandit::Bandit {
+    fn from(b: Bandit) -> Self {
+        Self {
+            algorithm: match b.algorithm {
+                BanditAlgorithm::ThompsonSampling => bandit::Algorithm::ThompsonSampling,
+                BanditAlgorithm::EpsilonGreedy => bandit::Algorithm::EpsilonGreedy,
+            },
+            epsilon: b.epsilon.unwrap_or_else(bandit::default_epsilon),
+            min_weight: b.min_weight.unwrap_or(0),
+            max_weight: b.max_weight.unwrap_or_else(bandit::default_max_weight),
+            recompute_interval_secs: b
+                .recompute_interval_secs
+                .unwrap_or_else(bandit::default_recompute_interval),
+            frozen: false,
+            rewards: HashMap::new(),
+            history: vec![],
+            recomputed_at: None,
         }
     }
 }
@@ -82,6 +172,37 @@ impl From<Variance> for experiment::Variance {
             indicator: v.indicator,
             weight: v.weight,
             values: v.values,
+            levels: HashMap::new(),
+        }
+    }
+}
+
+impl From<Factorial> for factorial::Factorial {
+    fn from(f: Factorial) -> Self {
+        Self {
+            factors: f.factors.into_iter().map(|v| v.into()).collect(),
+            fraction: match f.fraction {
+                Fraction::Full => factorial::Fraction::Full,
+                Fraction::Half => factorial::Fraction::Half,
+            },
+        }
+    }
+}
+
+impl From<Factor> for factorial::Factor {
+    fn from(f: Factor) -> Self {
+        Self {
+            name: f.name,
+            levels: f.levels.into_iter().map(|v| v.into()).collect(),
+        }
+    }
+}
+
+impl From<Level> for factorial::Level {
+    fn from(l: Level) -> Self {
+        Self {
+            name: l.name,
+            values: l.values,
         }
     }
 }
@@ -92,13 +213,128 @@ pub struct ResponsePayload {
     data: experiment::Experiment,
 }
 
-/// Handle function to handle create experimental request.
-pub async fn handle<ER: experiment::Store>(
+/// Handle function to handle create experimental request. A request with an
+/// `Idempotency-Key` is handled once, its retries get the first response.
+pub async fn handle<ER: experiment::Store + 'static>(
     req: HttpRequest,
     payload: web::Json<RequestPayload>,
     dep: web::Data<Dependency<ER>>,
-) -> Result<Json<ResponsePayload>, CustomAPIError> {
-    let mut data: experiment::Experiment = payload.into_inner().into();
+) -> Result<HttpResponse, CustomAPIError> {
+    let payload = payload.into_inner();
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let key = match req.headers().get(idempotency::HEADER) {
+        Some(v) => v.to_str().map_err(|_| {
+            HandlerError::BadRequest("idempotency key must be printable characters".to_owned())
+        })?,
+        None => return Ok(respond(&create(&req, payload, &dep).await?)),
+    };
+
+    let store = dep.idempotency_repo.as_ref();
+    let fingerprint = idempotency::fingerprint(&payload)?;
+    match idempotency::begin(store, &channel_id, key, &fingerprint).await? {
+        idempotency::Outcome::Proceed => {}
+        idempotency::Outcome::Replay(response) => {
+            let mut resp = respond(&response);
+            resp.headers_mut().insert(
+                HeaderName::from_static(REPLAYED_HEADER),
+                HeaderValue::from_static("true"),
+            );
+            return Ok(resp);
+        }
+        idempotency::Outcome::InProgress => {
+            return Err(HandlerError::Conflict(
+                "a request with the same idempotency key is in progress".to_owned(),
+            )
+            .into())
+        }
+        idempotency::Outcome::Mismatch => {
+            return Err(HandlerError::UnprocessableEntity(
+                "the idempotency key was used with another request body".to_owned(),
+            )
+            .into())
+        }
+    }
+
+    let reservation = Reservation {
+        dep: dep.clone(),
+        channel_id,
+        key: key.to_owned(),
+        is_done: false,
+    };
+    let result = create(&req, payload, &dep).await;
+    reservation.finish(result.as_ref().ok()).await;
+
+    result.map(|resp| respond(&resp))
+}
+
+/// Idempotency key reserved for a creation. The key is released when the reservation is
+/// dropped unfinished, the creation panicked or the request was dropped, or it would
+/// answer "in progress" until the end of the window.
+struct Reservation<ER: experiment::Store + 'static> {
+    dep: web::Data<Dependency<ER>>,
+    channel_id: String,
+    key: String,
+    is_done: bool,
+}
+
+impl<ER: experiment::Store + 'static> Reservation<ER> {
+    /// Store the response of the creation, or release the key when it failed.
+    async fn finish(mut self, response: Option<&idempotency::Response>) {
+        let store = self.dep.idempotency_repo.as_ref();
+        if let Some(response) = response {
+            match store.complete(&self.channel_id, &self.key, response).await {
+                Ok(_) => {
+                    self.is_done = true;
+                    return;
+                }
+                Err(e) => println!("unable to store idempotent response: {}", e),
+            }
+        }
+
+        if let Err(e) = store.release(&self.channel_id, &self.key).await {
+            println!("unable to release idempotency key: {}", e);
+        }
+        self.is_done = true;
+    }
+}
+
+impl<ER: experiment::Store + 'static> Drop for Reservation<ER> {
+    fn drop(&mut self) {
+        if self.is_done {
+            return;
+        }
+
+        let dep = self.dep.clone();
+        let channel_id = std::mem::take(&mut self.channel_id);
+        let key = std::mem::take(&mut self.key);
+        actix_web::rt::spawn(async move {
+            if let Err(e) = dep.idempotency_repo.release(&channel_id, &key).await {
+                println!("unable to release idempotency key: {}", e);
+            }
+        });
+    }
+}
+
+/// Header telling the response is the one stored for an earlier request.
+pub const REPLAYED_HEADER: &str = "idempotent-replayed";
+
+fn respond(response: &idempotency::Response) -> HttpResponse {
+    let status = StatusCode::from_u16(response.status).unwrap_or(StatusCode::OK);
+    HttpResponse::build(status).json(&response.body)
+}
+
+async fn create<ER: experiment::Store>(
+    req: &HttpRequest,
+    payload: RequestPayload,
+    dep: &Dependency<ER>,
+) -> Result<idempotency::Response, CustomAPIError> {
+    let mut data: experiment::Experiment = payload.into();
     let experiment_repo = &dep.experiment_repo;
 
     if let Some(ut) = req.extensions().get::<Claims>() {
@@ -112,7 +348,14 @@ pub async fn handle<ER: experiment::Store>(
     let create_result = experiment::create(experiment_repo, data).await;
 
     match create_result {
-        Ok(data) => Ok(Json(ResponsePayload { data })),
+        Ok(data) => {
+            publish_event(dep.event_publisher.as_ref(), event::Event::created(&data)).await;
+            Ok(idempotency::Response {
+                status: StatusCode::OK.as_u16(),
+                body: serde_json::to_value(ResponsePayload { data })
+                    .map_err(anyhow::Error::from)?,
+            })
+        }
         Err(e) => Err(e.into()),
     }
 }
@@ -123,31 +366,38 @@ mod tests {
     use crate::service::experiment as experiment_service;
     use crate::Dependency;
     use anyhow::Ok;
+    use futures_util::FutureExt;
+    use std::panic::AssertUnwindSafe;
 
-    use actix_web::{http::header::ContentType, test};
+    use actix_web::{http::header::ContentType, test, web::Json};
 
     #[actix_web::test]
     async fn test_index_ok() {
         let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
         let mock_create_result = Ok(String::from("mock"));
         mock_store
             .expect_save()
             .return_once(move |_| mock_create_result);
 
-        let data = web::Data::new(Dependency {
-            experiment_repo: mock_store,
-        });
+        let data = web::Data::new(Dependency::new(mock_store));
 
         let local_datetime = Utc::now();
         let body = Json(RequestPayload {
+            key: None,
             name: "mock-name".to_string(),
             description: "mock-description".to_string(),
             active_interval: Some(Interval(Some(local_datetime), Some(local_datetime))),
+            tags: vec!["team:growth".to_string()],
             variances: vec![],
             classing: Classing {
                 strategy: "mock-value".to_string(),
-                persistent_mode: "mock-value".to_string(),
+                persistent_mode: "key_contextual".to_string(),
+                bandit: None,
             },
+            factorial: None,
         });
 
         let mock_claims = Claims::default();
@@ -160,4 +410,207 @@ mod tests {
         let resp = handle(req, body, data).await;
         assert!(resp.is_ok());
     }
+
+    #[actix_web::test]
+    async fn test_factorial_ok() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_save()
+            .withf(|data| data.variations.len() == 4 && data.factorial.is_some())
+            .return_once(|_| Ok(String::from("mock")));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let factor = |name: &str| Factor {
+            name: name.to_string(),
+            levels: ["off", "on"]
+                .iter()
+                .map(|l| Level {
+                    name: l.to_string(),
+                    values: HashMap::from([(name.to_string(), serde_json::json!(l))]),
+                })
+                .collect(),
+        };
+        let body = Json(RequestPayload {
+            key: None,
+            name: "mock-name".to_string(),
+            description: "mock-description".to_string(),
+            active_interval: None,
+            tags: vec![],
+            variances: vec![],
+            classing: Classing::default(),
+            factorial: Some(Factorial {
+                factors: vec![factor("banner"), factor("badge")],
+                fraction: Fraction::Full,
+            }),
+        });
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let resp = handle(req, body, data).await;
+        assert!(resp.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_idempotency_key() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_save()
+            .times(1)
+            .returning(|_| Ok(String::from("62bb13dfea2b3ea78771e305")));
+        // the replays do not publish the creation again.
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .withf(|e| e.kind == event::Kind::Created)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut dep = Dependency::new(mock_store);
+        dep.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dep);
+
+        let payload = || {
+            Json(RequestPayload {
+                name: "mock-name".to_string(),
+                classing: Classing {
+                    strategy: "random".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+        let request = || {
+            let req = test::TestRequest::default()
+                .insert_header((idempotency::HEADER, "deploy-42"))
+                .to_http_request();
+            req.extensions_mut().insert(Claims::default());
+            req
+        };
+
+        let first = handle(request(), payload(), data.clone()).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        assert!(first.headers().get(REPLAYED_HEADER).is_none());
+
+        let replay = handle(request(), payload(), data.clone()).await.unwrap();
+        assert_eq!(replay.status(), StatusCode::OK);
+        assert_eq!(replay.headers().get(REPLAYED_HEADER).unwrap(), "true");
+
+        let mut other = payload();
+        other.name = "other-name".to_string();
+        let err = handle(request(), other, data).await.err().unwrap();
+        assert_eq!(
+            actix_web::ResponseError::status_code(&err),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_duplicate_key() {
+        let data = web::Data::new(Dependency::new(crate::repository::memory::Repo::new()));
+
+        let payload = || {
+            Json(RequestPayload {
+                key: Some("dup-key".to_string()),
+                name: "mock-name".to_string(),
+                classing: Classing {
+                    strategy: "random".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+        let request = || {
+            let req = test::TestRequest::default().to_http_request();
+            req.extensions_mut().insert(Claims::default());
+            req
+        };
+
+        let first = handle(request(), payload(), data.clone()).await.unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+
+        let err = handle(request(), payload(), data).await.err().unwrap();
+        assert_eq!(
+            actix_web::ResponseError::status_code(&err),
+            StatusCode::CONFLICT
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_failed_create_frees_idempotency_key() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_save()
+            .withf(|data| data.name == "panicking")
+            .returning(|_| panic!("the store went away"));
+        mock_store.expect_save().returning(|_| {
+            Err(experiment_service::StoreError::InternalError {
+                message: "unavailable".to_string(),
+            }
+            .into())
+        });
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let payload = |name: &str| {
+            Json(RequestPayload {
+                name: name.to_string(),
+                classing: Classing {
+                    strategy: "random".to_string(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+        };
+        let request = |key: &str| {
+            let req = test::TestRequest::default()
+                .insert_header((idempotency::HEADER, key))
+                .to_http_request();
+            req.extensions_mut().insert(Claims::default());
+            req
+        };
+        let is_free = |key: &'static str, name: &'static str| {
+            let data = data.clone();
+            async move {
+                let fingerprint = idempotency::fingerprint(&payload(name).into_inner()).unwrap();
+                idempotency::begin(data.idempotency_repo.as_ref(), "", key, &fingerprint)
+                    .await
+                    .unwrap()
+                    == idempotency::Outcome::Proceed
+            }
+        };
+
+        let err = handle(request("deploy-42"), payload("mock-name"), data.clone())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(
+            actix_web::ResponseError::status_code(&err),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        assert!(is_free("deploy-42", "mock-name").await);
+
+        let panicked = AssertUnwindSafe(handle(
+            request("deploy-43"),
+            payload("panicking"),
+            data.clone(),
+        ))
+        .catch_unwind()
+        .await;
+        assert!(panicked.is_err());
+        // the key is released by a task spawned while unwinding.
+        actix_web::rt::task::yield_now().await;
+        assert!(is_free("deploy-43", "panicking").await);
+    }
 }
@@ -1,12 +1,15 @@
-use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use actix_web::{http::header, web, web::Json, HttpMessage, HttpRequest};
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-use super::{Claims, CustomAPIError, HandlerError};
+use super::{check_if_match, etag_of, publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
 use crate::service::experiment as experiment_service;
 use crate::Dependency;
 
 #[derive(Deserialize)]
 pub struct Params {
+    /// Id or key of the experiment.
     pub id: String,
 }
 
@@ -15,7 +18,7 @@ pub struct ResponsePayload {
     acknowledge: bool,
 }
 
-/// Handle function to handle delete experimental request.
+/// Handle function to handle delete experimental request, honors `If-Match` when given.
 pub async fn handle<ER: experiment_service::Store>(
     req: HttpRequest,
     path: web::Path<Params>,
@@ -31,12 +34,23 @@ pub async fn handle<ER: experiment_service::Store>(
         return Err(HandlerError::Unauthorize.into());
     }
 
-    let data = experiment_service::delete(experiment_repo, &params.id, &channel_id).await;
+    // with `If-Match` the experiment is only deleted at the version the tag was made from.
+    let id = if req.headers().contains_key(header::IF_MATCH) {
+        let current =
+            experiment_service::get_fresh(experiment_repo, &params.id, &channel_id).await?;
+        check_if_match(&req, &etag_of(&current)?, false)?;
+        experiment_service::delete_if(experiment_repo, &current).await?
+    } else {
+        experiment_service::delete(experiment_repo, &params.id, &channel_id).await?
+    };
 
-    match data {
-        Ok(data) => Ok(Json(ResponsePayload { acknowledge: true })),
-        Err(e) => Err(e.into()),
-    }
+    publish_event(
+        dep.event_publisher.as_ref(),
+        event::Event::deleted(&id, &channel_id, Utc::now()),
+    )
+    .await;
+
+    Ok(Json(ResponsePayload { acknowledge: true }))
 }
 
 #[cfg(test)]
@@ -56,9 +70,7 @@ mod tests {
             .expect_delete()
             .return_once(move |_, _| mock_delete_result);
 
-        let data = web::Data::new(Dependency {
-            experiment_repo: mock_store,
-        });
+        let data = web::Data::new(Dependency::new(mock_store));
 
         let mock_claims = Claims::default();
         let req = test::TestRequest::default()
@@ -67,10 +79,117 @@ mod tests {
         req.extensions_mut().insert(mock_claims);
 
         let params = web::Path::from(Params {
-            id: "aaa".to_owned(),
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
         });
 
         let resp = handle(req, params, data).await;
         assert!(resp.is_ok());
     }
+
+    #[actix_web::test]
+    async fn test_handler_precondition_failed() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_fresh()
+            .return_once(|_, _| Ok(experiment_service::Experiment::default()));
+        mock_store.expect_delete().never();
+        mock_store.expect_delete_if().never();
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default()
+            .insert_header((header::IF_MATCH, "\"stale\""))
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+
+        let err = handle(req, params, data).await.err().unwrap();
+        assert_eq!(
+            actix_web::ResponseError::status_code(&err),
+            actix_web::http::StatusCode::PRECONDITION_FAILED
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_handler_by_key() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .withf(|key, _| key == "checkout-button")
+            .return_once(|_, _| {
+                Ok(experiment_service::Experiment {
+                    id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+                    ..Default::default()
+                })
+            });
+        mock_store
+            .expect_delete()
+            .withf(|id, _| id == "62bb13dfea2b3ea78771e305")
+            .return_once(|_, _| Ok(()));
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .withf(|e| {
+                e.kind == event::Kind::Deleted && e.experiment_id == "62bb13dfea2b3ea78771e305"
+            })
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut dep = Dependency::new(mock_store);
+        dep.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "checkout-button".to_owned(),
+        });
+
+        let resp = handle(req, params, data).await;
+        assert!(resp.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_handler_if_match() {
+        let current = || experiment_service::Experiment {
+            id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+            channel_id: "channel".to_owned(),
+            updated_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        let experiment = current();
+        let etag = etag_of(&experiment).unwrap();
+
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_fresh()
+            .return_once(move |_, _| Ok(experiment));
+        mock_store.expect_delete().never();
+        // the experiment changed between the read and the delete.
+        mock_store
+            .expect_delete_if()
+            .withf(|id, channel_id, _| id == "62bb13dfea2b3ea78771e305" && channel_id == "channel")
+            .return_once(|_, _, _| Err(experiment_service::StoreError::Stale.into()));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default()
+            .insert_header((header::IF_MATCH, etag.to_string()))
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+
+        let err = handle(req, params, data).await.err().unwrap();
+        assert_eq!(
+            actix_web::ResponseError::status_code(&err),
+            actix_web::http::StatusCode::PRECONDITION_FAILED
+        );
+    }
 }
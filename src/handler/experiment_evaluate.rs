@@ -0,0 +1,109 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::assignment;
+use crate::service::evaluation;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+#[derive(Deserialize)]
+pub struct Query {
+    pub unit: String,
+    /// Context the assignment is kept in when the persistent mode is `key_contextual`.
+    #[serde(default)]
+    pub context: String,
+}
+
+/// Evaluate experiment handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: evaluation::Evaluation,
+}
+
+/// Handle function to evaluate which variation is served to a unit.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    query: web::Query<Query>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let experiment = experiment_service::get(experiment_repo, &params.id, &channel_id).await?;
+
+    let data = assignment::evaluate(
+        dep.assignment_repo.as_ref(),
+        &experiment,
+        &query.unit,
+        &query.context,
+    )
+    .await?;
+
+    match data {
+        Some(data) => Ok(Json(ResponsePayload { data })),
+        None => Err(HandlerError::NotFound.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let experiment = experiment_service::Experiment {
+            variations: vec![experiment_service::Variance {
+                group_name: "Control".to_owned(),
+                description: String::default(),
+                indicator: "control".to_owned(),
+                weight: 1,
+                values: Default::default(),
+                levels: Default::default(),
+            }],
+            ..Default::default()
+        };
+
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get()
+            .return_once(move |_, _| Ok(experiment));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let query = web::Query(Query {
+            unit: "user-1".to_owned(),
+            context: String::default(),
+        });
+
+        let resp = handle(req, params, query, data).await;
+        assert_eq!(resp.unwrap().data.reason, evaluation::Reason::NotRunning);
+    }
+}
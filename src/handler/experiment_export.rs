@@ -0,0 +1,134 @@
+use actix_web::{
+    http::header, web, web::Bytes, Error as ActixError, HttpMessage, HttpRequest, HttpResponse,
+};
+use futures_util::stream;
+use serde::Deserialize;
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::transfer;
+use crate::Dependency;
+
+/// Export experimental handler's query string.
+#[derive(Deserialize, Debug, Default)]
+pub struct Query {
+    #[serde(default)]
+    pub format: transfer::Format,
+}
+
+/// Handle function to stream every experiment of the channel, a page at a time.
+pub async fn handle<ER: experiment_service::Store + 'static>(
+    req: HttpRequest,
+    query: web::Query<Query>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<HttpResponse, CustomAPIError> {
+    let format = query.into_inner().format;
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    // the first page is loaded before the response starts, so a failure gets its status.
+    let list_query = experiment_service::ListQuery {
+        limit: experiment_service::MAX_PAGE_LIMIT,
+        ..Default::default()
+    };
+    let page = dep.experiment_repo.list(&channel_id, &list_query).await?;
+
+    let mut first = format.header().unwrap_or_default();
+    first.push_str(&format.encode(&page.items)?);
+
+    let pages = stream::unfold(page.next_cursor, move |cursor| {
+        let dep = dep.clone();
+        let channel_id = channel_id.clone();
+        let mut list_query = list_query.clone();
+
+        async move {
+            let cursor = cursor?;
+            let chunk = async {
+                list_query.cursor = Some(experiment_service::Cursor::decode(&cursor)?);
+                let page = dep.experiment_repo.list(&channel_id, &list_query).await?;
+                Ok::<_, anyhow::Error>((format.encode(&page.items)?, page.next_cursor))
+            };
+
+            match chunk.await {
+                Ok((chunk, next)) => Some((Ok(Bytes::from(chunk)), next)),
+                Err(e) => Some((Err(CustomAPIError::from(e).into()), None)),
+            }
+        }
+    });
+
+    let body = stream::StreamExt::chain(
+        stream::once(async move { Ok::<_, ActixError>(Bytes::from(first)) }),
+        pages,
+    );
+
+    Ok(HttpResponse::Ok()
+        .insert_header((header::CONTENT_TYPE, format.content_type()))
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!(
+                "attachment; filename=\"experiments.{}\"",
+                format.extension()
+            ),
+        ))
+        .streaming(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{body, test};
+
+    #[actix_web::test]
+    async fn test_handler_pages() {
+        let mut mock_store = experiment_service::MockStore::new();
+        let cursor = experiment_service::Cursor {
+            key: serde_json::json!(1),
+            id: "b".to_owned(),
+            query: experiment_service::ListQuery::default().fingerprint(),
+        };
+        let expected = cursor.clone();
+        mock_store.expect_list().returning(move |_, q| {
+            let (id, next_cursor) = match &q.cursor {
+                None => ("a", Some(cursor.encode())),
+                Some(c) if c == &expected => ("b", None),
+                Some(_) => panic!("unexpected cursor"),
+            };
+            Ok(experiment_service::Page {
+                items: vec![experiment_service::Experiment {
+                    id: Some(id.to_owned()),
+                    name: id.to_owned(),
+                    ..Default::default()
+                }],
+                next_cursor,
+                total: 2,
+            })
+        });
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query("format=csv").unwrap();
+
+        let resp = handle(req, query, data).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/csv"
+        );
+
+        let body = body::to_bytes(resp.into_body()).await.unwrap();
+        let lines: Vec<&str> = std::str::from_utf8(&body).unwrap().lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].starts_with("a,,a,"));
+        assert!(lines[2].starts_with("b,,b,"));
+    }
+}
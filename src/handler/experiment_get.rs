@@ -1,27 +1,41 @@
-use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::{Claims, CustomAPIError, HandlerError};
+use super::experiment_list::project;
+use super::{etag_of, respond_with_etag, Claims, CustomAPIError, HandlerError};
 use crate::service::experiment as experiment_service;
+use crate::service::projection;
 use crate::Dependency;
 
 #[derive(Deserialize)]
 pub struct Params {
+    /// Id or key of the experiment.
     pub id: String,
 }
 
+/// Get experimental handler's query string.
+#[derive(Deserialize, Debug, Default)]
+pub struct Query {
+    /// Comma separated fields to return, e.g. `name,status,variations.indicator`.
+    pub fields: Option<String>,
+    /// Time to read the experiment as it was then, e.g. `2022-07-01T00:00:00Z`.
+    pub as_of: Option<DateTime<Utc>>,
+}
+
 /// Get experimental handler's response payload.
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ResponsePayload {
-    data: experiment_service::Experiment,
+    data: serde_json::Value,
 }
 
-/// Handle function to handle get experimental request.
+/// Handle function to handle get experimental request, the entity tag is the one of the data.
 pub async fn handle<ER: experiment_service::Store>(
     req: HttpRequest,
     path: web::Path<Params>,
+    query: web::Query<Query>,
     dep: web::Data<Dependency<ER>>,
-) -> Result<Json<ResponsePayload>, CustomAPIError> {
+) -> Result<HttpResponse, CustomAPIError> {
     let experiment_repo = &dep.experiment_repo;
     let params = path.into_inner();
 
@@ -32,12 +46,30 @@ pub async fn handle<ER: experiment_service::Store>(
         return Err(HandlerError::Unauthorize.into());
     }
 
-    let data = experiment_service::get(experiment_repo, &params.id, &channel_id).await;
+    let query = query.into_inner();
+    let fields = query
+        .fields
+        .map(|f| projection::Projection::parse(&f))
+        .transpose()?;
 
-    match data {
-        Ok(data) => Ok(Json(ResponsePayload { data })),
-        Err(e) => Err(e.into()),
-    }
+    let data = match (&fields, query.as_of) {
+        (_, Some(at)) => {
+            let id =
+                experiment_service::resolve_id(experiment_repo, &params.id, &channel_id).await?;
+            dep.experiment_history.as_of(&id, &channel_id, at).await
+        }
+        (Some(p), None) => {
+            let id =
+                experiment_service::resolve_id(experiment_repo, &params.id, &channel_id).await?;
+            experiment_repo.get_projected(&id, &channel_id, p).await
+        }
+        (None, None) => experiment_service::get(experiment_repo, &params.id, &channel_id).await,
+    };
+
+    let data = project(&data?, fields.as_ref())?;
+    let etag = etag_of(&data)?;
+
+    Ok(respond_with_etag(&req, etag, &ResponsePayload { data }))
 }
 
 #[cfg(test)]
@@ -47,7 +79,7 @@ mod tests {
     use crate::Dependency;
     use anyhow::Ok;
 
-    use actix_web::{http::header::ContentType, test};
+    use actix_web::{body, http::header::ContentType, test};
 
     #[actix_web::test]
     async fn test_handler_ok() {
@@ -57,9 +89,7 @@ mod tests {
             .expect_get()
             .return_once(move |_, _| mock_get_result);
 
-        let data = web::Data::new(Dependency {
-            experiment_repo: mock_store,
-        });
+        let data = web::Data::new(Dependency::new(mock_store));
 
         let mock_claims = Claims::default();
         let req = test::TestRequest::default()
@@ -68,10 +98,141 @@ mod tests {
         req.extensions_mut().insert(mock_claims);
 
         let params = web::Path::from(Params {
-            id: "aaa".to_owned(),
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+
+        let resp = handle(req, params, web::Query(Query::default()), data).await;
+        assert!(resp.is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_handler_fields() {
+        let mut mock_store = experiment_service::MockStore::new();
+        let experiment = experiment_service::Experiment {
+            id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+            name: "exp".to_owned(),
+            description: "desc".to_owned(),
+            ..Default::default()
+        };
+        mock_store
+            .expect_get_projected()
+            .withf(|_, _, p| p.paths() == vec!["id", "name"])
+            .return_once(move |_, _, _| Ok(experiment));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let query = web::Query(Query {
+            fields: Some("name".to_owned()),
+            ..Default::default()
+        });
+
+        let resp = handle(req, params, query, data).await.unwrap();
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        let resp: ResponsePayload = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(
+            resp.data,
+            serde_json::json!({"id": "62bb13dfea2b3ea78771e305", "name": "exp"})
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_handler_invalid_fields() {
+        let mock_store = experiment_service::MockStore::new();
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let query = web::Query(Query {
+            fields: Some("password".to_owned()),
+            ..Default::default()
+        });
+
+        let resp = handle(req, params, query, data).await;
+        assert!(resp.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_handler_by_key() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .withf(|key, _| key == "checkout-button")
+            .return_once(|_, _| Ok(experiment_service::Experiment::default()));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "checkout-button".to_owned(),
         });
 
-        let resp = handle(req, params, data).await;
+        let resp = handle(req, params, web::Query(Query::default()), data).await;
         assert!(resp.is_ok());
     }
+
+    #[actix_web::test]
+    async fn test_handler_as_of() {
+        let at = Utc::now() - chrono::Duration::days(1);
+        let mut mock_history = crate::service::history::MockReader::new();
+        mock_history
+            .expect_as_of()
+            .withf(move |id, _, t| id == "62bb13dfea2b3ea78771e305" && *t == at)
+            .return_once(|_, _, _| {
+                Ok(experiment_service::Experiment {
+                    name: "exp then".to_owned(),
+                    ..Default::default()
+                })
+            });
+
+        let mut dep = Dependency::new(experiment_service::MockStore::new());
+        dep.experiment_history = Box::new(mock_history);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let query = web::Query(Query {
+            as_of: Some(at),
+            ..Default::default()
+        });
+
+        let resp = handle(req, params, query, data).await.unwrap();
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        let resp: ResponsePayload = serde_json::from_slice(&bytes).unwrap();
+        assert_eq!(resp.data["name"], "exp then");
+    }
+
+    #[actix_web::test]
+    async fn test_handler_as_of_without_history() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let query = web::Query(Query {
+            as_of: Some(Utc::now()),
+            ..Default::default()
+        });
+
+        let resp = handle(req, params, query, data).await;
+        assert!(resp.is_err());
+    }
 }
@@ -0,0 +1,133 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::service::transfer;
+use crate::Dependency;
+
+/// Upper bound of the import body size.
+pub const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// Import experimental handler's query string.
+#[derive(Deserialize, Debug, Default)]
+pub struct Query {
+    #[serde(default)]
+    pub mode: transfer::Mode,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Import experimental handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: transfer::Report,
+}
+
+/// Handle function to import the ndjson experiments of the body into the channel.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    query: web::Query<Query>,
+    body: String,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let query = query.into_inner();
+
+    let channel_id: String;
+    let owner: serde_json::Value;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+        owner = serde_json::to_value(ut).unwrap_or_default();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = transfer::import(
+        experiment_repo,
+        &channel_id,
+        &body,
+        query.mode,
+        query.dry_run,
+        &owner,
+    )
+    .await?;
+
+    for record in &data.records {
+        let e = match (&record.written, record.action) {
+            (Some(experiment), transfer::Action::Created) => event::Event::created(experiment),
+            (Some(experiment), _) => event::Event::updated(experiment),
+            (None, _) => continue,
+        };
+        publish_event(dep.event_publisher.as_ref(), e).await;
+    }
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn test_handler_dry_run() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store.expect_save().never();
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher.expect_publish().never();
+
+        let mut dep = Dependency::new(mock_store);
+        dep.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query("mode=overwrite&dry_run=true").unwrap();
+        let body = r#"{"name": "imported", "description": "", "variations": [], "classing": {"strategy": "random", "persistent_mode": ""}, "channel_id": "other", "created_at": null, "updated_at": null, "deleted_at": null}"#;
+
+        let resp = handle(req, query, body.to_owned(), data).await.unwrap();
+        assert!(!resp.data.applied);
+        assert_eq!(resp.data.count(transfer::Action::Created), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_handler_publish_the_created() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_save()
+            .times(1)
+            .returning(|_| Ok("62bb13dfea2b3ea78771e305".to_owned()));
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .withf(|e| e.kind == event::Kind::Created)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut dep = Dependency::new(mock_store);
+        dep.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query("mode=overwrite").unwrap();
+        let body = r#"{"name": "imported", "description": "", "variations": [], "classing": {"strategy": "random", "persistent_mode": ""}, "channel_id": "other", "created_at": null, "updated_at": null, "deleted_at": null}"#;
+
+        let resp = handle(req, query, body.to_owned(), data).await.unwrap();
+        assert!(resp.data.applied);
+        assert_eq!(resp.data.count(transfer::Action::Created), 1);
+    }
+}
@@ -0,0 +1,109 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+/// Kill experiment handler's request payload.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RequestPayload {
+    pub reason: String,
+}
+
+/// Kill experiment handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: experiment_service::Experiment,
+}
+
+/// Handle function to force an experiment back to its control variation.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let params = path.into_inner();
+
+    let channel_id: String;
+    let owner: serde_json::Value;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+        owner = serde_json::to_value(ut).unwrap_or_default();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let kill_switch = experiment_service::KillSwitch {
+        scope: experiment_service::KillScope::Experiment,
+        reason: payload.into_inner().reason,
+        triggered_by: Some(owner),
+        triggered_at: Utc::now(),
+    };
+
+    let data = experiment_service::kill(
+        experiment_repo,
+        &params.id,
+        &channel_id,
+        kill_switch.clone(),
+    )
+    .await?;
+
+    publish_event(
+        dep.event_publisher.as_ref(),
+        event::Event::killed(&data, &kill_switch),
+    )
+    .await;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_patch()
+            .withf(|_, _, patch| matches!(patch, experiment_service::Patch::Kill(_)))
+            .return_once(|_, _, patch| {
+                let mut experiment = experiment_service::Experiment::default();
+                patch.apply(&mut experiment);
+                Ok(Some(experiment))
+            });
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let body = Json(RequestPayload {
+            reason: "conversion dropped".to_owned(),
+        });
+
+        let resp = handle(req, params, body, data).await;
+        assert!(resp.is_ok());
+    }
+}
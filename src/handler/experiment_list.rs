@@ -1,21 +1,70 @@
-use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::{Claims, CustomAPIError, HandlerError};
-use crate::service::experiment;
+use super::{etag_of, respond_with_etag, Claims, CustomAPIError, HandlerError};
+use crate::service::{experiment, projection};
 use crate::Dependency;
 
+/// List experimental handler's query string.
+#[derive(Deserialize, Debug, Default)]
+pub struct Query {
+    pub limit: Option<i64>,
+    pub cursor: Option<String>,
+    pub sort: Option<experiment::SortField>,
+    pub order: Option<experiment::SortOrder>,
+
+    pub status: Option<experiment::Status>,
+    pub owner_id: Option<String>,
+    pub name_prefix: Option<String>,
+    pub tag: Option<String>,
+    pub active_at: Option<DateTime<Utc>>,
+
+    /// Comma separated fields to return, e.g. `name,status,variations.indicator`.
+    pub fields: Option<String>,
+}
+
+impl TryFrom<Query> for experiment::ListQuery {
+    type Error = anyhow::Error;
+
+    fn try_from(q: Query) -> Result<Self, Self::Error> {
+        let default = Self::default();
+
+        Ok(Self {
+            limit: q.limit.unwrap_or(default.limit),
+            cursor: q
+                .cursor
+                .map(|c| experiment::Cursor::decode(&c))
+                .transpose()?,
+            sort: q.sort.unwrap_or(default.sort),
+            order: q.order.unwrap_or(default.order),
+            status: q.status,
+            owner_id: q.owner_id,
+            name_prefix: q.name_prefix,
+            tag: q.tag,
+            active_at: q.active_at,
+            fields: q
+                .fields
+                .map(|f| projection::Projection::parse(&f))
+                .transpose()?,
+        })
+    }
+}
+
 /// List user experimental handler's response payload.
 #[derive(Deserialize, Serialize, Debug)]
 pub struct ResponsePayload {
-    data: Vec<experiment::Experiment>,
+    data: Vec<serde_json::Value>,
+    next_cursor: Option<String>,
+    total: u64,
 }
 
 /// Handle function to handle list experimental request.
 pub async fn handle<ER: experiment::Store>(
     req: HttpRequest,
+    query: web::Query<Query>,
     dep: web::Data<Dependency<ER>>,
-) -> Result<Json<ResponsePayload>, CustomAPIError> {
+) -> Result<HttpResponse, CustomAPIError> {
     let experiment_repo = &dep.experiment_repo;
 
     let channel_id: String;
@@ -25,12 +74,34 @@ pub async fn handle<ER: experiment::Store>(
         return Err(HandlerError::Unauthorize.into());
     }
 
-    let data = experiment::list(experiment_repo, &channel_id).await;
+    let query = experiment::ListQuery::try_from(query.into_inner())?;
+    let data = experiment::list(experiment_repo, &channel_id, &query).await;
 
-    match data {
-        Ok(data) => Ok(Json(ResponsePayload { data })),
-        Err(e) => Err(e.into()),
-    }
+    let page = data?;
+    let body = ResponsePayload {
+        data: page
+            .items
+            .iter()
+            .map(|e| project(e, query.fields.as_ref()))
+            .collect::<anyhow::Result<_>>()?,
+        next_cursor: page.next_cursor,
+        total: page.total,
+    };
+    let etag = etag_of(&body)?;
+
+    Ok(respond_with_etag(&req, etag, &body))
+}
+
+/// Serialize the experiment keeping only the projected fields when there is a projection.
+pub(crate) fn project(
+    experiment: &experiment::Experiment,
+    fields: Option<&projection::Projection>,
+) -> anyhow::Result<serde_json::Value> {
+    let value = serde_json::to_value(experiment)?;
+    Ok(match fields {
+        Some(p) => p.apply(value),
+        None => value,
+    })
 }
 
 #[cfg(test)]
@@ -40,19 +111,26 @@ mod tests {
     use crate::Dependency;
     use anyhow::Ok;
 
-    use actix_web::{http::header::ContentType, test};
+    use actix_web::{body, http::header, http::header::ContentType, test};
+
+    async fn read_body(resp: HttpResponse) -> ResponsePayload {
+        let bytes = body::to_bytes(resp.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
 
     #[actix_web::test]
     async fn test_index_ok() {
         let mut mock_store = experiment_service::MockStore::new();
-        let mock_list_result = Ok(vec![experiment_service::Experiment::default()]);
+        let mock_list_result = Ok(experiment_service::Page {
+            items: vec![experiment_service::Experiment::default()],
+            next_cursor: None,
+            total: 1,
+        });
         mock_store
             .expect_list()
-            .return_once(move |_| mock_list_result);
+            .return_once(move |_, _| mock_list_result);
 
-        let data = web::Data::new(Dependency {
-            experiment_repo: mock_store,
-        });
+        let data = web::Data::new(Dependency::new(mock_store));
 
         let mock_claims = Claims::default();
         let req = test::TestRequest::default()
@@ -60,7 +138,174 @@ mod tests {
             .to_http_request();
         req.extensions_mut().insert(mock_claims);
 
-        let resp = handle(req, data).await;
+        let resp = handle(req, web::Query(Query::default()), data).await;
         assert!(resp.is_ok());
     }
+
+    #[actix_web::test]
+    async fn test_query_ok() {
+        let cursor = experiment_service::Cursor {
+            key: serde_json::json!(1656633600000i64),
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+            query: experiment_service::ListQuery {
+                sort: experiment_service::SortField::Name,
+                order: experiment_service::SortOrder::Asc,
+                status: Some(experiment_service::Status::Running),
+                name_prefix: Some("checkout".to_owned()),
+                tag: Some("team:growth".to_owned()),
+                ..Default::default()
+            }
+            .fingerprint(),
+        };
+
+        let mut mock_store = experiment_service::MockStore::new();
+        let expected = cursor.clone();
+        mock_store
+            .expect_list()
+            .withf(move |_, q| {
+                q.limit == 10
+                    && q.cursor.as_ref() == Some(&expected)
+                    && q.sort == experiment_service::SortField::Name
+                    && q.order == experiment_service::SortOrder::Asc
+                    && q.status == Some(experiment_service::Status::Running)
+                    && q.name_prefix.as_deref() == Some("checkout")
+                    && q.tag.as_deref() == Some("team:growth")
+            })
+            .return_once(|_, _| {
+                Ok(experiment_service::Page {
+                    items: vec![],
+                    next_cursor: Some("next".to_owned()),
+                    total: 11,
+                })
+            });
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query(&format!(
+            "limit=10&cursor={}&sort=name&order=asc&status=running&name_prefix=checkout&tag=team:growth",
+            cursor.encode()
+        ))
+        .unwrap();
+
+        let resp = handle(req, query, data).await.unwrap();
+        let resp: ResponsePayload = read_body(resp).await;
+        assert_eq!(resp.next_cursor.as_deref(), Some("next"));
+        assert_eq!(resp.total, 11);
+    }
+
+    #[actix_web::test]
+    async fn test_cursor_of_another_query() {
+        let cursor = experiment_service::Cursor {
+            key: serde_json::json!("checkout"),
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+            query: experiment_service::ListQuery {
+                sort: experiment_service::SortField::Name,
+                ..Default::default()
+            }
+            .fingerprint(),
+        };
+
+        // the store is not asked for the page.
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query(&format!(
+            "cursor={}&sort=name&status=running",
+            cursor.encode()
+        ))
+        .unwrap();
+
+        let err = handle(req, query, data).await.err().unwrap();
+        assert_eq!(
+            actix_web::ResponseError::status_code(&err),
+            actix_web::http::StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_invalid_limit() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query("limit=0").unwrap();
+
+        let resp = handle(req, query, data).await;
+        assert!(resp.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_fields() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_list()
+            .withf(|_, q| {
+                q.fields.as_ref().map(|p| p.paths())
+                    == Some(vec!["id".to_owned(), "status".to_owned()])
+            })
+            .return_once(|_, _| {
+                Ok(experiment_service::Page {
+                    items: vec![experiment_service::Experiment {
+                        id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+                        name: "exp".to_owned(),
+                        ..Default::default()
+                    }],
+                    next_cursor: None,
+                    total: 1,
+                })
+            });
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query("fields=status").unwrap();
+
+        let resp = handle(req, query, data).await.unwrap();
+        let resp: ResponsePayload = read_body(resp).await;
+        assert_eq!(
+            resp.data,
+            vec![serde_json::json!({"id": "62bb13dfea2b3ea78771e305", "status": "scheduled"})]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_not_modified() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_list()
+            .times(2)
+            .returning(|_, _| Ok(experiment_service::Page::default()));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let resp = handle(req, web::Query(Query::default()), data.clone())
+            .await
+            .unwrap();
+        let etag = resp.headers().get(header::ETAG).unwrap().clone();
+
+        let req = test::TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, etag))
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let resp = handle(req, web::Query(Query::default()), data)
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), actix_web::http::StatusCode::NOT_MODIFIED);
+    }
 }
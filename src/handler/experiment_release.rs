@@ -0,0 +1,81 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+/// Release experiment handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: experiment_service::Experiment,
+}
+
+/// Handle function to release the kill switch of an experiment.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let params = path.into_inner();
+
+    let channel_id: String;
+    let owner: serde_json::Value;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+        owner = serde_json::to_value(ut).unwrap_or_default();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = experiment_service::release(experiment_repo, &params.id, &channel_id).await?;
+
+    publish_event(
+        dep.event_publisher.as_ref(),
+        event::Event::released(&data, Some(owner)),
+    )
+    .await;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_patch()
+            .withf(|_, _, patch| *patch == experiment_service::Patch::Release(None))
+            .return_once(|_, _, _| Ok(Some(experiment_service::Experiment::default())));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+
+        let resp = handle(req, params, data).await;
+        assert!(resp.is_ok());
+    }
+}
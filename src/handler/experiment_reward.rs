@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::bandit;
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+/// Reward ingestion handler's request payload, rewards keyed by the variation indicator.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RequestPayload {
+    pub rewards: HashMap<String, Reward>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone, Copy)]
+pub struct Reward {
+    pub trials: u64,
+    pub successes: u64,
+}
+
+/// Reward ingestion handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: experiment_service::Experiment,
+}
+
+/// Handle function to add the observed rewards to a bandit experiment.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let rewards: HashMap<String, bandit::Reward> = payload
+        .into_inner()
+        .rewards
+        .into_iter()
+        .map(|(k, v)| {
+            let reward = bandit::Reward {
+                trials: v.trials,
+                successes: v.successes,
+            };
+            (k, reward)
+        })
+        .collect();
+
+    let data = bandit::add_rewards(experiment_repo, &params.id, &channel_id, &rewards).await?;
+
+    publish_event(dep.event_publisher.as_ref(), event::Event::updated(&data)).await;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_not_bandit() {
+        let mut mock_store = experiment_service::MockStore::new();
+        let mock_get_result = Ok(experiment_service::Experiment::default());
+        mock_store
+            .expect_get()
+            .return_once(move |_, _| mock_get_result);
+        mock_store.expect_patch().never();
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let mock_claims = Claims::default();
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(mock_claims);
+
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let body = Json(RequestPayload::default());
+
+        let resp = handle(req, params, body, data).await;
+        assert!(resp.is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::search;
+use crate::Dependency;
+
+/// Search experimental handler's query string.
+#[derive(Deserialize, Debug)]
+pub struct Query {
+    pub q: String,
+    pub limit: Option<i64>,
+}
+
+/// Search experimental handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<search::Hit>,
+}
+
+/// Handle function to search the experiments of the channel.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    query: web::Query<Query>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = search::search(
+        experiment_repo,
+        &channel_id,
+        &query.q,
+        query.limit.unwrap_or(20),
+    )
+    .await?;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+    use anyhow::Ok;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_search()
+            .withf(|_, q, limit| q == "banner" && *limit == 20)
+            .return_once(|_, _, _| {
+                Ok(vec![search::Hit {
+                    experiment: experiment_service::Experiment::default(),
+                    score: 1.5,
+                }])
+            });
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query("q=banner").unwrap();
+
+        let resp = handle(req, query, data).await;
+        assert_eq!(resp.unwrap().data.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_handler_empty_query() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let query = web::Query::<Query>::from_query("q=%20").unwrap();
+
+        let resp = handle(req, query, data).await;
+        assert!(resp.is_err());
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use actix_web::{
+    http::header::{CacheControl, CacheDirective},
+    rt::time,
+    web,
+    web::Bytes,
+    HttpMessage, HttpRequest, HttpResponse,
+};
+use futures_util::{stream, StreamExt};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::stream::{Message, Resume};
+use crate::Dependency;
+
+/// Header of the id of the last event a resuming client has seen.
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// How long a quiet stream waits before sending a comment, so proxies keep it open.
+pub const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+fn frame(message: &Message) -> Bytes {
+    Bytes::from(format!(
+        "id: {}\nevent: {}\ndata: {}\n\n",
+        message.id, message.event, message.data
+    ))
+}
+
+/// Handle function to stream the changes of the experiments of the channel as server-sent
+/// events. A stream resumed after the events left the buffer starts with a `reset` event,
+/// the client has to reload the experiments.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<HttpResponse, CustomAPIError> {
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let last_event_id = match req.headers().get(LAST_EVENT_ID_HEADER) {
+        Some(v) => Some(v.to_str().map_err(|_| {
+            HandlerError::BadRequest("last event id must be printable characters".to_owned())
+        })?),
+        None => None,
+    };
+
+    let subscription = dep.experiment_stream.subscribe(&channel_id, last_event_id);
+    let head: Vec<Bytes> = match subscription.resume {
+        Resume::Replay(messages) => messages.iter().map(frame).collect(),
+        Resume::Reset => vec![Bytes::from_static(b"event: reset\ndata: {}\n\n")],
+    };
+    let live = stream::unfold(subscription.receiver, |mut receiver| async move {
+        match time::timeout(KEEP_ALIVE, receiver.recv()).await {
+            Ok(Some(message)) => Some((frame(&message), receiver)),
+            Ok(None) => None,
+            Err(_) => Some((Bytes::from_static(b": keep-alive\n\n"), receiver)),
+        }
+    });
+    let body = stream::iter(head)
+        .chain(live)
+        .map(Ok::<_, actix_web::Error>);
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(CacheControl(vec![CacheDirective::NoCache]))
+        .streaming(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::event::{Event, Publisher};
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{body::MessageBody, http::header, test};
+    use chrono::Utc;
+    use std::pin::Pin;
+
+    async fn next_chunk(body: &mut actix_web::body::BoxBody) -> String {
+        let chunk = futures_util::future::poll_fn(|cx| Pin::new(&mut *body).poll_next(cx))
+            .await
+            .unwrap()
+            .unwrap();
+        String::from_utf8(chunk.to_vec()).unwrap()
+    }
+
+    fn request(last_event_id: Option<&str>) -> HttpRequest {
+        let mut req = test::TestRequest::default();
+        if let Some(id) = last_event_id {
+            req = req.insert_header((LAST_EVENT_ID_HEADER, id));
+        }
+        let req = req.to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+        req
+    }
+
+    #[actix_web::test]
+    async fn test_handler_streams_and_resumes() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+        let stream = data.experiment_stream.clone();
+
+        let resp = handle(request(None), data.clone()).await.unwrap();
+        assert_eq!(
+            resp.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+        let mut body = resp.into_body().boxed();
+
+        let deleted = Event::deleted("62bb13dfea2b3ea78771e305", "channel_a", Utc::now());
+        stream.publish(&deleted).await.unwrap();
+        stream.publish(&deleted).await.unwrap();
+
+        let first = next_chunk(&mut body).await;
+        assert!(first.starts_with("id: "));
+        assert!(first.contains("\nevent: deleted\ndata: {"));
+        assert!(first.ends_with("\n\n"));
+        let first_id = first.lines().next().unwrap().trim_start_matches("id: ");
+
+        // the resumed stream starts after the last event seen.
+        let resp = handle(request(Some(first_id)), data.clone()).await.unwrap();
+        let mut resumed = resp.into_body().boxed();
+        assert_eq!(next_chunk(&mut resumed).await, next_chunk(&mut body).await);
+
+        let resp = handle(request(Some("unknown-1")), data).await.unwrap();
+        let mut reset = resp.into_body().boxed();
+        assert!(next_chunk(&mut reset).await.starts_with("event: reset\n"));
+    }
+
+    #[actix_web::test]
+    async fn test_handler_unauthorized() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+
+        assert!(handle(req, data).await.is_err());
+    }
+}
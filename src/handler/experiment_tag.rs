@@ -0,0 +1,131 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::service::tag;
+use crate::Dependency;
+
+/// Bulk tag handler's request payload.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RequestPayload {
+    pub ids: Vec<String>,
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+/// Bulk tag handler's response payload, the outcome per experiment.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<tag::Outcome>,
+}
+
+/// Handle function to add and remove tags of many experiments at once.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let payload = payload.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = tag::bulk_update(
+        experiment_repo,
+        &channel_id,
+        &payload.ids,
+        &payload.add,
+        &payload.remove,
+    )
+    .await?;
+
+    for experiment in data.iter().filter_map(|o| o.experiment.as_ref()) {
+        publish_event(
+            dep.event_publisher.as_ref(),
+            event::Event::updated(experiment),
+        )
+        .await;
+    }
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::header::ContentType, test};
+
+    #[actix_web::test]
+    async fn test_handler_invalid_tag() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let body = Json(RequestPayload {
+            ids: vec!["62bb13dfea2b3ea78771e305".to_owned()],
+            add: vec!["Not Valid".to_owned()],
+            remove: vec![],
+        });
+
+        let resp = handle(req, body, data).await;
+        assert!(resp.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_handler_publish_the_tagged() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_patch()
+            .returning(|id, _, patch| match id {
+                "62bb13dfea2b3ea78771e305" => {
+                    let mut experiment = experiment_service::Experiment::default();
+                    patch.apply(&mut experiment);
+                    Ok(Some(experiment))
+                }
+                _ => Err(experiment_service::StoreError::DocumentNotfound.into()),
+            });
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .withf(|e| e.kind == event::Kind::Updated)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut dep = Dependency::new(mock_store);
+        dep.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default()
+            .insert_header(ContentType::json())
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let body = Json(RequestPayload {
+            ids: vec![
+                "62bb13dfea2b3ea78771e305".to_owned(),
+                "62bb13dfea2b3ea78771e306".to_owned(),
+            ],
+            add: vec!["team:growth".to_owned()],
+            remove: vec![],
+        });
+
+        let resp = handle(req, body, data).await.unwrap();
+        let oks: Vec<bool> = resp.data.iter().map(|o| o.ok).collect();
+        assert_eq!(oks, vec![true, false]);
+    }
+}
@@ -0,0 +1,186 @@
+use actix_web::{http::header::ETag, web, HttpMessage, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+
+use super::experiment_create::RequestPayload;
+use super::{check_if_match, etag_of, publish_event, Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+/// Update experimental handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: experiment_service::Experiment,
+}
+
+/// Handle function to replace the definition of an experiment. The `If-Match` header must
+/// carry the entity tag of the experiment being replaced.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<HttpResponse, CustomAPIError> {
+    let experiment_repo = &dep.experiment_repo;
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let current = experiment_service::get_fresh(experiment_repo, &params.id, &channel_id).await?;
+    check_if_match(&req, &etag_of(&current)?, true)?;
+
+    let data =
+        experiment_service::update(experiment_repo, &current, payload.into_inner().into()).await?;
+
+    publish_event(dep.event_publisher.as_ref(), event::Event::updated(&data)).await;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ETag(etag_of(&data)?))
+        .json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handler::experiment_create::Classing;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{
+        http::{header, StatusCode},
+        test, ResponseError,
+    };
+
+    fn current() -> experiment_service::Experiment {
+        experiment_service::Experiment {
+            id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+            name: "current".to_owned(),
+            channel_id: "channel".to_owned(),
+            status: experiment_service::Status::Running,
+            ..Default::default()
+        }
+    }
+
+    fn payload() -> web::Json<RequestPayload> {
+        web::Json(RequestPayload {
+            name: "updated".to_owned(),
+            classing: Classing {
+                strategy: "random".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn params() -> web::Path<Params> {
+        web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_get_fresh()
+            .return_once(|_, _| Ok(current()));
+        mock_store
+            .expect_update_if()
+            .withf(|e, version| {
+                e.name == "updated"
+                    && e.channel_id == "channel"
+                    && e.status == experiment_service::Status::Running
+                    && *version == experiment_service::Version::of(&current())
+            })
+            .return_once(|_, _| Ok(()));
+        let mut mock_publisher = event::MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .withf(|e| e.kind == event::Kind::Updated)
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let mut dep = Dependency::new(mock_store);
+        dep.event_publisher = Box::new(mock_publisher);
+        let data = web::Data::new(dep);
+
+        let etag = etag_of(&current()).unwrap();
+        let req = test::TestRequest::default()
+            .insert_header((header::IF_MATCH, etag.to_string()))
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+
+        let resp = handle(req, params(), payload(), data).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_ne!(
+            resp.headers().get(header::ETAG).unwrap().to_str().unwrap(),
+            etag.to_string()
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_handler_preconditions() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_get_fresh()
+            .returning(|_, _| Ok(current()));
+        mock_store.expect_update_if().never();
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let err = handle(req, params(), payload(), data.clone())
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_REQUIRED);
+
+        let req = test::TestRequest::default()
+            .insert_header((header::IF_MATCH, "\"stale\""))
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let err = handle(req, params(), payload(), data).await.err().unwrap();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+    }
+
+    #[actix_web::test]
+    async fn test_handler_concurrent_update() {
+        let mut mock_store = experiment_service::MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(experiment_service::StoreError::DocumentNotfound.into()));
+        mock_store
+            .expect_get_fresh()
+            .returning(|_, _| Ok(current()));
+        // another editor replaced the experiment after it was read.
+        mock_store
+            .expect_update_if()
+            .return_once(|_, _| Err(experiment_service::StoreError::Stale.into()));
+
+        let data = web::Data::new(Dependency::new(mock_store));
+
+        let req = test::TestRequest::default()
+            .insert_header((header::IF_MATCH, etag_of(&current()).unwrap().to_string()))
+            .to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let err = handle(req, params(), payload(), data).await.err().unwrap();
+        assert_eq!(err.status_code(), StatusCode::PRECONDITION_FAILED);
+    }
+}
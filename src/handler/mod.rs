@@ -2,22 +2,53 @@
 #![allow(unused_variables)]
 use actix_web::{
     error,
-    http::{header::ContentType, StatusCode},
-    HttpRequest, HttpResponse, ResponseError,
+    http::{
+        header::{ContentType, ETag, EntityTag, IfMatch, IfNoneMatch},
+        StatusCode,
+    },
+    HttpMessage, HttpRequest, HttpResponse, ResponseError,
 };
 use anyhow::anyhow;
 use anyhow::Error as AnyhowError;
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use thiserror::Error;
 
 use crate::middleware::auth;
+use crate::service::event;
 use crate::service::experiment;
 
+pub mod assignment_delete;
+pub mod assignment_export;
+pub mod assignment_get;
+pub mod channel_cache;
+pub mod channel_kill;
+pub mod channel_release;
+pub mod experiment_bandit_freeze;
+pub mod experiment_batch;
 pub mod experiment_create;
 pub mod experiment_delete;
+pub mod experiment_evaluate;
+pub mod experiment_export;
 pub mod experiment_get;
+pub mod experiment_import;
+pub mod experiment_kill;
 pub mod experiment_list;
+pub mod experiment_release;
+pub mod experiment_reward;
+pub mod experiment_search;
+pub mod experiment_stream;
+pub mod experiment_tag;
+pub mod experiment_update;
+pub mod tag_list;
+pub mod webhook_create;
+pub mod webhook_delete;
+pub mod webhook_deliveries;
+pub mod webhook_get;
+pub mod webhook_list;
+pub mod webhook_test;
+pub mod webhook_update;
 
 /// Modify this Claims struct to match up your JWT decoded data.
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
@@ -64,6 +95,13 @@ impl ResponseError for CustomAPIError {
             err.status_code()
         } else if let Some(err) = self.err.downcast_ref::<experiment::UserError>() {
             StatusCode::BAD_REQUEST
+        } else if let Some(err) = self.err.downcast_ref::<experiment::StoreError>() {
+            match err {
+                experiment::StoreError::DocumentNotfound => StatusCode::NOT_FOUND,
+                experiment::StoreError::Conflict { .. } => StatusCode::CONFLICT,
+                experiment::StoreError::Stale => StatusCode::PRECONDITION_FAILED,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            }
         } else {
             StatusCode::INTERNAL_SERVER_ERROR
         }
@@ -74,6 +112,8 @@ impl ResponseError for CustomAPIError {
             "handle_error".to_string()
         } else if let Some(err) = self.err.downcast_ref::<experiment::UserError>() {
             err.code()
+        } else if let Some(err) = self.err.downcast_ref::<experiment::StoreError>() {
+            err.code()
         } else {
             "internal_error".to_string()
         };
@@ -115,6 +155,14 @@ pub enum HandlerError {
     BadRequest(String),
     #[error("data not found")]
     NotFound,
+    #[error("the resource has changed, reload it and try again")]
+    PreconditionFailed,
+    #[error("`If-Match` header is required")]
+    PreconditionRequired,
+    #[error("conflict: `{0}`")]
+    Conflict(String),
+    #[error("unprocessable request: `{0}`")]
+    UnprocessableEntity(String),
 }
 
 impl ResponseError for HandlerError {
@@ -124,6 +172,10 @@ impl ResponseError for HandlerError {
             HandlerError::UnexpectedError => StatusCode::INTERNAL_SERVER_ERROR,
             HandlerError::BadRequest(_) => StatusCode::BAD_REQUEST,
             HandlerError::NotFound => StatusCode::NOT_FOUND,
+            HandlerError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            HandlerError::PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            HandlerError::Conflict(_) => StatusCode::CONFLICT,
+            HandlerError::UnprocessableEntity(_) => StatusCode::UNPROCESSABLE_ENTITY,
         }
     }
 
@@ -137,6 +189,59 @@ impl ResponseError for HandlerError {
     }
 }
 
+/// Publish the event, a failure is only logged since the change itself is already done.
+pub async fn publish_event(publisher: &(dyn event::Publisher + Send + Sync), e: event::Event) {
+    if let Err(err) = publisher.publish(&e).await {
+        println!("unable to publish event: {}", err);
+    }
+}
+
+/// Strong entity tag of the json representation of the data.
+pub fn etag_of(data: &impl Serialize) -> anyhow::Result<EntityTag> {
+    // through a `Value` so the keys of the maps are sorted.
+    let json = serde_json::to_vec(&serde_json::to_value(data)?)?;
+    let digest = Sha256::digest(&json);
+    let hex: String = digest[..16].iter().map(|b| format!("{:02x}", b)).collect();
+
+    Ok(EntityTag::new_strong(hex))
+}
+
+/// Respond the json body with its entity tag, or not modified when the client already has it.
+pub fn respond_with_etag(
+    req: &HttpRequest,
+    etag: EntityTag,
+    body: &impl Serialize,
+) -> HttpResponse {
+    let is_fresh = match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|t| t.weak_eq(&etag)),
+        None => false,
+    };
+
+    if is_fresh {
+        HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .finish()
+    } else {
+        HttpResponse::Ok().insert_header(ETag(etag)).json(body)
+    }
+}
+
+/// Check the `If-Match` precondition against the current entity tag of the resource.
+pub fn check_if_match(
+    req: &HttpRequest,
+    etag: &EntityTag,
+    required: bool,
+) -> Result<(), HandlerError> {
+    match req.get_header::<IfMatch>() {
+        Some(IfMatch::Any) => Ok(()),
+        Some(IfMatch::Items(tags)) if tags.iter().any(|t| t.strong_eq(etag)) => Ok(()),
+        Some(IfMatch::Items(_)) => Err(HandlerError::PreconditionFailed),
+        None if required => Err(HandlerError::PreconditionRequired),
+        None => Ok(()),
+    }
+}
+
 /// Transform json body error into a proper error message.
 /// Use for actix_web
 pub fn handle_json_error(err: error::JsonPayloadError, _req: &HttpRequest) -> error::Error {
@@ -0,0 +1,108 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::event;
+use crate::service::experiment as experiment_service;
+use crate::service::webhook;
+use crate::Dependency;
+
+/// Create webhook handler's request payload.
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct RequestPayload {
+    pub url: String,
+    /// Kinds of the events sent, every kind when empty.
+    #[serde(default)]
+    pub events: Vec<event::Kind>,
+    /// Key of the signatures, generated when not given.
+    pub secret: Option<String>,
+    pub active: Option<bool>,
+}
+
+impl From<RequestPayload> for webhook::Subscription {
+    fn from(rp: RequestPayload) -> Self {
+        Self {
+            url: rp.url,
+            secret: rp.secret.unwrap_or_default(),
+            events: rp.events,
+            active: rp.active.unwrap_or(true),
+            ..Default::default()
+        }
+    }
+}
+
+/// Create webhook handler's response payload, the only one showing the secret.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: webhook::Subscription,
+}
+
+/// Handle function to subscribe an url to the events of the channel.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let mut data: webhook::Subscription = payload.into_inner().into();
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        data.channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data =
+        webhook::create(dep.webhook_repo.as_ref(), dep.webhook_sender.as_ref(), data).await?;
+    println!("webhook {} of channel {} created", data.id, data.channel_id);
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::StatusCode, test, ResponseError};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+        let payload = web::Json(RequestPayload {
+            url: "https://example.com/hook".to_owned(),
+            events: vec![event::Kind::Deleted],
+            ..Default::default()
+        });
+
+        let resp = handle(req, payload, data.clone()).await.unwrap();
+        assert_eq!(resp.data.channel_id, "channel_a");
+        assert!(resp.data.active);
+        assert!(!resp.data.secret.is_empty());
+        assert!(data
+            .webhook_repo
+            .get(&resp.data.id, "channel_a")
+            .await
+            .is_ok());
+    }
+
+    #[actix_web::test]
+    async fn test_handler_invalid_url() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let payload = web::Json(RequestPayload {
+            url: "example.com/hook".to_owned(),
+            ..Default::default()
+        });
+
+        let err = handle(req, payload, data).await.err().unwrap();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,67 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::Serialize;
+
+use super::webhook_get::Params;
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::Dependency;
+
+#[derive(Serialize)]
+pub struct ResponsePayload {
+    acknowledge: bool,
+}
+
+/// Handle function to remove a webhook of the channel along with its deliveries.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    dep.webhook_repo.delete(&params.id, &channel_id).await?;
+
+    Ok(Json(ResponsePayload { acknowledge: true }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::service::webhook;
+    use crate::Dependency;
+
+    use actix_web::test;
+    use mockall::predicate::eq;
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = webhook::MockStore::new();
+        mock_store
+            .expect_delete()
+            .with(eq("62bb13dfea2b3ea78771e305"), eq("channel_a"))
+            .return_once(|_, _| Ok(()));
+
+        let mut dep = Dependency::new(experiment_service::MockStore::new());
+        dep.webhook_repo = std::sync::Arc::new(mock_store);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+
+        assert!(handle(req, params, data).await.is_ok());
+    }
+}
@@ -0,0 +1,116 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::webhook_get::Params;
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::webhook;
+use crate::Dependency;
+
+pub const DEFAULT_LIMIT: i64 = 20;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct Query {
+    pub limit: Option<i64>,
+}
+
+/// Webhook deliveries handler's response payload.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<webhook::Delivery>,
+}
+
+/// Handle function to list the latest deliveries of a webhook, the newest first.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    query: web::Query<Query>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=webhook::MAX_DELIVERIES as i64).contains(&limit) {
+        return Err(HandlerError::BadRequest(format!(
+            "limit must bound between 1 - {}",
+            webhook::MAX_DELIVERIES
+        ))
+        .into());
+    }
+
+    let store = dep.webhook_repo.as_ref();
+    let subscription = store.get(&params.id, &channel_id).await?;
+    let data = store
+        .deliveries(&subscription.id, &channel_id, limit)
+        .await?;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::event::Event;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::StatusCode, test, ResponseError};
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+        let hook = webhook::create(
+            data.webhook_repo.as_ref(),
+            data.webhook_sender.as_ref(),
+            webhook::Subscription {
+                channel_id: "channel_a".to_owned(),
+                url: "https://example.com/hook".to_owned(),
+                active: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        for _ in 0..3 {
+            data.webhook_repo
+                .enqueue(&webhook::Delivery::new(&hook, Event::test("channel_a")))
+                .await
+                .unwrap();
+        }
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+        let params = web::Path::from(Params {
+            id: hook.id.clone(),
+        });
+        let query = web::Query(Query { limit: Some(2) });
+
+        let resp = handle(req, params, query, data).await.unwrap();
+        assert_eq!(resp.data.len(), 2);
+    }
+
+    #[actix_web::test]
+    async fn test_handler_invalid_limit() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+        let query = web::Query(Query { limit: Some(0) });
+
+        let err = handle(req, params, query, data).await.err().unwrap();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+    }
+}
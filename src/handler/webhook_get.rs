@@ -0,0 +1,63 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::webhook;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+/// Get webhook handler's response payload, without the secret.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: webhook::Subscription,
+}
+
+/// Handle function to get a webhook of the channel.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = dep.webhook_repo.get(&params.id, &channel_id).await?;
+
+    Ok(Json(ResponsePayload {
+        data: data.redacted(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::{http::StatusCode, test, ResponseError};
+
+    #[actix_web::test]
+    async fn test_handler_not_found() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims::default());
+        let params = web::Path::from(Params {
+            id: "62bb13dfea2b3ea78771e305".to_owned(),
+        });
+
+        let err = handle(req, params, data).await.err().unwrap();
+        assert_eq!(err.status_code(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,83 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::webhook;
+use crate::Dependency;
+
+/// List webhooks handler's response payload, without the secrets.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: Vec<webhook::Subscription>,
+}
+
+/// Handle function to list the webhooks of the channel.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let data = dep.webhook_repo.list(&channel_id).await?;
+
+    Ok(Json(ResponsePayload {
+        data: data
+            .into_iter()
+            .map(webhook::Subscription::redacted)
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::test;
+    use mockall::predicate::eq;
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_store = webhook::MockStore::new();
+        mock_store
+            .expect_list()
+            .with(eq("channel_a"))
+            .return_once(|_| {
+                Ok(vec![webhook::Subscription {
+                    id: "62bb13dfea2b3ea78771e305".to_owned(),
+                    secret: "it's a secret key".to_owned(),
+                    ..Default::default()
+                }])
+            });
+
+        let mut dep = Dependency::new(experiment_service::MockStore::new());
+        dep.webhook_repo = std::sync::Arc::new(mock_store);
+        let data = web::Data::new(dep);
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+
+        let resp = handle(req, data).await.unwrap();
+        assert_eq!(resp.data.len(), 1);
+        assert!(resp.data[0].secret.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_handler_unauthorized() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+
+        let req = test::TestRequest::default().to_http_request();
+
+        assert!(handle(req, data).await.is_err());
+    }
+}
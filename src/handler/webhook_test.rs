@@ -0,0 +1,103 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::webhook_get::Params;
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::webhook;
+use crate::Dependency;
+
+/// Test webhook handler's response payload, the delivery after its first attempt.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: webhook::Delivery,
+}
+
+/// Handle function to send a test event to a webhook right away. A failed test delivery is
+/// retried as the others.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    config: web::Data<webhook::Config>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let store = dep.webhook_repo.as_ref();
+    let subscription = store.get(&params.id, &channel_id).await?;
+    let data = webhook::send_test(
+        store,
+        dep.webhook_sender.as_ref(),
+        config.as_ref(),
+        &subscription,
+    )
+    .await?;
+
+    Ok(Json(ResponsePayload { data }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::event;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let mut mock_sender = webhook::MockSender::new();
+        mock_sender.expect_check().returning(|_| Ok(()));
+        mock_sender
+            .expect_send()
+            .withf(|url, _, delivery| {
+                url == "https://example.com/hook" && delivery.event.kind == event::Kind::Test
+            })
+            .return_once(|_, _, _| Ok(200));
+
+        let mut dep = Dependency::new(experiment_service::MockStore::new());
+        dep.webhook_sender = Box::new(mock_sender);
+        let data = web::Data::new(dep);
+        let hook = webhook::create(
+            data.webhook_repo.as_ref(),
+            data.webhook_sender.as_ref(),
+            webhook::Subscription {
+                channel_id: "channel_a".to_owned(),
+                url: "https://example.com/hook".to_owned(),
+                active: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+        let params = web::Path::from(Params {
+            id: hook.id.clone(),
+        });
+        let config = web::Data::new(webhook::Config::default());
+
+        let resp = handle(req, params, config, data.clone()).await.unwrap();
+        assert_eq!(resp.data.status, webhook::DeliveryStatus::Delivered);
+        assert_eq!(resp.data.response_status, Some(200));
+
+        let log = data
+            .webhook_repo
+            .deliveries(&hook.id, "channel_a", 10)
+            .await
+            .unwrap();
+        assert_eq!(log[0].status, webhook::DeliveryStatus::Delivered);
+    }
+}
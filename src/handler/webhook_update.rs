@@ -0,0 +1,105 @@
+use actix_web::{web, web::Json, HttpMessage, HttpRequest};
+use serde::{Deserialize, Serialize};
+
+use super::webhook_create::RequestPayload;
+use super::{Claims, CustomAPIError, HandlerError};
+use crate::service::experiment as experiment_service;
+use crate::service::webhook;
+use crate::Dependency;
+
+#[derive(Deserialize)]
+pub struct Params {
+    pub id: String,
+}
+
+/// Update webhook handler's response payload, without the secret.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct ResponsePayload {
+    data: webhook::Subscription,
+}
+
+/// Handle function to replace a webhook of the channel, its secret is rotated when a new
+/// one is given.
+pub async fn handle<ER: experiment_service::Store>(
+    req: HttpRequest,
+    path: web::Path<Params>,
+    payload: web::Json<RequestPayload>,
+    dep: web::Data<Dependency<ER>>,
+) -> Result<Json<ResponsePayload>, CustomAPIError> {
+    let params = path.into_inner();
+
+    let channel_id: String;
+    if let Some(ut) = req.extensions().get::<Claims>() {
+        channel_id = ut.channel_id.clone();
+    } else {
+        return Err(HandlerError::Unauthorize.into());
+    }
+
+    let store = dep.webhook_repo.as_ref();
+    let current = store.get(&params.id, &channel_id).await?;
+    let data = webhook::update(
+        store,
+        dep.webhook_sender.as_ref(),
+        &current,
+        payload.into_inner().into(),
+    )
+    .await?;
+
+    Ok(Json(ResponsePayload {
+        data: data.redacted(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::event;
+    use crate::service::experiment as experiment_service;
+    use crate::Dependency;
+
+    use actix_web::test;
+
+    #[actix_web::test]
+    async fn test_handler_ok() {
+        let data = web::Data::new(Dependency::new(experiment_service::MockStore::new()));
+        let current = webhook::create(
+            data.webhook_repo.as_ref(),
+            data.webhook_sender.as_ref(),
+            webhook::Subscription {
+                channel_id: "channel_a".to_owned(),
+                url: "https://example.com/hook".to_owned(),
+                active: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let req = test::TestRequest::default().to_http_request();
+        req.extensions_mut().insert(Claims {
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        });
+        let params = web::Path::from(Params {
+            id: current.id.clone(),
+        });
+        let payload = web::Json(RequestPayload {
+            url: "https://example.com/v2/hook".to_owned(),
+            events: vec![event::Kind::Created],
+            active: Some(false),
+            ..Default::default()
+        });
+
+        let resp = handle(req, params, payload, data.clone()).await.unwrap();
+        assert_eq!(resp.data.url, "https://example.com/v2/hook");
+        assert!(resp.data.secret.is_empty());
+
+        let stored = data
+            .webhook_repo
+            .get(&current.id, "channel_a")
+            .await
+            .unwrap();
+        assert!(!stored.active);
+        assert_eq!(stored.secret, current.secret);
+    }
+}
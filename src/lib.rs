@@ -1,3 +1,6 @@
+use std::sync::Arc;
+use std::time::Duration;
+
 use actix_web::{web, App, HttpServer};
 
 mod handler;
@@ -7,10 +10,20 @@ pub mod service;
 
 use handler::Claims;
 use middleware::auth as auth_middleware;
+use service::assignment as assignment_service;
+use service::cache as cache_service;
+use service::event as event_service;
 use service::experiment as experiment_service;
+use service::history as history_service;
+use service::idempotency as idempotency_service;
+use service::scheduler as scheduler_service;
+use service::stream as stream_service;
+use service::webhook as webhook_service;
 
 pub struct ServerConfig {
     pub jwt_secret: String,
+    pub scheduler: scheduler_service::Config,
+    pub webhook: webhook_service::Config,
 }
 
 pub struct Dependency<ExpStore>
@@ -18,6 +31,84 @@ where
     ExpStore: experiment_service::Store,
 {
     pub experiment_repo: ExpStore,
+    pub assignment_repo: Box<dyn assignment_service::Store + Send + Sync>,
+    pub scheduler_lease: Box<dyn scheduler_service::Lease + Send + Sync>,
+    pub event_publisher: Box<dyn event_service::Publisher + Send + Sync>,
+    pub idempotency_repo: Box<dyn idempotency_service::Store + Send + Sync>,
+    pub experiment_cache: Box<dyn cache_service::Control + Send + Sync>,
+    pub experiment_history: Box<dyn history_service::Reader + Send + Sync>,
+    pub webhook_repo: Arc<dyn webhook_service::Store + Send + Sync>,
+    pub webhook_sender: Box<dyn webhook_service::Sender + Send + Sync>,
+    pub experiment_stream: stream_service::Broadcaster,
+    /// Whether the store writes the events to an outbox, whose dispatcher then delivers them
+    /// to the webhooks and the streams.
+    pub outbox: bool,
+}
+
+impl<ExpStore> Dependency<ExpStore>
+where
+    ExpStore: experiment_service::Store,
+{
+    /// Create the dependency with the single instance defaults of the other collaborators.
+    pub fn new(experiment_repo: ExpStore) -> Self {
+        let webhook_repo: Arc<dyn webhook_service::Store + Send + Sync> =
+            Arc::new(webhook_service::LocalStore::default());
+        Self {
+            experiment_repo,
+            assignment_repo: Box::new(assignment_service::LocalStore::default()),
+            scheduler_lease: Box::new(scheduler_service::LocalLease),
+            event_publisher: Box::new(event_service::LogPublisher),
+            idempotency_repo: Box::new(idempotency_service::LocalStore::default()),
+            experiment_cache: Box::new(cache_service::NoCache),
+            experiment_history: Box::new(history_service::NoHistory),
+            webhook_repo: webhook_repo.clone(),
+            webhook_sender: Box::new(
+                webhook_service::HttpSender::new(Duration::from_secs(10))
+                    .expect("the http client of the webhooks"),
+            ),
+            experiment_stream: stream_service::Broadcaster::default(),
+            outbox: false,
+        }
+        .with_webhooks(webhook_repo)
+    }
+
+    /// Keep the webhooks in the store.
+    pub fn with_webhooks(mut self, repo: Arc<dyn webhook_service::Store + Send + Sync>) -> Self {
+        self.webhook_repo = repo;
+        self.fan_out()
+    }
+
+    /// Stream the events through the broadcaster.
+    pub fn with_stream(mut self, broadcaster: stream_service::Broadcaster) -> Self {
+        self.experiment_stream = broadcaster;
+        self.fan_out()
+    }
+
+    /// Leave the webhooks and the streams to the dispatcher of the outbox, see `outbox_sinks`.
+    pub fn with_outbox(mut self) -> Self {
+        self.outbox = true;
+        self.fan_out()
+    }
+
+    /// The webhooks accepting the events and the streams, for the outbox to deliver to.
+    pub fn outbox_sinks(&self) -> Vec<Box<dyn event_service::Publisher + Send + Sync>> {
+        vec![
+            Box::new(webhook_service::Notifier(self.webhook_repo.clone())),
+            Box::new(self.experiment_stream.clone()),
+        ]
+    }
+
+    /// Publish the events to the log, the webhooks accepting them and the streams. With the
+    /// outbox they are only logged, it delivers them itself once they are committed.
+    fn fan_out(mut self) -> Self {
+        let mut publishers: Vec<Box<dyn event_service::Publisher + Send + Sync>> =
+            vec![Box::new(event_service::LogPublisher)];
+        if !self.outbox {
+            publishers.extend(self.outbox_sinks());
+        }
+        self.event_publisher = Box::new(event_service::Fanout(publishers));
+        self
+    }
 }
 
 pub async fn init_server<ExpStore>(
@@ -30,6 +121,29 @@ where
 {
     let dependency = web::Data::new(dep);
 
+    let scheduler_dependency = dependency.clone();
+    let scheduler_conf = conf.scheduler.clone();
+    actix_web::rt::spawn(async move {
+        scheduler_service::run(
+            &scheduler_dependency.experiment_repo,
+            scheduler_dependency.scheduler_lease.as_ref(),
+            scheduler_dependency.event_publisher.as_ref(),
+            &scheduler_conf,
+        )
+        .await
+    });
+
+    let webhook_dependency = dependency.clone();
+    let webhook_conf = conf.webhook.clone();
+    actix_web::rt::spawn(async move {
+        webhook_service::run(
+            webhook_dependency.webhook_repo.as_ref(),
+            webhook_dependency.webhook_sender.as_ref(),
+            &webhook_conf,
+        )
+        .await
+    });
+
     HttpServer::new(move || {
         App::new()
             .app_data(web::JsonConfig::default().error_handler(handler::handle_json_error))
@@ -50,6 +164,7 @@ where
                         Claims::default(),
                     ))
                     .route(web::get().to(handler::experiment_get::handle::<ExpStore>))
+                    .route(web::put().to(handler::experiment_update::handle::<ExpStore>))
                     .route(web::delete().to(handler::experiment_delete::handle::<ExpStore>)),
             )
             .service(
@@ -61,6 +176,204 @@ where
                     ))
                     .route(web::get().to(handler::experiment_list::handle::<ExpStore>)),
             )
+            .service(
+                web::resource("/experiments/stream")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::experiment_stream::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiments/batch")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::experiment_batch::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiments/export")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::experiment_export::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiments/import")
+                    .app_data(dependency.clone())
+                    .app_data(web::PayloadConfig::new(
+                        handler::experiment_import::MAX_BODY_SIZE,
+                    ))
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::experiment_import::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiments/tags")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::experiment_tag::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/tags")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::tag_list::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiments/search")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::experiment_search::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiment/{id}/evaluate")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::experiment_evaluate::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiment/{id}/assignments/{unit}")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::assignment_get::handle::<ExpStore>))
+                    .route(web::delete().to(handler::assignment_delete::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/assignments/{unit}")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::assignment_export::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiment/{id}/kill")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::experiment_kill::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiment/{id}/release")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::experiment_release::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiment/{id}/rewards")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::experiment_reward::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/experiment/{id}/bandit/frozen")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::put().to(handler::experiment_bandit_freeze::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/channel/kill-switch")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::channel_kill::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/channel/kill-switch/release")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::channel_release::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/channel/cache")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::channel_cache::stats::<ExpStore>))
+                    .route(web::delete().to(handler::channel_cache::flush::<ExpStore>)),
+            )
+            .service(
+                web::resource("/webhooks")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::webhook_list::handle::<ExpStore>))
+                    .route(web::post().to(handler::webhook_create::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/webhooks/{id}")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::webhook_get::handle::<ExpStore>))
+                    .route(web::put().to(handler::webhook_update::handle::<ExpStore>))
+                    .route(web::delete().to(handler::webhook_delete::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/webhooks/{id}/deliveries")
+                    .app_data(dependency.clone())
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::get().to(handler::webhook_deliveries::handle::<ExpStore>)),
+            )
+            .service(
+                web::resource("/webhooks/{id}/test")
+                    .app_data(dependency.clone())
+                    .app_data(web::Data::new(conf.webhook.clone()))
+                    .wrap(auth_middleware::JwtExtractor::new(
+                        conf.jwt_secret.clone(),
+                        Claims::default(),
+                    ))
+                    .route(web::post().to(handler::webhook_test::handle::<ExpStore>)),
+            )
     })
     .bind(("0.0.0.0", port))?
     .run()
@@ -1,9 +1,28 @@
 use anyhow::Result;
-use mongodb::{bson::doc, options::ClientOptions, Client, Collection};
-use std::env;
+use mongodb::{bson::doc, options::ClientOptions, Client, Database};
+use std::{collections::HashMap, env, sync::Arc, time::Duration};
 
+use enigma_admin_server::repository::assignment as assignment_repo;
+use enigma_admin_server::repository::cache as cache_repo;
+use enigma_admin_server::repository::event_sourced as event_sourced_repo;
 use enigma_admin_server::repository::experiment as experiment_repo;
+use enigma_admin_server::repository::history as history_repo;
+use enigma_admin_server::repository::idempotency as idempotency_repo;
+use enigma_admin_server::repository::lease as lease_repo;
+use enigma_admin_server::repository::memory as memory_repo;
+use enigma_admin_server::repository::migration;
+use enigma_admin_server::repository::outbox as outbox_repo;
+use enigma_admin_server::repository::postgres as postgres_repo;
+use enigma_admin_server::repository::sqlite as sqlite_repo;
+use enigma_admin_server::repository::webhook as webhook_repo;
+use enigma_admin_server::service::cache as cache_service;
+use enigma_admin_server::service::event as event_service;
 use enigma_admin_server::service::experiment as experiment_service;
+use enigma_admin_server::service::history as history_service;
+use enigma_admin_server::service::outbox as outbox_service;
+use enigma_admin_server::service::scheduler as scheduler_service;
+use enigma_admin_server::service::stream as stream_service;
+use enigma_admin_server::service::webhook as webhook_service;
 use enigma_admin_server::*;
 
 #[actix_web::main]
@@ -15,42 +34,354 @@ async fn main() -> std::io::Result<()> {
 
     let jwt_secret = env::var("JWT_SECRET").expect("JWT_SECRET is not found in env");
 
-    let experiment_coll = init_mongo_coll::<experiment_repo::Document>(
+    let scheduler = scheduler_service::Config {
+        interval: Duration::from_secs(env_or("SCHEDULER_INTERVAL_SECS", 30)),
+        lease_ttl: Duration::from_secs(env_or("SCHEDULER_LEASE_TTL_SECS", 90)),
+        holder: env::var("HOSTNAME").unwrap_or_else(|_| bson::oid::ObjectId::new().to_hex()),
+    };
+    let webhook = webhook_service::Config {
+        interval: Duration::from_millis(env_or("WEBHOOK_INTERVAL_MS", 1000)),
+        max_attempts: env_or("WEBHOOK_MAX_ATTEMPTS", 8),
+        ..Default::default()
+    };
+    let conf = ServerConfig {
+        jwt_secret,
+        scheduler,
+        webhook,
+    };
+
+    match env::var("STORE_BACKEND")
+        .unwrap_or_else(|_| "mongo".to_owned())
+        .as_str()
+    {
+        "mongo" => run_with_mongo(port, conf).await,
+        "memory" => {
+            println!("Using the in-memory store, the data is lost on exit.");
+            serve(port, conf, Dependency::new(memory_repo::Repo::new()), None).await
+        }
+        "sqlite" => {
+            let path = env::var("SQLITE_PATH").unwrap_or_else(|_| "enigma.db".to_owned());
+            let repo = sqlite_repo::Repo::open(&path).unwrap();
+            println!("Using the sqlite store at {}.", path);
+            serve(port, conf, Dependency::new(repo), None).await
+        }
+        "postgres" => {
+            let repo = postgres_repo::Repo::connect(
+                &env::var("POSTGRES_URL").expect("POSTGRES_URL is not found in env"),
+                env_or("POSTGRES_POOL_SIZE", 16),
+            )
+            .unwrap();
+            for name in repo.migrate().await.unwrap() {
+                println!("migration {} applied", name);
+            }
+
+            // the replicas share the lease, the assignments and the idempotency keys.
+            let mut dep = Dependency::new(repo.clone());
+            dep.scheduler_lease = Box::new(repo.leases());
+            dep.assignment_repo = Box::new(repo.assignments());
+            dep.idempotency_repo = Box::new(repo.idempotency(Duration::from_secs(env_or(
+                "IDEMPOTENCY_WINDOW_SECS",
+                24 * 60 * 60,
+            ))));
+            serve(port, conf, dep, None).await
+        }
+        "events" => run_event_sourced(port, conf).await,
+        backend => panic!(
+            "STORE_BACKEND `{}` is not supported, use mongo, events, postgres, sqlite or memory",
+            backend
+        ),
+    }
+}
+
+async fn run_with_mongo(port: u16, conf: ServerConfig) -> std::io::Result<()> {
+    let (client, db) = init_mongo_db(
         &env::var("MONGO_URL").expect("MONGO_URL is not found in env"),
         &env::var("MONGO_DBNAME").expect("MONGO_DBNAME is not found in env"),
-        &env::var("MONGO_COLLECTION_EXPERIMENT")
-            .expect("MONGO_COLLECTION_EXPERIMENT is not found in env"),
     )
     .await
     .unwrap();
 
-    let experiment_repo = init_experiment_repository(experiment_coll);
+    let experiment_coll_name = env::var("MONGO_COLLECTION_EXPERIMENT")
+        .expect("MONGO_COLLECTION_EXPERIMENT is not found in env");
+
+    // `migrate` brings the stored documents to the current shape then exits.
+    let migrate_only = env::args().nth(1).as_deref() == Some("migrate");
+    if migrate_only || env_or("MIGRATE_ON_STARTUP", true) {
+        run_migrations(&db, &experiment_coll_name).await.unwrap();
+    }
+    if migrate_only {
+        return Ok(());
+    }
+
+    let experiment_coll = db.collection::<experiment_repo::Document>(&experiment_coll_name);
+    let lease_coll = db.collection::<lease_repo::Document>(
+        &env::var("MONGO_COLLECTION_LEASE").unwrap_or_else(|_| "leases".to_owned()),
+    );
+
+    let assignment_coll = db.collection::<assignment_repo::Document>(
+        &env::var("MONGO_COLLECTION_ASSIGNMENT").unwrap_or_else(|_| "assignments".to_owned()),
+    );
+
+    let idempotency_coll = db.collection::<idempotency_repo::Document>(
+        &env::var("MONGO_COLLECTION_IDEMPOTENCY").unwrap_or_else(|_| "idempotency_keys".to_owned()),
+    );
+
+    let mut experiment_repo = experiment_repo::Repo::new(experiment_coll).with_client(client);
+    let mut dispatcher = None;
+    if env_or("OUTBOX_ENABLED", false) {
+        let outbox_coll = db.collection::<outbox_repo::Document>(
+            &env::var("MONGO_COLLECTION_OUTBOX").unwrap_or_else(|_| "outbox".to_owned()),
+        );
+        let outbox = outbox_repo::Repo::new(outbox_coll.clone());
+        outbox.create_indexes().await.unwrap();
+        experiment_repo = experiment_repo.with_outbox(outbox_coll);
+
+        dispatcher = Some(Dispatcher {
+            outbox,
+            sinks: outbox_sinks().unwrap(),
+            config: outbox_service::Config {
+                interval: Duration::from_millis(env_or("OUTBOX_INTERVAL_MS", 1000)),
+                max_attempts: env_or("OUTBOX_MAX_ATTEMPTS", 10),
+                ..Default::default()
+            },
+        });
+    }
+    let experiment_repo = init_experiment_repository(experiment_repo).await.unwrap();
+    let assignment_repo = assignment_repo::Repo::new(assignment_coll);
+    assignment_repo.create_indexes().await.unwrap();
+    let idempotency_repo = idempotency_repo::Repo::new(
+        idempotency_coll,
+        Duration::from_secs(env_or("IDEMPOTENCY_WINDOW_SECS", 24 * 60 * 60)),
+    );
+    idempotency_repo.create_indexes().await.unwrap();
+    let webhook_repo = Arc::new(webhook_repo::Repo::new(
+        db.collection(
+            &env::var("MONGO_COLLECTION_WEBHOOK").unwrap_or_else(|_| "webhooks".to_owned()),
+        ),
+        db.collection(
+            &env::var("MONGO_COLLECTION_WEBHOOK_DELIVERY")
+                .unwrap_or_else(|_| "webhook_deliveries".to_owned()),
+        ),
+    ));
+    webhook_repo.create_indexes().await.unwrap();
+
+    serve(
+        port,
+        conf,
+        Dependency {
+            experiment_repo,
+            assignment_repo: Box::new(assignment_repo),
+            scheduler_lease: Box::new(lease_repo::Repo::new(lease_coll)),
+            event_publisher: Box::new(event_service::LogPublisher),
+            idempotency_repo: Box::new(idempotency_repo),
+            experiment_cache: Box::new(cache_service::NoCache),
+            experiment_history: Box::new(history_service::NoHistory),
+            webhook_repo: webhook_repo.clone(),
+            webhook_sender: webhook_sender(),
+            experiment_stream: stream_service::Broadcaster::default(),
+            outbox: dispatcher.is_some(),
+        }
+        .with_webhooks(webhook_repo),
+        dispatcher,
+    )
+    .await
+}
+
+/// Dispatcher of the outbox of the MongoDB store.
+struct Dispatcher {
+    outbox: outbox_repo::Repo,
+    sinks: Vec<Box<dyn event_service::Publisher + Send + Sync>>,
+    config: outbox_service::Config,
+}
 
+/// Start the server, caching the reads of the experiment store when `CACHE_TTL_SECS` is set.
+/// The dispatcher of the outbox, if any, delivers the events to the webhooks and the streams
+/// too.
+async fn serve<S>(
+    port: u16,
+    conf: ServerConfig,
+    mut dep: Dependency<S>,
+    dispatcher: Option<Dispatcher>,
+) -> std::io::Result<()>
+where
+    S: experiment_service::Store + Send + Sync + 'static,
+{
+    dep.webhook_sender = webhook_sender();
+    let dep = dep.with_stream(stream_service::Broadcaster::new(env_or(
+        "STREAM_REPLAY_SIZE",
+        stream_service::DEFAULT_REPLAY_SIZE,
+    )));
+
+    if let Some(mut dispatcher) = dispatcher {
+        dispatcher.sinks.extend(dep.outbox_sinks());
+        actix_web::rt::spawn(async move {
+            outbox_service::run(&dispatcher.outbox, &dispatcher.sinks, &dispatcher.config).await
+        });
+    }
+
+    let ttl = env_or("CACHE_TTL_SECS", 0);
+    if ttl == 0 {
+        return init_server(port, conf, dep).await;
+    }
+
+    let config = cache_repo::Config {
+        ttl: Duration::from_secs(ttl),
+        channel_ttls: channel_ttls(&env::var("CACHE_CHANNEL_TTL_SECS").unwrap_or_default()),
+        max_entries: env_or("CACHE_MAX_ENTRIES", 10_000),
+    };
+    println!("Caching the experiments for {}s.", ttl);
+
+    let experiment_repo = cache_repo::Repo::new(dep.experiment_repo, config);
+    let experiment_cache = Box::new(experiment_repo.handle());
     init_server(
         port,
-        ServerConfig { jwt_secret },
-        Dependency { experiment_repo },
+        conf,
+        Dependency {
+            experiment_repo,
+            assignment_repo: dep.assignment_repo,
+            scheduler_lease: dep.scheduler_lease,
+            event_publisher: dep.event_publisher,
+            idempotency_repo: dep.idempotency_repo,
+            experiment_cache,
+            experiment_history: dep.experiment_history,
+            webhook_repo: dep.webhook_repo,
+            webhook_sender: dep.webhook_sender,
+            experiment_stream: dep.experiment_stream,
+            outbox: dep.outbox,
+        },
     )
     .await
 }
 
-async fn init_mongo_coll<T>(url: &str, dbname: &str, collection: &str) -> Result<Collection<T>> {
+/// Parse the ttl of the channels, like `channel_a=5,channel_b=120` in seconds.
+fn channel_ttls(value: &str) -> HashMap<String, Duration> {
+    value
+        .split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(channel_id, secs)| {
+            let secs = secs
+                .trim()
+                .parse()
+                .expect("CACHE_CHANNEL_TTL_SECS must be like `channel=seconds,...`");
+            (channel_id.trim().to_owned(), Duration::from_secs(secs))
+        })
+        .collect()
+}
+
+async fn run_event_sourced(port: u16, conf: ServerConfig) -> std::io::Result<()> {
+    let (_, db) = init_mongo_db(
+        &env::var("MONGO_URL").expect("MONGO_URL is not found in env"),
+        &env::var("MONGO_DBNAME").expect("MONGO_DBNAME is not found in env"),
+    )
+    .await
+    .unwrap();
+
+    let log = history_repo::Repo::new(db.collection(
+        &env::var("MONGO_COLLECTION_EVENT").unwrap_or_else(|_| "experiment_events".to_owned()),
+    ));
+    log.create_indexes().await.unwrap();
+
+    // `rebuild` replays the log, checking it folds, then exits.
+    let (repo, rebuilt) = event_sourced_repo::Repo::open(log).await.unwrap();
+    println!(
+        "{} experiments rebuilt from {} changes.",
+        rebuilt.experiments, rebuilt.changes
+    );
+    if env::args().nth(1).as_deref() == Some("rebuild") {
+        return Ok(());
+    }
+
+    let mut dep = Dependency::new(repo);
+    dep.experiment_history = Box::new(dep.experiment_repo.reader());
+    serve(port, conf, dep, None).await
+}
+
+async fn init_mongo_db(url: &str, dbname: &str) -> Result<(Client, Database)> {
     let opts = ClientOptions::parse(url).await?;
     let client = Client::with_options(opts)?;
     let db_instance = client.database(dbname);
-    let coll = db_instance.collection::<T>(collection);
 
-    client
-        .database(dbname)
-        .run_command(doc! {"ping": 1}, None)
-        .await?;
+    db_instance.run_command(doc! {"ping": 1}, None).await?;
     println!("Connected successfully.");
 
-    Ok(coll)
+    Ok((client, db_instance))
 }
 
-fn init_experiment_repository(
-    coll: Collection<experiment_repo::Document>,
-) -> impl experiment_service::Store {
-    experiment_repo::Repo::new(coll)
+async fn run_migrations(db: &Database, experiment_coll: &str) -> Result<()> {
+    let migrator = migration::Migrator::new(
+        db.collection(
+            &env::var("MONGO_COLLECTION_MIGRATION").unwrap_or_else(|_| "migrations".to_owned()),
+        ),
+        migration::experiment_migrations(db.collection(experiment_coll)),
+    );
+
+    for record in migrator.run().await? {
+        println!(
+            "migration {} {} applied, {} documents changed",
+            record.version, record.name, record.modified
+        );
+    }
+    Ok(())
+}
+
+/// The sinks of `OUTBOX_SINKS`, a comma separated list of `log`, `file` and `http`.
+fn outbox_sinks() -> Result<Vec<Box<dyn event_service::Publisher + Send + Sync>>> {
+    let mut sinks: Vec<Box<dyn event_service::Publisher + Send + Sync>> = vec![];
+    for sink in env::var("OUTBOX_SINKS")
+        .unwrap_or_else(|_| "log".to_owned())
+        .split(',')
+        .map(str::trim)
+    {
+        match sink {
+            "log" => sinks.push(Box::new(event_service::LogPublisher)),
+            "file" => sinks.push(Box::new(event_service::FilePublisher::new(
+                env::var("OUTBOX_FILE_PATH").unwrap_or_else(|_| "events.jsonl".to_owned()),
+            ))),
+            "http" => sinks.push(Box::new(event_service::HttpPublisher::new(
+                &env::var("OUTBOX_HTTP_URL")?,
+                Duration::from_secs(env_or("OUTBOX_HTTP_TIMEOUT_SECS", 10)),
+            )?)),
+            sink => anyhow::bail!(
+                "outbox sink `{}` is not supported, use log, file or http",
+                sink
+            ),
+        }
+    }
+    Ok(sinks)
+}
+
+/// Sender of the webhooks, waiting `WEBHOOK_TIMEOUT_SECS` for the responses. The hosts of
+/// `WEBHOOK_ALLOWED_HOSTS`, comma separated, may be private addresses.
+fn webhook_sender() -> Box<dyn webhook_service::Sender + Send + Sync> {
+    let timeout = Duration::from_secs(env_or("WEBHOOK_TIMEOUT_SECS", 10));
+    let allowed_hosts = env::var("WEBHOOK_ALLOWED_HOSTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|h| !h.is_empty())
+        .map(str::to_owned)
+        .collect();
+    Box::new(webhook_service::HttpSender::with_allowed_hosts(timeout, allowed_hosts).unwrap())
+}
+
+fn env_or<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|v| v.parse::<T>().ok())
+        .unwrap_or(default)
+}
+
+async fn init_experiment_repository(
+    repo: experiment_repo::Repo,
+) -> Result<impl experiment_service::Store> {
+    let check_only = env_or("INDEX_CHECK_ONLY", false);
+    for index in repo.reconcile_indexes(check_only).await? {
+        match (index.drift, index.fixed) {
+            (experiment_repo::Drift::InSync, _) => {}
+            (drift, true) => println!("index {}: {}, fixed", index.name, drift),
+            (drift, false) => println!("index {}: {}", index.name, drift),
+        }
+    }
+
+    Ok(repo)
 }
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use futures_util::{TryFutureExt, TryStreamExt};
+use mongodb::{bson::doc, bson::oid, options::IndexOptions, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+
+use super::is_duplicate_key;
+use crate::service::assignment as service;
+use crate::service::experiment as experiment_service;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Document {
+    pub _id: Option<oid::ObjectId>,
+    pub channel_id: String,
+    pub experiment_id: String,
+    pub unit: String,
+    pub context: String,
+    pub indicator: String,
+    #[serde(with = "ts_milliseconds")]
+    pub assigned_at: DateTime<Utc>,
+}
+
+impl From<service::Assignment> for Document {
+    fn from(data: service::Assignment) -> Self {
+        Self {
+            _id: None,
+            channel_id: data.channel_id,
+            experiment_id: data.experiment_id,
+            unit: data.unit,
+            context: data.context,
+            indicator: data.indicator,
+            assigned_at: data.assigned_at,
+        }
+    }
+}
+
+impl From<Document> for service::Assignment {
+    fn from(doc: Document) -> Self {
+        Self {
+            channel_id: doc.channel_id,
+            experiment_id: doc.experiment_id,
+            unit: doc.unit,
+            context: doc.context,
+            indicator: doc.indicator,
+            assigned_at: doc.assigned_at,
+        }
+    }
+}
+
+pub struct Repo {
+    coll: Collection<Document>,
+}
+
+impl Repo {
+    pub fn new(coll: Collection<Document>) -> Self {
+        Self { coll }
+    }
+
+    /// Create the unique index which keeps a single assignment per unit and context.
+    pub async fn create_indexes(&self) -> Result<()> {
+        let index = IndexModel::builder()
+            .keys(doc! {"channel_id": 1, "unit": 1, "experiment_id": 1, "context": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+
+        self.coll.create_index(index, None).await?;
+        Ok(())
+    }
+}
+
+fn internal_error(e: mongodb::error::Error) -> experiment_service::StoreError {
+    experiment_service::StoreError::InternalError {
+        message: e.to_string(),
+    }
+}
+
+#[async_trait]
+impl service::Store for Repo {
+    async fn find(
+        &self,
+        channel_id: &str,
+        experiment_id: &str,
+        unit: &str,
+        context: &str,
+    ) -> Result<Option<service::Assignment>> {
+        let filter = doc! {
+            "channel_id": channel_id,
+            "unit": unit,
+            "experiment_id": experiment_id,
+            "context": context,
+        };
+
+        let doc = self
+            .coll
+            .find_one(filter, None)
+            .await
+            .map_err(internal_error)?;
+
+        Ok(doc.map(|d| d.into()))
+    }
+
+    async fn insert(&self, assignment: &service::Assignment) -> Result<service::Assignment> {
+        let mut document = Document::from(assignment.clone());
+        document._id = Some(oid::ObjectId::new());
+
+        match self.coll.insert_one(document, None).await {
+            Ok(_) => Ok(assignment.clone()),
+            // another evaluation assigned the unit first, its assignment is kept.
+            Err(e) if is_duplicate_key(&e) => self
+                .find(
+                    &assignment.channel_id,
+                    &assignment.experiment_id,
+                    &assignment.unit,
+                    &assignment.context,
+                )
+                .await?
+                .ok_or_else(|| experiment_service::StoreError::DocumentNotfound.into()),
+            Err(e) => Err(internal_error(e).into()),
+        }
+    }
+
+    async fn list(
+        &self,
+        channel_id: &str,
+        unit: &str,
+        experiment_id: Option<String>,
+    ) -> Result<Vec<service::Assignment>> {
+        let mut filter = doc! {"channel_id": channel_id, "unit": unit};
+        if let Some(experiment_id) = experiment_id {
+            filter.insert("experiment_id", experiment_id);
+        }
+
+        let cursor = self.coll.find(filter, None).await.map_err(internal_error)?;
+
+        let docs: Vec<service::Assignment> = cursor
+            .map_ok(|d| d.into())
+            .try_collect()
+            .map_err(internal_error)
+            .await?;
+
+        Ok(docs)
+    }
+
+    async fn delete(&self, channel_id: &str, unit: &str, experiment_id: &str) -> Result<u64> {
+        let result = self
+            .coll
+            .delete_many(
+                doc! {"channel_id": channel_id, "unit": unit, "experiment_id": experiment_id},
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+
+        Ok(result.deleted_count)
+    }
+}
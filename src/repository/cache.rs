@@ -0,0 +1,551 @@
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::service::cache::{Control, Stats};
+use crate::service::experiment as service;
+use crate::service::projection::Projection;
+use crate::service::search;
+use crate::service::tag;
+
+/// Settings of the cache.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// How long a read is served from the cache, unless the channel has its own.
+    pub ttl: Duration,
+    pub channel_ttls: HashMap<String, Duration>,
+    /// Upper bound of the cached reads, all the channels together, the oldest go first.
+    pub max_entries: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            ttl: Duration::from_secs(30),
+            channel_ttls: HashMap::new(),
+            max_entries: 10_000,
+        }
+    }
+}
+
+impl Config {
+    fn ttl_of(&self, channel_id: &str) -> Duration {
+        self.channel_ttls
+            .get(channel_id)
+            .copied()
+            .unwrap_or(self.ttl)
+    }
+}
+
+/// Experiment store caching the reads of another one. A write through it drops the cached
+/// reads of the channel, the writes of the other instances are seen once the reads expire.
+pub struct Repo<S> {
+    inner: S,
+    shared: Arc<Shared>,
+}
+
+impl<S: service::Store> Repo<S> {
+    pub fn new(inner: S, config: Config) -> Self {
+        Self {
+            inner,
+            shared: Arc::new(Shared {
+                config,
+                state: Mutex::new(State::default()),
+            }),
+        }
+    }
+
+    /// Handle to look into and flush the cache, e.g. from the admin endpoints.
+    pub fn handle(&self) -> Handle {
+        Handle(self.shared.clone())
+    }
+
+    async fn read<T: Cacheable>(
+        &self,
+        channel_id: &str,
+        key: Read,
+        fetch: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let generation = match self.shared.lookup(channel_id, &key) {
+            Ok(value) => return Ok(value),
+            Err(generation) => generation,
+        };
+
+        let value = fetch.await?;
+        self.shared
+            .keep(channel_id, key, generation, value.clone().wrap());
+        Ok(value)
+    }
+}
+
+/// Handle on the cache of a `Repo`.
+#[derive(Clone)]
+pub struct Handle(Arc<Shared>);
+
+impl Control for Handle {
+    fn stats(&self, channel_id: &str) -> Stats {
+        let state = self.0.state.lock().unwrap();
+        let channel = state.channels.get(channel_id);
+        Stats {
+            enabled: true,
+            entries: channel.map(|c| c.entries.len()).unwrap_or_default(),
+            hits: channel.map(|c| c.hits).unwrap_or_default(),
+            misses: channel.map(|c| c.misses).unwrap_or_default(),
+            evictions: channel.map(|c| c.evictions).unwrap_or_default(),
+            invalidations: channel.map(|c| c.invalidations).unwrap_or_default(),
+        }
+    }
+
+    fn flush(&self, channel_id: &str) -> usize {
+        self.0.invalidate(channel_id)
+    }
+}
+
+/// The read a value is cached for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Read {
+    Id(String),
+    Key(String),
+    /// The id and the debug representation of the projection.
+    Projected(String, String),
+    /// The debug representation of the query.
+    List(String),
+    Tags,
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Experiment(Box<service::Experiment>),
+    Page(service::Page),
+    Tags(Vec<tag::TagCount>),
+}
+
+trait Cacheable: Clone + Sized {
+    fn wrap(self) -> Value;
+    fn unwrap(value: &Value) -> Option<Self>;
+}
+
+impl Cacheable for service::Experiment {
+    fn wrap(self) -> Value {
+        Value::Experiment(Box::new(self))
+    }
+
+    fn unwrap(value: &Value) -> Option<Self> {
+        match value {
+            Value::Experiment(e) => Some(e.as_ref().clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Cacheable for service::Page {
+    fn wrap(self) -> Value {
+        Value::Page(self)
+    }
+
+    fn unwrap(value: &Value) -> Option<Self> {
+        match value {
+            Value::Page(p) => Some(p.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl Cacheable for Vec<tag::TagCount> {
+    fn wrap(self) -> Value {
+        Value::Tags(self)
+    }
+
+    fn unwrap(value: &Value) -> Option<Self> {
+        match value {
+            Value::Tags(t) => Some(t.clone()),
+            _ => None,
+        }
+    }
+}
+
+struct Entry {
+    value: Value,
+    expires_at: Instant,
+    /// Position of the entry in the eviction order.
+    seq: u64,
+}
+
+#[derive(Default)]
+struct Channel {
+    entries: HashMap<Read, Entry>,
+    /// Bumped by every invalidation, so a read started before a write is not kept.
+    generation: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+    invalidations: u64,
+}
+
+#[derive(Default)]
+struct State {
+    channels: HashMap<String, Channel>,
+    /// Insertion order of the entries, the ones replaced or dropped since are skipped.
+    order: VecDeque<(u64, String, Read)>,
+    next_seq: u64,
+    size: usize,
+}
+
+struct Shared {
+    config: Config,
+    state: Mutex<State>,
+}
+
+impl Shared {
+    /// The cached value, or the generation of the channel to keep the value read instead.
+    fn lookup<T: Cacheable>(&self, channel_id: &str, key: &Read) -> std::result::Result<T, u64> {
+        let mut state = self.state.lock().unwrap();
+        let channel = state.channels.entry(channel_id.to_owned()).or_default();
+
+        let cached = match channel.entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => T::unwrap(&entry.value),
+            _ => None,
+        };
+        if let Some(value) = cached {
+            channel.hits += 1;
+            return Ok(value);
+        }
+
+        channel.misses += 1;
+        let generation = channel.generation;
+        if channel.entries.remove(key).is_some() {
+            state.size -= 1;
+        }
+        Err(generation)
+    }
+
+    fn keep(&self, channel_id: &str, key: Read, generation: u64, value: Value) {
+        let ttl = self.config.ttl_of(channel_id);
+        if ttl.is_zero() || self.config.max_entries == 0 {
+            return;
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let seq = state.next_seq;
+        let channel = state.channels.entry(channel_id.to_owned()).or_default();
+        if channel.generation != generation {
+            return;
+        }
+
+        let entry = Entry {
+            value,
+            expires_at: Instant::now() + ttl,
+            seq,
+        };
+        let replaced = channel.entries.insert(key.clone(), entry).is_some();
+        state.next_seq += 1;
+        state.order.push_back((seq, channel_id.to_owned(), key));
+        if !replaced {
+            state.size += 1;
+        }
+
+        self.evict(&mut state);
+    }
+
+    fn evict(&self, state: &mut State) {
+        while state.size > self.config.max_entries {
+            let (seq, channel_id, key) = match state.order.pop_front() {
+                Some(item) => item,
+                None => break,
+            };
+            let channel = match state.channels.get_mut(&channel_id) {
+                Some(channel) => channel,
+                None => continue,
+            };
+            if channel.entries.get(&key).map(|e| e.seq) == Some(seq) {
+                channel.entries.remove(&key);
+                channel.evictions += 1;
+                state.size -= 1;
+            }
+        }
+
+        // forget the positions of the entries dropped meanwhile.
+        if state.order.len() > 2 * self.config.max_entries.max(16) {
+            let channels = &state.channels;
+            state.order.retain(|(seq, channel_id, key)| {
+                channels
+                    .get(channel_id)
+                    .and_then(|c| c.entries.get(key))
+                    .map(|e| e.seq)
+                    == Some(*seq)
+            });
+        }
+    }
+
+    fn invalidate(&self, channel_id: &str) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let channel = state.channels.entry(channel_id.to_owned()).or_default();
+
+        let dropped = channel.entries.len();
+        channel.entries.clear();
+        channel.generation += 1;
+        channel.invalidations += 1;
+        state.size -= dropped;
+        dropped
+    }
+}
+
+#[async_trait]
+impl<S: service::Store + Send + Sync> service::Store for Repo<S> {
+    async fn save(&self, data: &mut service::Experiment) -> Result<String> {
+        let result = self.inner.save(data).await;
+        self.shared.invalidate(&data.channel_id);
+        result
+    }
+
+    async fn list(&self, channel_id: &str, query: &service::ListQuery) -> Result<service::Page> {
+        let key = Read::List(format!("{:?}", query));
+        self.read(channel_id, key, self.inner.list(channel_id, query))
+            .await
+    }
+
+    async fn get(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        let key = Read::Id(id.to_owned());
+        self.read(channel_id, key, self.inner.get(id, channel_id))
+            .await
+    }
+
+    async fn get_fresh(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.inner.get_fresh(id, channel_id).await
+    }
+
+    async fn get_by_key(&self, key: &str, channel_id: &str) -> Result<service::Experiment> {
+        let cache_key = Read::Key(key.to_owned());
+        self.read(
+            channel_id,
+            cache_key,
+            self.inner.get_by_key(key, channel_id),
+        )
+        .await
+    }
+
+    async fn get_projected(
+        &self,
+        id: &str,
+        channel_id: &str,
+        projection: &Projection,
+    ) -> Result<service::Experiment> {
+        let key = Read::Projected(id.to_owned(), format!("{:?}", projection));
+        self.read(
+            channel_id,
+            key,
+            self.inner.get_projected(id, channel_id, projection),
+        )
+        .await
+    }
+
+    async fn update(&self, data: &mut service::Experiment) -> Result<()> {
+        let result = self.inner.update(data).await;
+        self.shared.invalidate(&data.channel_id);
+        result
+    }
+
+    async fn update_if(
+        &self,
+        data: &mut service::Experiment,
+        version: service::Version,
+    ) -> Result<()> {
+        let result = self.inner.update_if(data, version).await;
+        self.shared.invalidate(&data.channel_id);
+        result
+    }
+
+    async fn patch(
+        &self,
+        id: &str,
+        channel_id: &str,
+        patch: &service::Patch,
+    ) -> Result<Option<service::Experiment>> {
+        let result = self.inner.patch(id, channel_id, patch).await;
+        self.shared.invalidate(channel_id);
+        result
+    }
+
+    async fn write_atomically(&self, writes: &mut [service::Write]) -> Result<bool> {
+        let result = self.inner.write_atomically(writes).await;
+        for write in writes.iter() {
+            let channel_id = match write {
+                service::Write::Insert(data) | service::Write::Replace(data) => &data.channel_id,
+                service::Write::Delete { channel_id, .. } => channel_id,
+            };
+            self.shared.invalidate(channel_id);
+        }
+        result
+    }
+
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
+        let result = self.inner.delete(id, channel_id).await;
+        self.shared.invalidate(channel_id);
+        result
+    }
+
+    async fn delete_if(&self, id: &str, channel_id: &str, version: service::Version) -> Result<()> {
+        let result = self.inner.delete_if(id, channel_id, version).await;
+        self.shared.invalidate(channel_id);
+        result
+    }
+
+    async fn list_by_status(
+        &self,
+        statuses: &[service::Status],
+        after: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<service::Experiment>> {
+        self.inner.list_by_status(statuses, after, limit).await
+    }
+
+    async fn search(&self, channel_id: &str, q: &str, limit: i64) -> Result<Vec<search::Hit>> {
+        self.inner.search(channel_id, q, limit).await
+    }
+
+    async fn tag_counts(&self, channel_id: &str) -> Result<Vec<tag::TagCount>> {
+        self.read(channel_id, Read::Tags, self.inner.tag_counts(channel_id))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory;
+    use crate::service::experiment::Store;
+
+    fn experiment(name: &str, channel_id: &str) -> service::Experiment {
+        service::Experiment {
+            name: name.to_owned(),
+            key: name.to_owned(),
+            channel_id: channel_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    crate::repository::conformance::store_conformance!(async {
+        Repo::new(memory::Repo::new(), Config::default())
+    });
+
+    #[actix_web::test]
+    async fn test_hits_and_invalidation() {
+        let repo = Repo::new(memory::Repo::new(), Config::default());
+        let handle = repo.handle();
+
+        let mut data = experiment("checkout", "channel_a");
+        let id = repo.save(&mut data).await.unwrap();
+        repo.get(&id, "channel_a").await.unwrap();
+        repo.get(&id, "channel_a").await.unwrap();
+        repo.list("channel_a", &service::ListQuery::default())
+            .await
+            .unwrap();
+
+        let stats = handle.stats("channel_a");
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+        data.name = "checkout v2".to_owned();
+        repo.update(&mut data).await.unwrap();
+        assert_eq!(handle.stats("channel_a").entries, 0);
+        assert_eq!(
+            repo.get(&id, "channel_a").await.unwrap().name,
+            "checkout v2"
+        );
+
+        assert_eq!(handle.flush("channel_a"), 1);
+        assert_eq!(
+            handle.stats("channel_b"),
+            Stats {
+                enabled: true,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_get_fresh_skips_the_cache() {
+        let repo = Repo::new(memory::Repo::new(), Config::default());
+        let mut data = experiment("checkout", "channel_a");
+        let id = repo.save(&mut data).await.unwrap();
+        repo.get(&id, "channel_a").await.unwrap();
+
+        // written by another instance, this one still has the former read.
+        data.name = "checkout v2".to_owned();
+        repo.inner.update(&mut data).await.unwrap();
+        assert_eq!(repo.get(&id, "channel_a").await.unwrap().name, "checkout");
+        assert_eq!(
+            repo.get_fresh(&id, "channel_a").await.unwrap().name,
+            "checkout v2"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_channel_ttl() {
+        let config = Config {
+            channel_ttls: [("channel_b".to_owned(), Duration::ZERO)].into(),
+            ..Default::default()
+        };
+        let repo = Repo::new(memory::Repo::new(), config);
+        let handle = repo.handle();
+
+        for channel_id in ["channel_a", "channel_b"] {
+            let mut data = experiment("checkout", channel_id);
+            let id = repo.save(&mut data).await.unwrap();
+            repo.get(&id, channel_id).await.unwrap();
+            repo.get(&id, channel_id).await.unwrap();
+        }
+
+        assert_eq!(handle.stats("channel_a").hits, 1);
+        assert_eq!(handle.stats("channel_b").hits, 0);
+    }
+
+    #[actix_web::test]
+    async fn test_size_bound() {
+        let config = Config {
+            max_entries: 2,
+            ..Default::default()
+        };
+        let repo = Repo::new(memory::Repo::new(), config);
+        let handle = repo.handle();
+
+        let mut ids = vec![];
+        for name in ["first", "second", "third"] {
+            ids.push(repo.save(&mut experiment(name, "channel_a")).await.unwrap());
+        }
+        for id in ids.iter() {
+            repo.get(id, "channel_a").await.unwrap();
+        }
+
+        let stats = handle.stats("channel_a");
+        assert_eq!((stats.entries, stats.evictions), (2, 1));
+
+        // the first one went first.
+        repo.get(&ids[2], "channel_a").await.unwrap();
+        repo.get(&ids[0], "channel_a").await.unwrap();
+        assert_eq!(handle.stats("channel_a").hits, 1);
+    }
+
+    #[actix_web::test]
+    async fn test_read_racing_a_write_is_not_kept() {
+        let repo = Repo::new(memory::Repo::new(), Config::default());
+        let handle = repo.handle();
+        let mut data = experiment("checkout", "channel_a");
+        let id = repo.save(&mut data).await.unwrap();
+
+        let key = Read::Id(id.clone());
+        let generation = repo
+            .shared
+            .lookup::<service::Experiment>("channel_a", &key)
+            .unwrap_err();
+        let stale = repo.inner.get(&id, "channel_a").await.unwrap();
+        repo.delete(&id, "channel_a").await.unwrap();
+        repo.shared.keep("channel_a", key, generation, stale.wrap());
+
+        assert_eq!(handle.stats("channel_a").entries, 0);
+        assert!(repo.get(&id, "channel_a").await.is_err());
+    }
+}
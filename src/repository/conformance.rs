@@ -0,0 +1,834 @@
+//! Behavior every `service::experiment::Store` must have, whatever keeps the experiments.
+//! `store_conformance!` runs the checks against a store. Each check works in channels of
+//! its own, so the stores backed by a shared database may run them concurrently.
+
+use chrono::Utc;
+use mongodb::bson::oid;
+
+use crate::service::bandit::{self, Algorithm, Bandit, Reward, WeightChange};
+use crate::service::experiment::{
+    Classing, Cursor, Experiment, KillScope, KillSwitch, ListQuery, Patch, SortField, SortOrder,
+    Status, Store, StoreError, Variance, Version, Write,
+};
+use crate::service::tag;
+
+/// Generate a test per check, `$store` is an expression, awaited in the tests, giving
+/// `impl Store`. With `ignore = "reason"` the tests only run with `cargo test -- --ignored`,
+/// e.g. when they need a database.
+macro_rules! store_conformance {
+    ($store:expr) => {
+        crate::repository::conformance::store_conformance!(@module $store;);
+    };
+    ($store:expr, ignore = $reason:literal) => {
+        crate::repository::conformance::store_conformance!(@module $store; ignore = $reason);
+    };
+    (@module $store:expr; $($ignore:tt)*) => {
+        mod conformance {
+            use super::*;
+            use crate::repository::conformance;
+
+            crate::repository::conformance::store_conformance!(@checks $store; $($ignore)*;
+                save_and_get,
+                save_keeps_given_id,
+                update,
+                delete,
+                conditional_writes,
+                patch_status,
+                patch_bandit,
+                patch_tags,
+                patch_kill,
+                not_found,
+                invalid_ids,
+                channel_isolation,
+                key_conflicts,
+                list_filters,
+                list_pages,
+                list_by_status,
+                write_atomically,
+            );
+        }
+    };
+    (@checks $store:expr; ; $($check:ident),* $(,)?) => {
+        $(
+            #[actix_web::test]
+            async fn $check() {
+                conformance::$check(&$store.await).await;
+            }
+        )*
+    };
+    (@checks $store:expr; ignore = $reason:literal; $($check:ident),* $(,)?) => {
+        $(
+            #[actix_web::test]
+            #[ignore = $reason]
+            async fn $check() {
+                conformance::$check(&$store.await).await;
+            }
+        )*
+    };
+}
+
+pub(crate) use store_conformance;
+
+fn channel() -> String {
+    oid::ObjectId::new().to_hex()
+}
+
+fn experiment(name: &str, channel_id: &str) -> Experiment {
+    Experiment {
+        key: name.to_owned(),
+        name: name.to_owned(),
+        description: format!("{} description", name),
+        channel_id: channel_id.to_owned(),
+        tags: vec!["team:growth".to_owned()],
+        variations: vec![Variance {
+            group_name: "control".to_owned(),
+            description: String::new(),
+            indicator: "control".to_owned(),
+            weight: 100,
+            values: [("color".to_owned(), serde_json::json!("blue"))].into(),
+            levels: Default::default(),
+        }],
+        classing: Classing {
+            strategy: "random".to_owned(),
+            persistent_mode: "key".to_owned(),
+            bandit: None,
+        },
+        ..Default::default()
+    }
+}
+
+fn store_error<T: std::fmt::Debug>(result: anyhow::Result<T>) -> StoreError {
+    result
+        .expect_err("the store should fail")
+        .downcast::<StoreError>()
+        .expect("the store should fail with a StoreError")
+}
+
+fn json(experiment: &Experiment) -> serde_json::Value {
+    serde_json::to_value(experiment).unwrap()
+}
+
+pub async fn save_and_get(store: &impl Store) {
+    let channel = channel();
+    let before = Utc::now() - chrono::Duration::seconds(1);
+
+    let mut data = experiment("checkout", &channel);
+    let id = store.save(&mut data).await.unwrap();
+
+    assert_eq!(data.id.as_deref(), Some(id.as_str()));
+    assert!(oid::ObjectId::parse_str(&id).is_ok(), "ids are object ids");
+    let created_at = data.created_at.expect("save stamps created_at");
+    assert!(created_at >= before && created_at <= Utc::now());
+    assert_eq!(data.updated_at, data.created_at);
+
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(
+        json(&stored),
+        json(&data),
+        "get returns what save handed back"
+    );
+
+    let by_key = store.get_by_key("checkout", &channel).await.unwrap();
+    assert_eq!(by_key.id, data.id);
+}
+
+pub async fn save_keeps_given_id(store: &impl Store) {
+    let channel = channel();
+    let id = oid::ObjectId::new().to_hex();
+    let created_at = Utc::now() - chrono::Duration::days(3);
+
+    let mut data = experiment("imported", &channel);
+    data.id = Some(id.clone());
+    data.created_at = Some(created_at);
+    assert_eq!(store.save(&mut data).await.unwrap(), id);
+
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(
+        stored.created_at.map(|t| t.timestamp_millis()),
+        Some(created_at.timestamp_millis())
+    );
+    assert!(stored.updated_at > stored.created_at);
+
+    let mut again = experiment("imported-again", &channel);
+    again.id = Some(id);
+    assert!(matches!(
+        store_error(store.save(&mut again).await),
+        StoreError::Conflict { .. }
+    ));
+}
+
+pub async fn update(store: &impl Store) {
+    let channel = channel();
+    let mut data = experiment("checkout", &channel);
+    let id = store.save(&mut data).await.unwrap();
+    let created_at = data.created_at;
+    let updated_at = data.updated_at;
+
+    data.name = "checkout v2".to_owned();
+    data.status = Status::Running;
+    store.update(&mut data).await.unwrap();
+    assert_eq!(data.created_at, created_at, "update keeps created_at");
+    assert!(data.updated_at >= updated_at, "update stamps updated_at");
+
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(json(&stored), json(&data));
+    assert_eq!(stored.status, Status::Running);
+}
+
+pub async fn delete(store: &impl Store) {
+    let channel = channel();
+    let mut data = experiment("checkout", &channel);
+    let id = store.save(&mut data).await.unwrap();
+
+    store.delete(&id, &channel).await.unwrap();
+    assert!(matches!(
+        store_error(store.get(&id, &channel).await),
+        StoreError::DocumentNotfound
+    ));
+    assert!(matches!(
+        store_error(store.delete(&id, &channel).await),
+        StoreError::DocumentNotfound
+    ));
+    assert!(matches!(
+        store_error(store.get_by_key("checkout", &channel).await),
+        StoreError::DocumentNotfound
+    ));
+    let page = store.list(&channel, &ListQuery::default()).await.unwrap();
+    assert_eq!(page.total, 0);
+
+    // the id and the key of a deleted experiment can be given again, e.g. by an import.
+    let mut again = experiment("checkout", &channel);
+    again.id = Some(id.clone());
+    assert_eq!(store.save(&mut again).await.unwrap(), id);
+    assert_eq!(
+        store.get_by_key("checkout", &channel).await.unwrap().id,
+        Some(id)
+    );
+}
+
+pub async fn conditional_writes(store: &impl Store) {
+    let channel = channel();
+    let mut data = experiment("checkout", &channel);
+    let id = store.save(&mut data).await.unwrap();
+    let read = store.get(&id, &channel).await.unwrap();
+
+    // two editors holding the same version, the second one is refused.
+    let mut first = read.clone();
+    first.name = "checkout v2".to_owned();
+    store
+        .update_if(&mut first, Version::of(&read))
+        .await
+        .unwrap();
+    assert_ne!(
+        Version::of(&first),
+        Version::of(&read),
+        "the update is a new version"
+    );
+
+    let mut second = read.clone();
+    second.name = "checkout v3".to_owned();
+    assert!(matches!(
+        store_error(store.update_if(&mut second, Version::of(&read)).await),
+        StoreError::Stale
+    ));
+    assert!(matches!(
+        store_error(store.delete_if(&id, &channel, Version::of(&read)).await),
+        StoreError::Stale
+    ));
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(stored.name, "checkout v2");
+
+    store
+        .delete_if(&id, &channel, Version::of(&stored))
+        .await
+        .unwrap();
+    assert!(matches!(
+        store_error(store.delete_if(&id, &channel, Version::of(&stored)).await),
+        StoreError::DocumentNotfound
+    ));
+    assert!(matches!(
+        store_error(store.update_if(&mut first, Version::of(&stored)).await),
+        StoreError::DocumentNotfound
+    ));
+}
+
+pub async fn patch_status(store: &impl Store) {
+    let channel = channel();
+    let mut data = experiment("checkout", &channel);
+    let id = store.save(&mut data).await.unwrap();
+    let listed = store.get(&id, &channel).await.unwrap();
+
+    // an edit made after the experiment was listed is kept by the patch.
+    let mut edited = listed.clone();
+    edited.name = "checkout v2".to_owned();
+    store.update(&mut edited).await.unwrap();
+
+    let start = Patch::Status {
+        from: Status::Scheduled,
+        to: Status::Running,
+    };
+    let patched = store.patch(&id, &channel, &start).await.unwrap().unwrap();
+    assert_eq!(patched.status, Status::Running);
+    assert_eq!(patched.name, "checkout v2");
+    assert_ne!(Version::of(&patched), Version::of(&edited));
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(json(&stored), json(&patched));
+
+    // the experiment is not in `from` anymore.
+    assert!(store.patch(&id, &channel, &start).await.unwrap().is_none());
+    assert_eq!(
+        store.get(&id, &channel).await.unwrap().status,
+        Status::Running
+    );
+
+    assert!(matches!(
+        store_error(
+            store
+                .patch(&oid::ObjectId::new().to_hex(), &channel, &start)
+                .await
+        ),
+        StoreError::DocumentNotfound
+    ));
+    assert!(matches!(
+        store_error(store.patch(&id, &self::channel(), &start).await),
+        StoreError::DocumentNotfound
+    ));
+}
+
+pub async fn patch_bandit(store: &impl Store) {
+    let channel = channel();
+    let mut data = experiment("checkout", &channel);
+    data.status = Status::Running;
+    let mut treatment = data.variations[0].clone();
+    treatment.indicator = "treatment".to_owned();
+    data.variations[0].weight = 50;
+    treatment.weight = 50;
+    data.variations.push(treatment);
+    data.classing.strategy = bandit::STRATEGY.to_owned();
+    data.classing.bandit = Some(Bandit {
+        algorithm: Algorithm::EpsilonGreedy,
+        epsilon: 0.2,
+        min_weight: 10,
+        max_weight: 90,
+        recompute_interval_secs: 3600,
+        frozen: false,
+        rewards: Default::default(),
+        history: vec![],
+        recomputed_at: None,
+    });
+    let id = store.save(&mut data).await.unwrap();
+
+    // the rewards add up to the counters rather than replacing them.
+    let rewards = Patch::Rewards(
+        [(
+            "treatment".to_owned(),
+            Reward {
+                trials: 10,
+                successes: 3,
+            },
+        )]
+        .into(),
+    );
+    store.patch(&id, &channel, &rewards).await.unwrap().unwrap();
+    let rewarded = store.patch(&id, &channel, &rewards).await.unwrap().unwrap();
+    let bandit = rewarded.classing.bandit.as_ref().unwrap();
+    assert_eq!(
+        bandit.rewards["treatment"],
+        Reward {
+            trials: 20,
+            successes: 6,
+        }
+    );
+
+    let change = WeightChange {
+        changed_at: Utc::now(),
+        weights: [("control".to_owned(), 20), ("treatment".to_owned(), 80)].into(),
+    };
+    let weighed = store
+        .patch(&id, &channel, &Patch::Weights(change))
+        .await
+        .unwrap()
+        .unwrap();
+    let weights: Vec<i32> = weighed.variations.iter().map(|v| v.weight).collect();
+    assert_eq!(weights, vec![20, 80]);
+    let bandit = weighed.classing.bandit.as_ref().unwrap();
+    assert_eq!(bandit.rewards["treatment"].trials, 20);
+    assert_eq!(bandit.history.len(), 1);
+    assert!(bandit.recomputed_at.is_some());
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(json(&stored), json(&weighed));
+
+    // the weights of a paused bandit stay as they are.
+    let paused = Patch::Status {
+        from: Status::Running,
+        to: Status::Paused,
+    };
+    store.patch(&id, &channel, &paused).await.unwrap().unwrap();
+    let change = WeightChange {
+        changed_at: Utc::now(),
+        weights: [("control".to_owned(), 50), ("treatment".to_owned(), 50)].into(),
+    };
+    assert!(store
+        .patch(&id, &channel, &Patch::Weights(change))
+        .await
+        .unwrap()
+        .is_none());
+
+    // freezing keeps the rewards counted since it was read.
+    let frozen = store
+        .patch(&id, &channel, &Patch::Freeze(true))
+        .await
+        .unwrap()
+        .unwrap();
+    let bandit = frozen.classing.bandit.as_ref().unwrap();
+    assert!(bandit.frozen);
+    assert_eq!(bandit.rewards["treatment"].trials, 20);
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(json(&stored), json(&frozen));
+
+    // an experiment without a bandit has nothing to reward nor to freeze.
+    let mut plain = experiment("plain", &channel);
+    let plain_id = store.save(&mut plain).await.unwrap();
+    assert!(store
+        .patch(&plain_id, &channel, &rewards)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(store
+        .patch(&plain_id, &channel, &Patch::Freeze(true))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+pub async fn patch_tags(store: &impl Store) {
+    let channel = channel();
+    let mut data = experiment("checkout", &channel);
+    data.tags = vec!["old".to_owned(), "keep".to_owned()];
+    let id = store.save(&mut data).await.unwrap();
+
+    let tags = |add: &[&str], remove: &[&str]| Patch::Tags {
+        add: add.iter().map(|t| t.to_string()).collect(),
+        remove: remove.iter().map(|t| t.to_string()).collect(),
+    };
+
+    let patched = store
+        .patch(&id, &channel, &tags(&["new", "keep"], &["old"]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(patched.tags, vec!["keep".to_owned(), "new".to_owned()]);
+
+    let patched = store
+        .patch(&id, &channel, &tags(&["more"], &[]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(patched.tags, vec!["keep", "new", "more"]);
+
+    let patched = store
+        .patch(&id, &channel, &tags(&[], &["keep"]))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(patched.tags, vec!["new", "more"]);
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(json(&stored), json(&patched));
+
+    // the patch leaving too many tags is not applied.
+    let many: Vec<String> = (0..tag::MAX_TAGS).map(|i| format!("tag-{}", i)).collect();
+    let too_many = Patch::Tags {
+        add: many,
+        remove: vec![],
+    };
+    assert!(store
+        .patch(&id, &channel, &too_many)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        store.get(&id, &channel).await.unwrap().tags,
+        vec!["new", "more"]
+    );
+}
+
+pub async fn patch_kill(store: &impl Store) {
+    let channel = channel();
+    let mut data = experiment("checkout", &channel);
+    let id = store.save(&mut data).await.unwrap();
+    let mut ended = experiment("ended", &channel);
+    ended.status = Status::Ended;
+    let ended_id = store.save(&mut ended).await.unwrap();
+
+    let kill = |scope| {
+        Patch::Kill(KillSwitch {
+            scope,
+            reason: "incident".to_owned(),
+            triggered_by: None,
+            triggered_at: Utc::now(),
+        })
+    };
+
+    let killed = store
+        .patch(&id, &channel, &kill(KillScope::Channel))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        killed.kill_switch.map(|ks| ks.scope),
+        Some(KillScope::Channel)
+    );
+    assert!(store
+        .patch(&ended_id, &channel, &kill(KillScope::Channel))
+        .await
+        .unwrap()
+        .is_none());
+
+    // killed already, only the experiment kill switch replaces it.
+    assert!(store
+        .patch(&id, &channel, &kill(KillScope::Channel))
+        .await
+        .unwrap()
+        .is_none());
+    store
+        .patch(&id, &channel, &kill(KillScope::Experiment))
+        .await
+        .unwrap()
+        .unwrap();
+
+    let release_channel = Patch::Release(Some(KillScope::Channel));
+    assert!(store
+        .patch(&id, &channel, &release_channel)
+        .await
+        .unwrap()
+        .is_none());
+    let released = store
+        .patch(&id, &channel, &Patch::Release(None))
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(released.kill_switch.is_none());
+    let stored = store.get(&id, &channel).await.unwrap();
+    assert_eq!(json(&stored), json(&released));
+    assert!(store
+        .patch(&id, &channel, &Patch::Release(None))
+        .await
+        .unwrap()
+        .is_none());
+}
+
+pub async fn not_found(store: &impl Store) {
+    let channel = channel();
+    let id = oid::ObjectId::new().to_hex();
+
+    assert!(matches!(
+        store_error(store.get(&id, &channel).await),
+        StoreError::DocumentNotfound
+    ));
+    assert!(matches!(
+        store_error(store.get_by_key("missing", &channel).await),
+        StoreError::DocumentNotfound
+    ));
+    assert!(matches!(
+        store_error(store.delete(&id, &channel).await),
+        StoreError::DocumentNotfound
+    ));
+
+    let mut data = experiment("missing", &channel);
+    data.id = Some(id);
+    assert!(matches!(
+        store_error(store.update(&mut data).await),
+        StoreError::DocumentNotfound
+    ));
+}
+
+pub async fn invalid_ids(store: &impl Store) {
+    let channel = channel();
+
+    assert!(matches!(
+        store_error(store.get("not-an-id", &channel).await),
+        StoreError::InvalidInput { .. }
+    ));
+    assert!(matches!(
+        store_error(store.delete("not-an-id", &channel).await),
+        StoreError::InvalidInput { .. }
+    ));
+
+    let mut data = experiment("checkout", &channel);
+    data.id = Some("not-an-id".to_owned());
+    assert!(matches!(
+        store_error(store.save(&mut data).await),
+        StoreError::InvalidInput { .. }
+    ));
+    assert!(matches!(
+        store_error(store.update(&mut data).await),
+        StoreError::InvalidInput { .. }
+    ));
+}
+
+pub async fn channel_isolation(store: &impl Store) {
+    let (channel, other) = (channel(), channel());
+    let mut data = experiment("checkout", &channel);
+    let id = store.save(&mut data).await.unwrap();
+
+    assert!(matches!(
+        store_error(store.get(&id, &other).await),
+        StoreError::UnauthorizedAccess
+    ));
+    assert!(matches!(
+        store_error(store.get_by_key("checkout", &other).await),
+        StoreError::DocumentNotfound
+    ));
+    assert!(matches!(
+        store_error(store.delete(&id, &other).await),
+        StoreError::DocumentNotfound
+    ));
+
+    let mut moved = data.clone();
+    moved.channel_id = other.clone();
+    assert!(matches!(
+        store_error(store.update(&mut moved).await),
+        StoreError::DocumentNotfound
+    ));
+
+    let page = store.list(&other, &ListQuery::default()).await.unwrap();
+    assert_eq!(page.total, 0);
+    assert!(store
+        .search(&other, "checkout", 10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(store.tag_counts(&other).await.unwrap().is_empty());
+
+    // the same key is free in another channel.
+    let mut elsewhere = experiment("checkout", &other);
+    store.save(&mut elsewhere).await.unwrap();
+    assert_eq!(store.get(&id, &channel).await.unwrap().name, "checkout");
+}
+
+pub async fn key_conflicts(store: &impl Store) {
+    let channel = channel();
+    store
+        .save(&mut experiment("checkout", &channel))
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        store_error(store.save(&mut experiment("checkout", &channel)).await),
+        StoreError::Conflict { .. }
+    ));
+
+    let mut pricing = experiment("pricing", &channel);
+    store.save(&mut pricing).await.unwrap();
+    pricing.key = "checkout".to_owned();
+    assert!(matches!(
+        store_error(store.update(&mut pricing).await),
+        StoreError::Conflict { .. }
+    ));
+
+    // experiments without a key do not conflict.
+    for name in ["legacy-1", "legacy-2"] {
+        let mut legacy = experiment(name, &channel);
+        legacy.key = String::new();
+        store.save(&mut legacy).await.unwrap();
+    }
+}
+
+pub async fn list_filters(store: &impl Store) {
+    let channel = channel();
+    let mut running = experiment("checkout button", &channel);
+    running.status = Status::Running;
+    running.tags = vec!["team:growth".to_owned(), "q3".to_owned()];
+    running.owner = Some(serde_json::json!({"id": "user_01"}));
+    store.save(&mut running).await.unwrap();
+    store
+        .save(&mut experiment("checkout page", &channel))
+        .await
+        .unwrap();
+    store
+        .save(&mut experiment("pricing", &channel))
+        .await
+        .unwrap();
+
+    let names = |page: crate::service::experiment::Page| {
+        let mut names: Vec<String> = page.items.into_iter().map(|e| e.name).collect();
+        names.sort();
+        names
+    };
+
+    let query = ListQuery {
+        status: Some(Status::Running),
+        ..Default::default()
+    };
+    let page = store.list(&channel, &query).await.unwrap();
+    assert_eq!(page.total, 1);
+    assert_eq!(names(page), vec!["checkout button"]);
+
+    let query = ListQuery {
+        name_prefix: Some("checkout".to_owned()),
+        ..Default::default()
+    };
+    let page = store.list(&channel, &query).await.unwrap();
+    assert_eq!(names(page), vec!["checkout button", "checkout page"]);
+
+    let query = ListQuery {
+        tag: Some("q3".to_owned()),
+        owner_id: Some("user_01".to_owned()),
+        ..Default::default()
+    };
+    assert_eq!(store.list(&channel, &query).await.unwrap().total, 1);
+
+    let counts = store.tag_counts(&channel).await.unwrap();
+    assert_eq!(
+        (counts[0].tag.as_str(), counts[0].count),
+        ("team:growth", 3)
+    );
+    assert_eq!((counts[1].tag.as_str(), counts[1].count), ("q3", 1));
+
+    let hits = store.search(&channel, "checkout", 10).await.unwrap();
+    assert_eq!(hits.len(), 2);
+}
+
+pub async fn list_pages(store: &impl Store) {
+    let channel = channel();
+    for name in ["c", "a", "e", "b", "d"] {
+        store.save(&mut experiment(name, &channel)).await.unwrap();
+    }
+
+    for sort in [SortField::Name, SortField::CreatedAt, SortField::UpdatedAt] {
+        for order in [SortOrder::Asc, SortOrder::Desc] {
+            let mut query = ListQuery {
+                limit: 2,
+                sort,
+                order,
+                ..Default::default()
+            };
+
+            let mut seen = vec![];
+            loop {
+                let page = store.list(&channel, &query).await.unwrap();
+                assert_eq!(page.total, 5);
+                assert!(page.items.len() <= 2);
+                seen.extend(page.items);
+                match page.next_cursor {
+                    Some(cursor) => {
+                        let cursor = Cursor::decode(&cursor).unwrap();
+                        assert_eq!(cursor.query, query.fingerprint());
+                        query.cursor = Some(cursor);
+                    }
+                    None => break,
+                }
+            }
+
+            let mut ids: Vec<_> = seen.iter().map(|e| e.id.clone()).collect();
+            ids.sort();
+            ids.dedup();
+            assert_eq!(
+                ids.len(),
+                5,
+                "{:?} {:?} visits every experiment once",
+                sort,
+                order
+            );
+
+            if sort == SortField::Name {
+                let names: String = seen.iter().map(|e| e.name.as_str()).collect();
+                let expected = match order {
+                    SortOrder::Asc => "abcde",
+                    SortOrder::Desc => "edcba",
+                };
+                assert_eq!(names, expected);
+            }
+        }
+    }
+}
+
+pub async fn list_by_status(store: &impl Store) {
+    let channel = channel();
+    let mut ids = vec![];
+    for name in ["a", "b", "c"] {
+        let mut data = experiment(name, &channel);
+        data.status = Status::Paused;
+        ids.push(store.save(&mut data).await.unwrap());
+    }
+    let mut ended = experiment("ended", &channel);
+    ended.status = Status::Ended;
+    let ended_id = store.save(&mut ended).await.unwrap();
+
+    // the other tests may list experiments too, only the ones of the channel are checked.
+    let mut listed = vec![];
+    let mut after = None;
+    loop {
+        let page = store
+            .list_by_status(&[Status::Paused], after, 2)
+            .await
+            .unwrap();
+        assert!(page.len() <= 2);
+        after = match page.last() {
+            Some(last) => last.id.clone(),
+            None => break,
+        };
+        listed.extend(page.into_iter().map(|e| e.id.unwrap_or_default()));
+    }
+
+    let mut sorted = listed.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert_eq!(listed, sorted, "the pages follow the ids");
+    assert!(ids.iter().all(|id| listed.contains(id)));
+    assert!(!listed.contains(&ended_id));
+}
+
+pub async fn write_atomically(store: &impl Store) {
+    let channel = channel();
+    let mut existing = experiment("existing", &channel);
+    let existing_id = store.save(&mut existing).await.unwrap();
+
+    // the delete of a missing experiment fails the whole batch.
+    let mut writes = vec![
+        Write::Insert(experiment("new", &channel)),
+        Write::Delete {
+            id: oid::ObjectId::new().to_hex(),
+            channel_id: channel.clone(),
+        },
+    ];
+    let failed = store.write_atomically(&mut writes).await;
+    assert!(!matches!(failed, Ok(true)));
+    assert_eq!(
+        store
+            .list(&channel, &ListQuery::default())
+            .await
+            .unwrap()
+            .total,
+        1
+    );
+
+    let mut renamed = existing.clone();
+    renamed.name = "renamed".to_owned();
+    let mut writes = vec![
+        Write::Insert(experiment("new", &channel)),
+        Write::Replace(renamed),
+    ];
+    if !store.write_atomically(&mut writes).await.unwrap() {
+        // a store without transactions writes nothing.
+        assert_eq!(
+            store
+                .list(&channel, &ListQuery::default())
+                .await
+                .unwrap()
+                .total,
+            1
+        );
+        return;
+    }
+
+    let inserted_id = match &writes[0] {
+        Write::Insert(e) => e.id.clone().expect("inserted experiments get their id"),
+        _ => unreachable!(),
+    };
+    assert_eq!(store.get(&inserted_id, &channel).await.unwrap().name, "new");
+    assert_eq!(
+        store.get(&existing_id, &channel).await.unwrap().name,
+        "renamed"
+    );
+}
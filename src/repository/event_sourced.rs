@@ -0,0 +1,391 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use futures_util::lock::Mutex;
+
+use super::memory;
+use crate::service::experiment as service;
+use crate::service::history::{self, Change, Kind, Log};
+use crate::service::projection::Projection;
+use crate::service::search;
+use crate::service::tag;
+
+/// Experiment store keeping every change in an append-only log, the current experiments are
+/// their projection, kept in memory and rebuilt from the log when the store is opened.
+/// The projection only follows the changes made through it, so a single instance may run.
+pub struct Repo<L> {
+    log: Arc<L>,
+    projection: memory::Repo,
+    /// Latest version of each experiment, locked by the writes so versions are taken in turn.
+    versions: Mutex<HashMap<String, i64>>,
+}
+
+/// Numbers of a rebuild of the projection.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Rebuilt {
+    pub changes: usize,
+    pub experiments: usize,
+}
+
+impl<L: Log + Send + Sync> Repo<L> {
+    /// Open the store over the log, rebuilding the experiments from it.
+    pub async fn open(log: L) -> Result<(Self, Rebuilt)> {
+        let repo = Self {
+            log: Arc::new(log),
+            projection: memory::Repo::new(),
+            versions: Mutex::new(HashMap::new()),
+        };
+        let rebuilt = repo.rebuild().await?;
+        Ok((repo, rebuilt))
+    }
+
+    /// Replay the whole log into the projection, fails on a log which does not fold, e.g.
+    /// with a missing version.
+    pub async fn rebuild(&self) -> Result<Rebuilt> {
+        let mut versions = self.versions.lock().await;
+
+        let mut histories: HashMap<String, Vec<Change>> = HashMap::new();
+        let changes = self.log.all().await?;
+        let count = changes.len();
+        for change in changes {
+            histories
+                .entry(change.experiment_id.clone())
+                .or_default()
+                .push(change);
+        }
+
+        let mut experiments = vec![];
+        let mut latest = HashMap::new();
+        for (id, mut changes) in histories {
+            changes.sort_by_key(|c| c.version);
+            experiments.extend(history::fold(changes.iter())?);
+            latest.insert(id, changes.len() as i64);
+        }
+
+        let rebuilt = Rebuilt {
+            changes: count,
+            experiments: experiments.len(),
+        };
+        self.projection.restore(experiments);
+        *versions = latest;
+        Ok(rebuilt)
+    }
+
+    /// Reader of the experiments as they were.
+    pub fn reader(&self) -> Reader<L> {
+        Reader(self.log.clone())
+    }
+
+    async fn append(
+        &self,
+        versions: &mut HashMap<String, i64>,
+        experiment_id: &str,
+        channel_id: &str,
+        kind: Kind,
+        occurred_at: DateTime<Utc>,
+    ) -> Result<()> {
+        let version = versions.get(experiment_id).copied().unwrap_or_default() + 1;
+        let change = Change {
+            experiment_id: experiment_id.to_owned(),
+            channel_id: channel_id.to_owned(),
+            version,
+            kind,
+            occurred_at,
+        };
+
+        self.log.append(&change).await?;
+        versions.insert(experiment_id.to_owned(), version);
+        Ok(())
+    }
+
+    /// Replace the experiment, at the `expected` version if any.
+    async fn replace(
+        &self,
+        data: &mut service::Experiment,
+        expected: Option<service::Version>,
+    ) -> Result<()> {
+        let mut versions = self.versions.lock().await;
+
+        let current = self.projection.prepare_update(data, expected)?;
+        to_stored_precision(data);
+        let id = current.id.clone().unwrap_or_default();
+
+        let kind = if is_status_change(&current, data) {
+            Kind::StatusChanged {
+                from: current.status,
+                to: data.status,
+            }
+        } else {
+            Kind::Updated(Box::new(data.clone()))
+        };
+        let occurred_at = data.updated_at.unwrap_or_else(Utc::now);
+        self.append(&mut versions, &id, &data.channel_id, kind, occurred_at)
+            .await?;
+
+        self.projection.put(data.clone());
+        Ok(())
+    }
+
+    /// Delete the experiment, at the `expected` version if any.
+    async fn remove(
+        &self,
+        id: &str,
+        channel_id: &str,
+        expected: Option<service::Version>,
+    ) -> Result<()> {
+        let mut versions = self.versions.lock().await;
+
+        let id = self.projection.prepare_delete(id, channel_id, expected)?;
+        let occurred_at = Utc
+            .timestamp_millis_opt(Utc::now().timestamp_millis())
+            .single()
+            .unwrap_or_else(Utc::now);
+        self.append(&mut versions, &id, channel_id, Kind::Deleted, occurred_at)
+            .await?;
+
+        self.projection.forget(&id);
+        Ok(())
+    }
+}
+
+/// Reader of the experiments as they were, from the log of a `Repo`.
+pub struct Reader<L>(Arc<L>);
+
+#[async_trait]
+impl<L: Log + Send + Sync> history::Reader for Reader<L> {
+    async fn as_of(
+        &self,
+        id: &str,
+        channel_id: &str,
+        at: DateTime<Utc>,
+    ) -> Result<service::Experiment> {
+        history::as_of(self.0.as_ref(), id, channel_id, at).await
+    }
+}
+
+/// Truncate the timestamps to the milliseconds of the logged changes, so the experiment
+/// handed back after a write is the same as the one rebuilt later.
+fn to_stored_precision(data: &mut service::Experiment) {
+    let truncate = |v: &mut Option<DateTime<Utc>>| {
+        *v = v.and_then(|dt| Utc.timestamp_millis_opt(dt.timestamp_millis()).single());
+    };
+    truncate(&mut data.created_at);
+    truncate(&mut data.updated_at);
+    truncate(&mut data.deleted_at);
+    if let Some(interval) = data.active_interval.as_mut() {
+        truncate(&mut interval.0);
+        truncate(&mut interval.1);
+    }
+}
+
+/// Whether the status, and the time of the update, is all that changed.
+fn is_status_change(current: &service::Experiment, data: &service::Experiment) -> bool {
+    let without_status = |e: &service::Experiment| {
+        let mut e = e.clone();
+        e.status = service::Status::default();
+        e.updated_at = None;
+        serde_json::to_value(e).ok()
+    };
+    current.status != data.status && without_status(current) == without_status(data)
+}
+
+#[async_trait]
+impl<L: Log + Send + Sync> service::Store for Repo<L> {
+    async fn save(&self, data: &mut service::Experiment) -> Result<String> {
+        let mut versions = self.versions.lock().await;
+
+        self.projection.prepare_save(data)?;
+        to_stored_precision(data);
+        let id = data.id.clone().unwrap_or_default();
+
+        let kind = Kind::Created(Box::new(data.clone()));
+        let occurred_at = data.updated_at.unwrap_or_else(Utc::now);
+        self.append(&mut versions, &id, &data.channel_id, kind, occurred_at)
+            .await?;
+
+        self.projection.put(data.clone());
+        Ok(id)
+    }
+
+    async fn list(&self, channel_id: &str, query: &service::ListQuery) -> Result<service::Page> {
+        self.projection.list(channel_id, query).await
+    }
+
+    async fn get(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.projection.get(id, channel_id).await
+    }
+
+    async fn get_fresh(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.get(id, channel_id).await
+    }
+
+    async fn get_by_key(&self, key: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.projection.get_by_key(key, channel_id).await
+    }
+
+    async fn get_projected(
+        &self,
+        id: &str,
+        channel_id: &str,
+        projection: &Projection,
+    ) -> Result<service::Experiment> {
+        self.projection
+            .get_projected(id, channel_id, projection)
+            .await
+    }
+
+    async fn update(&self, data: &mut service::Experiment) -> Result<()> {
+        self.replace(data, None).await
+    }
+
+    async fn update_if(
+        &self,
+        data: &mut service::Experiment,
+        version: service::Version,
+    ) -> Result<()> {
+        self.replace(data, Some(version)).await
+    }
+
+    async fn patch(
+        &self,
+        id: &str,
+        channel_id: &str,
+        patch: &service::Patch,
+    ) -> Result<Option<service::Experiment>> {
+        let mut versions = self.versions.lock().await;
+
+        let (current, mut data) = match self.projection.prepare_patch(id, channel_id, patch)? {
+            Some(patched) => patched,
+            None => return Ok(None),
+        };
+        to_stored_precision(&mut data);
+        let id = current.id.clone().unwrap_or_default();
+
+        let kind = match patch {
+            service::Patch::Status { from, to } => Kind::StatusChanged {
+                from: *from,
+                to: *to,
+            },
+            service::Patch::Rewards(_)
+            | service::Patch::Weights(_)
+            | service::Patch::Tags { .. }
+            | service::Patch::Kill(_)
+            | service::Patch::Release(_)
+            | service::Patch::Freeze(_) => Kind::Updated(Box::new(data.clone())),
+        };
+        let occurred_at = data.updated_at.unwrap_or_else(Utc::now);
+        self.append(&mut versions, &id, channel_id, kind, occurred_at)
+            .await?;
+
+        self.projection.put(data.clone());
+        Ok(Some(data))
+    }
+
+    /// The changes are appended one by one, so they cannot be applied in a transaction.
+    async fn write_atomically(&self, _writes: &mut [service::Write]) -> Result<bool> {
+        Ok(false)
+    }
+
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
+        self.remove(id, channel_id, None).await
+    }
+
+    async fn delete_if(&self, id: &str, channel_id: &str, version: service::Version) -> Result<()> {
+        self.remove(id, channel_id, Some(version)).await
+    }
+
+    async fn list_by_status(
+        &self,
+        statuses: &[service::Status],
+        after: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<service::Experiment>> {
+        self.projection.list_by_status(statuses, after, limit).await
+    }
+
+    async fn search(&self, channel_id: &str, q: &str, limit: i64) -> Result<Vec<search::Hit>> {
+        self.projection.search(channel_id, q, limit).await
+    }
+
+    async fn tag_counts(&self, channel_id: &str) -> Result<Vec<tag::TagCount>> {
+        self.projection.tag_counts(channel_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::Store;
+    use crate::service::history::{LocalLog, Reader as _};
+
+    fn experiment(name: &str, channel_id: &str) -> service::Experiment {
+        service::Experiment {
+            name: name.to_owned(),
+            key: name.to_owned(),
+            channel_id: channel_id.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    crate::repository::conformance::store_conformance!(async {
+        Repo::open(LocalLog::default()).await.unwrap().0
+    });
+
+    #[actix_web::test]
+    async fn test_changes_are_logged_and_rebuilt() {
+        let (repo, _) = Repo::open(LocalLog::default()).await.unwrap();
+
+        let mut data = experiment("checkout", "channel_a");
+        let id = repo.save(&mut data).await.unwrap();
+        data.status = service::Status::Running;
+        repo.update(&mut data).await.unwrap();
+        data.name = "checkout v2".to_owned();
+        repo.update(&mut data).await.unwrap();
+        let mut other = experiment("pricing", "channel_a");
+        let other_id = repo.save(&mut other).await.unwrap();
+        // the changes are timed by the millisecond.
+        actix_web::rt::time::sleep(std::time::Duration::from_millis(2)).await;
+        repo.delete(&other_id, "channel_a").await.unwrap();
+
+        let kinds: Vec<_> = repo
+            .log
+            .changes_of(&id)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|c| serde_json::to_value(&c).unwrap()["kind"].clone())
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                serde_json::json!("created"),
+                serde_json::json!("status_changed"),
+                serde_json::json!("updated")
+            ]
+        );
+
+        let before = serde_json::to_value(repo.get(&id, "channel_a").await.unwrap()).unwrap();
+        let rebuilt = repo.rebuild().await.unwrap();
+        assert_eq!(
+            rebuilt,
+            Rebuilt {
+                changes: 5,
+                experiments: 1
+            }
+        );
+        let after = serde_json::to_value(repo.get(&id, "channel_a").await.unwrap()).unwrap();
+        assert_eq!(before, after);
+        assert!(repo.get(&other_id, "channel_a").await.is_err());
+
+        // the deleted experiment is still there in the past.
+        let reader = repo.reader();
+        let past = reader
+            .as_of(&other_id, "channel_a", other.updated_at.unwrap())
+            .await
+            .unwrap();
+        assert_eq!(past.name, "pricing");
+    }
+}
@@ -1,38 +1,106 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
 
 use anyhow::Result;
 use async_trait::async_trait;
 use bson::Bson;
-use chrono::{serde::ts_milliseconds_option, DateTime, Utc};
+use chrono::{DateTime, Utc};
 use futures_util::{TryFutureExt, TryStreamExt};
-use mongodb::{bson::doc, bson::oid, Collection};
-use serde::{Deserialize, Serialize};
+use mongodb::{
+    bson::doc,
+    bson::oid,
+    options::{
+        FindOneAndReplaceOptions, FindOneAndUpdateOptions, FindOneOptions, FindOptions,
+        IndexOptions, ReturnDocument, UpdateModifications,
+    },
+    Client, ClientSession, Collection, IndexModel,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json;
 
+use super::is_namespace_missing;
+use super::outbox;
+use crate::service::bandit;
+use crate::service::event::Event;
 use crate::service::experiment as service;
+use crate::service::factorial;
+use crate::service::projection::Projection;
+use crate::service::search;
+use crate::service::tag;
+
+/// Version of the shape `Document` is written in, the documents of an older version are
+/// brought to it by `repository::migration`. Bump it along with a new migration.
+pub const SCHEMA_VERSION: i32 = 3;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Document {
     pub _id: Option<oid::ObjectId>,
-    pub id: Option<String>,
+    /// Zero for the documents written before the versioning.
+    #[serde(default)]
+    pub schema_version: i32,
+    /// Empty for the experiments created before the keys.
+    #[serde(default)]
+    pub key: String,
     pub name: String,
     pub description: String,
     pub active_interval: Option<Interval>,
+    #[serde(default)]
+    pub tags: Vec<String>,
 
     pub variations: Vec<Variance>,
     pub classing: Classing,
+    #[serde(default)]
+    pub factorial: Option<Factorial>,
 
     pub owner: Option<serde_json::Value>,
     pub channel_id: String,
 
-    #[serde(with = "ts_milliseconds_option")]
+    #[serde(default)]
+    pub status: Status,
+    #[serde(default)]
+    pub kill_switch: Option<KillSwitch>,
+
+    #[serde(with = "timestamp")]
     pub created_at: Option<DateTime<Utc>>,
+    #[serde(with = "timestamp")]
     pub updated_at: Option<DateTime<Utc>>,
+    #[serde(with = "timestamp")]
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    #[default]
+    Scheduled,
+    Running,
+    Paused,
+    Ended,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct KillSwitch {
+    pub scope: KillScope,
+    pub reason: String,
+    pub triggered_by: Option<serde_json::Value>,
+    #[serde(with = "timestamp::required")]
+    pub triggered_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KillScope {
+    #[default]
+    Experiment,
+    Channel,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct Interval(pub Option<DateTime<Utc>>, pub Option<DateTime<Utc>>);
+pub struct Interval(
+    #[serde(with = "timestamp")] pub Option<DateTime<Utc>>,
+    #[serde(with = "timestamp")] pub Option<DateTime<Utc>>,
+);
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Variance {
@@ -41,32 +109,398 @@ pub struct Variance {
     pub indicator: String,
     pub weight: i32,
     pub values: HashMap<String, serde_json::Value>,
+    #[serde(default)]
+    pub levels: HashMap<String, String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Factorial {
+    pub factors: Vec<Factor>,
+    pub fraction: Fraction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Factor {
+    pub name: String,
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Level {
+    pub name: String,
+    pub values: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Fraction {
+    #[default]
+    Full,
+    Half,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Classing {
     pub strategy: String,
     pub persistent_mode: String,
+    #[serde(default)]
+    pub bandit: Option<Bandit>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bandit {
+    pub algorithm: BanditAlgorithm,
+    pub epsilon: f64,
+    pub min_weight: i32,
+    pub max_weight: i32,
+    pub recompute_interval_secs: i64,
+    pub frozen: bool,
+    pub rewards: HashMap<String, Reward>,
+    pub history: Vec<WeightChange>,
+    #[serde(with = "timestamp")]
+    pub recomputed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum BanditAlgorithm {
+    #[default]
+    ThompsonSampling,
+    EpsilonGreedy,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct Reward {
+    pub trials: u64,
+    pub successes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WeightChange {
+    #[serde(with = "timestamp::required")]
+    pub changed_at: DateTime<Utc>,
+    pub weights: HashMap<String, i32>,
+}
+
+/// Document loaded through a projection, the fields left out take their default. The nested
+/// values have their projected read too since the projection may select some of their fields.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Projected {
+    _id: Option<oid::ObjectId>,
+    schema_version: i32,
+    key: String,
+    name: String,
+    description: String,
+    active_interval: Option<Interval>,
+    tags: Vec<String>,
+    variations: Vec<ProjectedVariance>,
+    classing: ProjectedClassing,
+    factorial: Option<ProjectedFactorial>,
+    owner: Option<serde_json::Value>,
+    channel_id: String,
+    status: Status,
+    kill_switch: Option<ProjectedKillSwitch>,
+    #[serde(with = "timestamp")]
+    created_at: Option<DateTime<Utc>>,
+    #[serde(with = "timestamp")]
+    updated_at: Option<DateTime<Utc>>,
+    #[serde(with = "timestamp")]
+    deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedVariance {
+    group_name: String,
+    description: String,
+    indicator: String,
+    weight: i32,
+    values: HashMap<String, serde_json::Value>,
+    levels: HashMap<String, String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedClassing {
+    strategy: String,
+    persistent_mode: String,
+    bandit: Option<ProjectedBandit>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedBandit {
+    algorithm: BanditAlgorithm,
+    epsilon: f64,
+    min_weight: i32,
+    max_weight: i32,
+    recompute_interval_secs: i64,
+    frozen: bool,
+    rewards: HashMap<String, ProjectedReward>,
+    history: Vec<ProjectedWeightChange>,
+    #[serde(with = "timestamp")]
+    recomputed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedReward {
+    trials: u64,
+    successes: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedWeightChange {
+    #[serde(with = "timestamp::required")]
+    changed_at: DateTime<Utc>,
+    weights: HashMap<String, i32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedFactorial {
+    factors: Vec<ProjectedFactor>,
+    fraction: Fraction,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedFactor {
+    name: String,
+    levels: Vec<ProjectedLevel>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedLevel {
+    name: String,
+    values: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ProjectedKillSwitch {
+    scope: KillScope,
+    reason: String,
+    triggered_by: Option<serde_json::Value>,
+    #[serde(with = "timestamp::required")]
+    triggered_at: DateTime<Utc>,
+}
+
+impl From<Projected> for Document {
+    fn from(doc: Projected) -> Self {
+        Self {
+            _id: doc._id,
+            schema_version: doc.schema_version,
+            key: doc.key,
+            name: doc.name,
+            description: doc.description,
+            active_interval: doc.active_interval,
+            tags: doc.tags,
+            variations: doc.variations.into_iter().map(|v| v.into()).collect(),
+            classing: doc.classing.into(),
+            factorial: doc.factorial.map(|v| v.into()),
+            owner: doc.owner,
+            channel_id: doc.channel_id,
+            status: doc.status,
+            kill_switch: doc.kill_switch.map(|v| v.into()),
+            created_at: doc.created_at,
+            updated_at: doc.updated_at,
+            deleted_at: doc.deleted_at,
+        }
+    }
+}
+
+impl From<ProjectedVariance> for Variance {
+    fn from(v: ProjectedVariance) -> Self {
+        Self {
+            group_name: v.group_name,
+            description: v.description,
+            indicator: v.indicator,
+            weight: v.weight,
+            values: v.values,
+            levels: v.levels,
+        }
+    }
+}
+
+impl From<ProjectedClassing> for Classing {
+    fn from(c: ProjectedClassing) -> Self {
+        Self {
+            strategy: c.strategy,
+            persistent_mode: c.persistent_mode,
+            bandit: c.bandit.map(|v| v.into()),
+        }
+    }
+}
+
+impl From<ProjectedBandit> for Bandit {
+    fn from(b: ProjectedBandit) -> Self {
+        Self {
+            algorithm: b.algorithm,
+            epsilon: b.epsilon,
+            min_weight: b.min_weight,
+            max_weight: b.max_weight,
+            recompute_interval_secs: b.recompute_interval_secs,
+            frozen: b.frozen,
+            rewards: b
+                .rewards
+                .into_iter()
+                .map(|(k, v)| {
+                    let reward = Reward {
+                        trials: v.trials,
+                        successes: v.successes,
+                    };
+                    (k, reward)
+                })
+                .collect(),
+            history: b
+                .history
+                .into_iter()
+                .map(|v| WeightChange {
+                    changed_at: v.changed_at,
+                    weights: v.weights,
+                })
+                .collect(),
+            recomputed_at: b.recomputed_at,
+        }
+    }
+}
+
+impl From<ProjectedFactorial> for Factorial {
+    fn from(f: ProjectedFactorial) -> Self {
+        Self {
+            factors: f.factors.into_iter().map(|v| v.into()).collect(),
+            fraction: f.fraction,
+        }
+    }
+}
+
+impl From<ProjectedFactor> for Factor {
+    fn from(f: ProjectedFactor) -> Self {
+        Self {
+            name: f.name,
+            levels: f
+                .levels
+                .into_iter()
+                .map(|l| Level {
+                    name: l.name,
+                    values: l.values,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ProjectedKillSwitch> for KillSwitch {
+    fn from(ks: ProjectedKillSwitch) -> Self {
+        Self {
+            scope: ks.scope,
+            reason: ks.reason,
+            triggered_by: ks.triggered_by,
+            triggered_at: ks.triggered_at,
+        }
+    }
 }
 
 impl From<service::Experiment> for Document {
     fn from(data: service::Experiment) -> Self {
         Self {
-            _id: None,
-            id: data.id,
+            _id: data
+                .id
+                .as_deref()
+                .and_then(|id| oid::ObjectId::parse_str(id).ok()),
+            schema_version: SCHEMA_VERSION,
+            key: data.key,
             name: data.name,
             description: data.description,
             active_interval: data.active_interval.map(|v| v.into()),
+            tags: data.tags,
             created_at: data.created_at,
             updated_at: data.updated_at,
             deleted_at: data.deleted_at,
             variations: data.variations.into_iter().map(|v| v.into()).collect(),
-            classing: Classing {
-                strategy: data.classing.strategy,
-                persistent_mode: data.classing.persistent_mode,
-            },
+            classing: data.classing.into(),
+            factorial: data.factorial.map(|v| v.into()),
             owner: data.owner,
             channel_id: data.channel_id,
+            status: data.status.into(),
+            kill_switch: data.kill_switch.map(|v| v.into()),
+        }
+    }
+}
+
+impl From<service::Classing> for Classing {
+    fn from(data: service::Classing) -> Self {
+        Self {
+            strategy: data.strategy,
+            persistent_mode: data.persistent_mode,
+            bandit: data.bandit.map(|v| v.into()),
+        }
+    }
+}
+
+impl From<bandit::Bandit> for Bandit {
+    fn from(data: bandit::Bandit) -> Self {
+        Self {
+            algorithm: match data.algorithm {
+                bandit::Algorithm::ThompsonSampling => BanditAlgorithm::ThompsonSampling,
+                bandit::Algorithm::EpsilonGreedy => BanditAlgorithm::EpsilonGreedy,
+            },
+            epsilon: data.epsilon,
+            min_weight: data.min_weight,
+            max_weight: data.max_weight,
+            recompute_interval_secs: data.recompute_interval_secs,
+            frozen: data.frozen,
+            rewards: data
+                .rewards
+                .into_iter()
+                .map(|(k, v)| {
+                    let reward = Reward {
+                        trials: v.trials,
+                        successes: v.successes,
+                    };
+                    (k, reward)
+                })
+                .collect(),
+            history: data.history.into_iter().map(|v| v.into()).collect(),
+            recomputed_at: data.recomputed_at,
+        }
+    }
+}
+
+impl From<bandit::WeightChange> for WeightChange {
+    fn from(data: bandit::WeightChange) -> Self {
+        Self {
+            changed_at: data.changed_at,
+            weights: data.weights,
+        }
+    }
+}
+
+impl From<service::KillSwitch> for KillSwitch {
+    fn from(data: service::KillSwitch) -> Self {
+        Self {
+            scope: match data.scope {
+                service::KillScope::Experiment => KillScope::Experiment,
+                service::KillScope::Channel => KillScope::Channel,
+            },
+            reason: data.reason,
+            triggered_by: data.triggered_by,
+            triggered_at: data.triggered_at,
+        }
+    }
+}
+
+impl From<service::Status> for Status {
+    fn from(status: service::Status) -> Self {
+        match status {
+            service::Status::Scheduled => Self::Scheduled,
+            service::Status::Running => Self::Running,
+            service::Status::Paused => Self::Paused,
+            service::Status::Ended => Self::Ended,
         }
     }
 }
@@ -85,6 +519,37 @@ impl From<service::Variance> for Variance {
             indicator: data.indicator,
             weight: data.weight,
             values: data.values,
+            levels: data.levels,
+        }
+    }
+}
+
+impl From<factorial::Factorial> for Factorial {
+    fn from(data: factorial::Factorial) -> Self {
+        Self {
+            factors: data.factors.into_iter().map(|v| v.into()).collect(),
+            fraction: match data.fraction {
+                factorial::Fraction::Full => Fraction::Full,
+                factorial::Fraction::Half => Fraction::Half,
+            },
+        }
+    }
+}
+
+impl From<factorial::Factor> for Factor {
+    fn from(data: factorial::Factor) -> Self {
+        Self {
+            name: data.name,
+            levels: data.levels.into_iter().map(|v| v.into()).collect(),
+        }
+    }
+}
+
+impl From<factorial::Level> for Level {
+    fn from(data: factorial::Level) -> Self {
+        Self {
+            name: data.name,
+            values: data.values,
         }
     }
 }
@@ -93,13 +558,18 @@ impl From<Document> for service::Experiment {
     fn from(doc: Document) -> Self {
         service::Experiment {
             id: doc._id.map(|v| v.to_hex()),
+            key: doc.key,
             name: doc.name,
             description: doc.description,
             active_interval: doc.active_interval.map(|v| v.into()),
+            tags: doc.tags,
             variations: doc.variations.into_iter().map(|v| v.into()).collect(),
             classing: doc.classing.into(),
+            factorial: doc.factorial.map(|v| v.into()),
             owner: doc.owner,
             channel_id: doc.channel_id,
+            status: doc.status.into(),
+            kill_switch: doc.kill_switch.map(|v| v.into()),
             created_at: doc.created_at,
             updated_at: doc.updated_at,
             deleted_at: doc.deleted_at,
@@ -107,6 +577,31 @@ impl From<Document> for service::Experiment {
     }
 }
 
+impl From<Status> for service::Status {
+    fn from(status: Status) -> Self {
+        match status {
+            Status::Scheduled => Self::Scheduled,
+            Status::Running => Self::Running,
+            Status::Paused => Self::Paused,
+            Status::Ended => Self::Ended,
+        }
+    }
+}
+
+impl From<KillSwitch> for service::KillSwitch {
+    fn from(ks: KillSwitch) -> Self {
+        Self {
+            scope: match ks.scope {
+                KillScope::Experiment => service::KillScope::Experiment,
+                KillScope::Channel => service::KillScope::Channel,
+            },
+            reason: ks.reason,
+            triggered_by: ks.triggered_by,
+            triggered_at: ks.triggered_at,
+        }
+    }
+}
+
 impl From<Interval> for service::Interval {
     fn from(i: Interval) -> Self {
         Self(i.0, i.1)
@@ -121,6 +616,37 @@ impl From<Variance> for service::Variance {
             indicator: v.indicator,
             weight: v.weight,
             values: v.values,
+            levels: v.levels,
+        }
+    }
+}
+
+impl From<Factorial> for factorial::Factorial {
+    fn from(f: Factorial) -> Self {
+        Self {
+            factors: f.factors.into_iter().map(|v| v.into()).collect(),
+            fraction: match f.fraction {
+                Fraction::Full => factorial::Fraction::Full,
+                Fraction::Half => factorial::Fraction::Half,
+            },
+        }
+    }
+}
+
+impl From<Factor> for factorial::Factor {
+    fn from(f: Factor) -> Self {
+        Self {
+            name: f.name,
+            levels: f.levels.into_iter().map(|v| v.into()).collect(),
+        }
+    }
+}
+
+impl From<Level> for factorial::Level {
+    fn from(l: Level) -> Self {
+        Self {
+            name: l.name,
+            values: l.values,
         }
     }
 }
@@ -130,116 +656,659 @@ impl From<Classing> for service::Classing {
         Self {
             strategy: c.strategy,
             persistent_mode: c.persistent_mode,
+            bandit: c.bandit.map(|v| v.into()),
         }
     }
 }
 
-pub struct Repo {
-    coll: Collection<Document>,
+impl From<Bandit> for bandit::Bandit {
+    fn from(b: Bandit) -> Self {
+        Self {
+            algorithm: match b.algorithm {
+                BanditAlgorithm::ThompsonSampling => bandit::Algorithm::ThompsonSampling,
+                BanditAlgorithm::EpsilonGreedy => bandit::Algorithm::EpsilonGreedy,
+            },
+            epsilon: b.epsilon,
+            min_weight: b.min_weight,
+            max_weight: b.max_weight,
+            recompute_interval_secs: b.recompute_interval_secs,
+            frozen: b.frozen,
+            rewards: b
+                .rewards
+                .into_iter()
+                .map(|(k, v)| {
+                    let reward = bandit::Reward {
+                        trials: v.trials,
+                        successes: v.successes,
+                    };
+                    (k, reward)
+                })
+                .collect(),
+            history: b
+                .history
+                .into_iter()
+                .map(|v| bandit::WeightChange {
+                    changed_at: v.changed_at,
+                    weights: v.weights,
+                })
+                .collect(),
+            recomputed_at: b.recomputed_at,
+        }
+    }
 }
 
-impl Repo {
-    pub fn new(coll: Collection<Document>) -> Self {
-        Self { coll }
+/// Serde of the timestamps stored as dates. The ones written before `SCHEMA_VERSION` 1, or 3
+/// for the ones of the kill switch and the bandit, are still read, as milliseconds or RFC 3339
+/// strings.
+mod timestamp {
+    use bson::Bson;
+    use chrono::{DateTime, Utc};
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Option<DateTime<Utc>>, s: S) -> Result<S::Ok, S::Error> {
+        v.map(bson::DateTime::from_chrono).serialize(s)
     }
-}
 
-#[async_trait]
-impl service::Store for Repo {
-    async fn save(&self, data: &mut service::Experiment) -> Result<String> {
-        let now = Utc::now();
-        data.updated_at = Some(now);
-        if data.created_at.is_none() {
-            data.created_at = Some(now);
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        read(&Bson::deserialize(d)?).map_err(de::Error::custom)
+    }
+
+    /// Read a timestamp whatever shape it was stored in.
+    pub fn read(v: &Bson) -> Result<Option<DateTime<Utc>>, String> {
+        match v {
+            Bson::Null | Bson::Undefined => Ok(None),
+            Bson::DateTime(dt) => Ok(Some(dt.to_chrono())),
+            Bson::Int64(ms) => Ok(Some(bson::DateTime::from_millis(*ms).to_chrono())),
+            Bson::Int32(ms) => Ok(Some(bson::DateTime::from_millis(*ms as i64).to_chrono())),
+            Bson::String(s) => DateTime::parse_from_rfc3339(s)
+                .map(|dt| Some(dt.with_timezone(&Utc)))
+                .map_err(|e| format!("invalid timestamp `{}`: {}", s, e)),
+            v => Err(format!("invalid timestamp {}", v)),
+        }
+    }
+
+    /// Serde of the timestamps which are always set.
+    pub mod required {
+        use bson::Bson;
+        use chrono::{DateTime, Utc};
+        use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+        pub fn serialize<S: Serializer>(v: &DateTime<Utc>, s: S) -> Result<S::Ok, S::Error> {
+            bson::DateTime::from_chrono(*v).serialize(s)
         }
 
-        let mut document = Document::from(data.clone());
-        if document._id.is_none() {
-            document._id = Some(oid::ObjectId::new());
+        pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<DateTime<Utc>, D::Error> {
+            super::read(&Bson::deserialize(d)?)
+                .map_err(de::Error::custom)?
+                .ok_or_else(|| de::Error::custom("missing timestamp"))
         }
+    }
+}
 
-        let result = self.coll.insert_one(document, None).await;
-        let insert_result = result.map_err(|e| service::StoreError::InternalError {
-            message: e.to_string(),
-        })?;
+pub(crate) use timestamp::read as read_timestamp;
 
-        if let Bson::ObjectId(ref id) = insert_result.inserted_id {
-            data.id = Some(id.to_hex());
-            let dd = oid::ObjectId::parse_str(id.to_hex());
-            match dd {
-                Ok(o) => println!("created: {}", o),
-                Err(e) => println!("error: {}", e),
-            }
-        } else {
-            return Err(service::StoreError::InternalError {
-                message: "undefined inserted id".to_string(),
-            }
-            .into());
+fn parse_id(id: &str) -> Result<oid::ObjectId> {
+    oid::ObjectId::parse_str(id).map_err(|e| {
+        service::StoreError::InvalidInput {
+            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
         }
+        .into()
+    })
+}
 
-        Ok(data.id.clone().unwrap_or_default())
+/// Stamp the new experiment and build its document, the experiment gets the id of the document.
+fn new_document(data: &mut service::Experiment) -> Result<Document> {
+    let now = Utc::now();
+    data.updated_at = Some(now);
+    if data.created_at.is_none() {
+        data.created_at = Some(now);
     }
+    data.deleted_at = None;
+    to_stored_precision(data);
 
-    async fn list(&self, channel_id: &str) -> Result<Vec<service::Experiment>> {
-        let result = self.coll.find(doc! {"channel_id": channel_id}, None).await;
+    // an experiment brought from elsewhere, e.g. an import, keeps its id.
+    let oid = match &data.id {
+        Some(id) => parse_id(id)?,
+        None => oid::ObjectId::new(),
+    };
+    data.id = Some(oid.to_hex());
 
-        let cursor = result.map_err(|e| -> service::StoreError {
-            service::StoreError::InternalError {
-                message: e.to_string(),
-            }
-        })?;
+    Ok(Document::from(data.clone()))
+}
 
-        let docs: Vec<service::Experiment> = cursor
-            .map_ok(|d| d.into())
-            .try_collect()
-            .map_err(|e| -> service::StoreError {
-                service::StoreError::InternalError {
-                    message: e.to_string(),
-                }
-            })
-            .await?;
+/// Stamp the updated experiment and build the document replacing the stored one, at the
+/// `expected` version if any.
+fn replacement(
+    data: &mut service::Experiment,
+    expected: Option<service::Version>,
+) -> Result<(oid::ObjectId, Document)> {
+    let oid = parse_id(&data.id.clone().unwrap_or_default())?;
 
-        Ok(docs)
-    }
+    data.updated_at = Some(expected.map(|v| v.next()).unwrap_or_else(Utc::now));
+    to_stored_precision(data);
 
-    async fn get(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
-        let id = oid::ObjectId::parse_str(id).map_err(|e| service::StoreError::InvalidInput {
-            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
-        })?;
+    let mut document = Document::from(data.clone());
+    document._id = Some(oid);
 
-        let result = self.coll.find_one(doc! {"_id": id }, None).await;
+    Ok((oid, document))
+}
 
-        let doc = result
-            .map_err(|e| -> service::StoreError {
-                service::StoreError::InternalError {
-                    message: e.to_string(),
-                }
-            })?
-            .ok_or(service::StoreError::DocumentNotfound)?;
+/// Filter of the experiment of the channel, at the `expected` version if any.
+fn filter_of(
+    oid: oid::ObjectId,
+    channel_id: &str,
+    expected: Option<service::Version>,
+) -> bson::Document {
+    let mut filter = doc! {"_id": oid, "channel_id": channel_id, "deleted_at": Bson::Null};
+    if let Some(service::Version(at)) = expected {
+        filter.insert("updated_at", at.map(bson::DateTime::from_chrono));
+    }
+    filter
+}
 
-        if doc.channel_id != channel_id {
-            return Err(service::StoreError::UnauthorizedAccess.into());
-        }
+/// Filter of the experiment of the channel in the trash, purged once its id is reused.
+fn trashed_filter(oid: oid::ObjectId, channel_id: &str) -> bson::Document {
+    doc! {"_id": oid, "channel_id": channel_id, "deleted_at": {"$ne": Bson::Null}}
+}
 
-        Ok(doc.into())
+/// Move the experiment to the trash, `TRASH_RETENTION` before the database purges it. Its
+/// key is freed for the experiments created meanwhile.
+fn trash_update(now: DateTime<Utc>) -> bson::Document {
+    doc! {
+        "$set": {"deleted_at": bson::DateTime::from_chrono(now)},
+        "$unset": {"key": ""},
     }
+}
 
-    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
-        let id = oid::ObjectId::parse_str(id).map_err(|e| service::StoreError::InvalidInput {
-            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
-        })?;
+/// Filter, update and array filters applying the patch to the experiment of the channel
+/// in place.
+fn patch_query(
+    oid: oid::ObjectId,
+    channel_id: &str,
+    patch: &service::Patch,
+    now: DateTime<Utc>,
+) -> Result<(bson::Document, UpdateModifications, Vec<bson::Document>)> {
+    let mut filter = doc! {"_id": oid, "channel_id": channel_id, "deleted_at": Bson::Null};
+    let mut set = doc! {"updated_at": bson::DateTime::from_chrono(now)};
+    let mut update = doc! {};
+    let mut array_filters = vec![];
 
-        let result = self
-            .coll
-            .delete_one(doc! {"_id": id, "channel_id": channel_id }, None)
+    match patch {
+        service::Patch::Status { from, to } => {
+            let from_value = bson::to_bson(&Status::from(*from))?;
+            if *from == service::Status::default() {
+                filter.insert(
+                    "$or",
+                    vec![
+                        doc! {"status": from_value},
+                        doc! {"status": {"$exists": false}},
+                    ],
+                );
+            } else {
+                filter.insert("status", from_value);
+            }
+            filter.insert("kill_switch", Bson::Null);
+            set.insert("status", bson::to_bson(&Status::from(*to))?);
+        }
+        service::Patch::Rewards(rewards) => {
+            filter.insert("classing.bandit", doc! {"$ne": Bson::Null});
+            let mut inc = doc! {};
+            for (indicator, reward) in rewards.iter() {
+                let path = format!("classing.bandit.rewards.{}", field_name(indicator)?);
+                inc.insert(format!("{}.trials", path), reward.trials as i64);
+                inc.insert(format!("{}.successes", path), reward.successes as i64);
+            }
+            update.insert("$inc", inc);
+        }
+        service::Patch::Weights(change) => {
+            filter.insert("status", bson::to_bson(&Status::Running)?);
+            filter.insert("kill_switch", Bson::Null);
+            filter.insert("classing.bandit", doc! {"$ne": Bson::Null});
+            filter.insert("classing.bandit.frozen", doc! {"$ne": true});
+            for (i, (indicator, weight)) in change.weights.iter().enumerate() {
+                set.insert(format!("variations.$[v{}].weight", i), *weight);
+                array_filters.push(doc! {format!("v{}.indicator", i): indicator});
+            }
+            set.insert(
+                "classing.bandit.recomputed_at",
+                bson::DateTime::from_chrono(change.changed_at),
+            );
+            let entry = bson::to_bson(&WeightChange::from(change.clone()))?;
+            update.insert("$push", doc! {"classing.bandit.history": entry});
+        }
+        service::Patch::Tags { add, remove } => {
+            let current = doc! {"$ifNull": ["$tags", []]};
+            let kept = doc! {"$filter": {
+                "input": current,
+                "cond": {"$not": [{"$in": ["$$this", remove]}]},
+            }};
+            let tags = doc! {"$concatArrays": [kept.clone(), {"$filter": {
+                "input": add,
+                "cond": {"$not": [{"$in": ["$$this", kept]}]},
+            }}]};
+            filter.insert(
+                "$expr",
+                doc! {"$lte": [{"$size": tags.clone()}, tag::MAX_TAGS as i64]},
+            );
+
+            if remove.is_empty() {
+                update.insert("$addToSet", doc! {"tags": {"$each": add}});
+            } else if add.is_empty() {
+                update.insert("$pull", doc! {"tags": {"$in": remove}});
+            } else {
+                // `$addToSet` and `$pull` cannot both change the tags in one update.
+                set.insert("tags", tags);
+                return Ok((
+                    filter,
+                    UpdateModifications::Pipeline(vec![doc! {"$set": set}]),
+                    array_filters,
+                ));
+            }
+        }
+        service::Patch::Kill(kill_switch) => {
+            if kill_switch.scope == service::KillScope::Channel {
+                filter.insert("status", doc! {"$ne": bson::to_bson(&Status::Ended)?});
+                filter.insert("kill_switch", Bson::Null);
+            }
+            let kill_switch = KillSwitch::from(kill_switch.clone());
+            set.insert("kill_switch", bson::to_bson(&kill_switch)?);
+        }
+        service::Patch::Freeze(frozen) => {
+            filter.insert("classing.bandit", doc! {"$ne": Bson::Null});
+            set.insert("classing.bandit.frozen", *frozen);
+        }
+        service::Patch::Release(scope) => {
+            filter.insert("kill_switch", doc! {"$ne": Bson::Null});
+            if let Some(scope) = scope {
+                filter.insert("kill_switch.scope", bson::to_bson(scope)?);
+            }
+            set.insert("kill_switch", Bson::Null);
+        }
+    }
+    update.insert("$set", set);
+
+    Ok((filter, UpdateModifications::Document(update), array_filters))
+}
+
+/// Check the key can be used as the name of a field of the document.
+fn field_name(key: &str) -> Result<&str> {
+    if key.is_empty() || key.contains('.') || key.starts_with('$') {
+        return Err(service::StoreError::InvalidInput {
+            message: format!("`{}` cannot be stored as a field name", key),
+        }
+        .into());
+    }
+    Ok(key)
+}
+
+/// Event of the patch applied to the experiment, written to the outbox.
+fn patch_event(patch: &service::Patch, data: &service::Experiment, now: DateTime<Utc>) -> Event {
+    match patch {
+        service::Patch::Status { from, to } => Event::status_changed(
+            &data.id.clone().unwrap_or_default(),
+            &data.channel_id,
+            *from,
+            *to,
+            now,
+        ),
+        service::Patch::Rewards(_) | service::Patch::Tags { .. } | service::Patch::Freeze(_) => {
+            Event::updated(data)
+        }
+        service::Patch::Weights(_) => Event::weights_changed(data, now),
+        service::Patch::Kill(kill_switch) => Event::killed(data, kill_switch),
+        service::Patch::Release(_) => Event::released(data, None),
+    }
+}
+
+/// Truncate the timestamps to the milliseconds of the stored dates, so the experiment handed
+/// back after a write is the same as the one loaded later.
+fn to_stored_precision(data: &mut service::Experiment) {
+    let truncate = |v: &mut Option<DateTime<Utc>>| {
+        *v = v.map(|dt| bson::DateTime::from_chrono(dt).to_chrono());
+    };
+    truncate(&mut data.created_at);
+    truncate(&mut data.updated_at);
+    truncate(&mut data.deleted_at);
+    if let Some(interval) = data.active_interval.as_mut() {
+        truncate(&mut interval.0);
+        truncate(&mut interval.1);
+    }
+}
+
+/// Translate the projection to the mongo one, `channel_id` is always loaded since the
+/// access is checked against it, `required` are the other fields which must be loaded. The
+/// dotted paths are kept, `Projected` reads what they leave, but under the fields loaded
+/// whole and the bounds of `active_interval` which are not addressed by name.
+fn mongo_projection(projection: &Projection, required: &[&str]) -> bson::Document {
+    let mut fields = doc! {"channel_id": 1};
+    for path in projection.paths() {
+        let field = path.split('.').next().unwrap_or_default();
+        let path =
+            if field == "channel_id" || field == "active_interval" || required.contains(&field) {
+                field
+            } else {
+                &path
+            };
+        if path != "id" && !fields.contains_key(path) {
+            fields.insert(path, 1);
+        }
+    }
+    for field in required {
+        if !fields.contains_key(*field) {
+            fields.insert(*field, 1);
+        }
+    }
+    fields
+}
+
+fn sort_field(sort: service::SortField) -> &'static str {
+    match sort {
+        service::SortField::CreatedAt => "created_at",
+        service::SortField::UpdatedAt => "updated_at",
+        service::SortField::Name => "name",
+    }
+}
+
+/// Build the filter of the experiments matching the query, the pagination aside.
+fn list_filter(channel_id: &str, query: &service::ListQuery) -> Result<bson::Document> {
+    let mut filter = doc! {"channel_id": channel_id, "deleted_at": Bson::Null};
+    let mut conditions = vec![];
+
+    if let Some(status) = query.status {
+        let value = bson::to_bson(&Status::from(status))?;
+        if status == service::Status::default() {
+            conditions.push(doc! {"$or": [{"status": value}, {"status": {"$exists": false}}]});
+        } else {
+            filter.insert("status", value);
+        }
+    }
+    if let Some(ref owner_id) = query.owner_id {
+        filter.insert("owner.id", owner_id);
+    }
+    if let Some(ref tag) = query.tag {
+        filter.insert("tags", tag);
+    }
+    if let Some(ref prefix) = query.name_prefix {
+        filter.insert(
+            "name",
+            doc! {"$regex": format!("^{}", escape_regex(prefix))},
+        );
+    }
+    if let Some(active_at) = query.active_at {
+        let at = Bson::DateTime(bson::DateTime::from_chrono(active_at));
+        conditions.push(doc! {"$or": [
+            {"active_interval": Bson::Null},
+            {"active_interval.0": Bson::Null},
+            {"active_interval.0": {"$lte": at.clone()}},
+        ]});
+        conditions.push(doc! {"$or": [
+            {"active_interval": Bson::Null},
+            {"active_interval.1": Bson::Null},
+            {"active_interval.1": {"$gt": at}},
+        ]});
+    }
+
+    if !conditions.is_empty() {
+        filter.insert("$and", conditions);
+    }
+
+    Ok(filter)
+}
+
+fn escape_regex(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| {
+            if "\\^$.|?*+()[]{}".contains(c) {
+                vec!['\\', c]
+            } else {
+                vec![c]
+            }
+        })
+        .collect()
+}
+
+pub struct Repo {
+    coll: Collection<Document>,
+    client: Option<Client>,
+    outbox: Option<Collection<outbox::Document>>,
+}
+
+impl Repo {
+    pub fn new(coll: Collection<Document>) -> Self {
+        Self {
+            coll,
+            client: None,
+            outbox: None,
+        }
+    }
+
+    /// Enable the transactions of the batches, they need the client of the collection.
+    pub fn with_client(mut self, client: Client) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Write the event of every change to the outbox in the transaction of the change. The
+    /// writes then need the client and a deployment with transactions, e.g. a replica set.
+    pub fn with_outbox(mut self, outbox: Collection<outbox::Document>) -> Self {
+        self.outbox = Some(outbox);
+        self
+    }
+
+    /// Apply the writes and their events in a transaction, `conflict` is the message of a
+    /// duplicate key.
+    async fn write_with_events(
+        &self,
+        writes: &mut [service::Write],
+        expected: Option<service::Version>,
+        conflict: impl FnOnce() -> String,
+    ) -> Result<()> {
+        let internal_error = |e: mongodb::error::Error| service::StoreError::InternalError {
+            message: e.to_string(),
+        };
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| service::StoreError::InternalError {
+                message: "the outbox needs the client of the collection".to_owned(),
+            })?;
+
+        let mut session = client.start_session(None).await.map_err(internal_error)?;
+        session
+            .start_transaction(None)
+            .await
+            .map_err(internal_error)?;
+
+        if let Err(e) = self.write_in_session(writes, expected, &mut session).await {
+            let _ = session.abort_transaction().await;
+
+            return Err(match e.downcast::<mongodb::error::Error>() {
+                Ok(e) if super::is_duplicate_key(&e) => service::StoreError::Conflict {
+                    message: conflict(),
+                }
+                .into(),
+                Ok(e) => internal_error(e).into(),
+                Err(e) => e,
+            });
+        }
+
+        session.commit_transaction().await.map_err(internal_error)?;
+        Ok(())
+    }
+
+    /// Apply the writes in the transaction of the session, the replaced and deleted
+    /// experiments must be at the `expected` version if any.
+    async fn write_in_session(
+        &self,
+        writes: &mut [service::Write],
+        expected: Option<service::Version>,
+        session: &mut ClientSession,
+    ) -> Result<()> {
+        for write in writes.iter_mut() {
+            match write {
+                service::Write::Insert(data) => {
+                    let given_id = data.id.is_some();
+                    let document = new_document(data)?;
+                    if let (true, Some(oid)) = (given_id, document._id) {
+                        self.coll
+                            .delete_one_with_session(
+                                trashed_filter(oid, &data.channel_id),
+                                None,
+                                session,
+                            )
+                            .await?;
+                    }
+                    self.coll
+                        .insert_one_with_session(document, None, session)
+                        .await?;
+                    self.record_in_session(Event::created(data), session)
+                        .await?;
+                }
+                service::Write::Replace(data) => {
+                    let (oid, document) = replacement(data, expected)?;
+                    // the status replaced tells whether the status changed too.
+                    let opts = FindOneAndReplaceOptions::builder()
+                        .projection(doc! {"status": 1})
+                        .return_document(ReturnDocument::Before)
+                        .build();
+                    let replaced = self
+                        .coll
+                        .clone_with_type::<bson::Document>()
+                        .find_one_and_replace_with_session(
+                            filter_of(oid, &data.channel_id, expected),
+                            bson::to_document(&document)?,
+                            opts,
+                            session,
+                        )
+                        .await?;
+                    let replaced = match replaced {
+                        Some(replaced) => replaced,
+                        None => {
+                            return Err(self
+                                .not_written(oid, &data.channel_id, Some(session))
+                                .await?)
+                        }
+                    };
+                    self.record_in_session(Event::updated(data), session)
+                        .await?;
+
+                    let from = match replaced.get("status") {
+                        Some(status) => bson::from_bson::<Status>(status.clone())?.into(),
+                        None => service::Status::default(),
+                    };
+                    if from != data.status {
+                        let event = Event::status_changed(
+                            &oid.to_hex(),
+                            &data.channel_id,
+                            from,
+                            data.status,
+                            data.updated_at.unwrap_or_else(Utc::now),
+                        );
+                        self.record_in_session(event, session).await?;
+                    }
+                }
+                service::Write::Delete { id, channel_id } => {
+                    let oid = parse_id(id)?;
+                    let now = Utc::now();
+                    let result = self
+                        .coll
+                        .update_one_with_session(
+                            filter_of(oid, channel_id, expected),
+                            trash_update(now),
+                            None,
+                            session,
+                        )
+                        .await?;
+                    if result.matched_count == 0 {
+                        return Err(self.not_written(oid, channel_id, Some(session)).await?);
+                    }
+                    let event = Event::deleted(&oid.to_hex(), channel_id, now);
+                    self.record_in_session(event, session).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace the experiment, at the `expected` version if any.
+    async fn replace(
+        &self,
+        data: &mut service::Experiment,
+        expected: Option<service::Version>,
+    ) -> Result<()> {
+        if self.outbox.is_some() {
+            let key = data.key.clone();
+            let mut writes = [service::Write::Replace(data.clone())];
+            self.write_with_events(&mut writes, expected, || format!("key `{}` is taken", key))
+                .await?;
+            if let [service::Write::Replace(updated)] = writes {
+                *data = updated;
+            }
+            return Ok(());
+        }
+
+        let (oid, document) = replacement(data, expected)?;
+
+        let result = self
+            .coll
+            .replace_one(filter_of(oid, &data.channel_id, expected), document, None)
+            .await
+            .map_err(|e| {
+                if super::is_duplicate_key(&e) {
+                    service::StoreError::Conflict {
+                        message: format!("key `{}` is taken", data.key),
+                    }
+                } else {
+                    service::StoreError::InternalError {
+                        message: e.to_string(),
+                    }
+                }
+            })?;
+
+        if result.matched_count == 0 {
+            return Err(self.not_written(oid, &data.channel_id, None).await?);
+        }
+
+        Ok(())
+    }
+
+    /// Move the experiment to the trash, at the `expected` version if any.
+    async fn remove(
+        &self,
+        id: &str,
+        channel_id: &str,
+        expected: Option<service::Version>,
+    ) -> Result<()> {
+        if self.outbox.is_some() {
+            let mut writes = [service::Write::Delete {
+                id: id.to_owned(),
+                channel_id: channel_id.to_owned(),
+            }];
+            return self
+                .write_with_events(&mut writes, expected, || "experiment is taken".to_owned())
+                .await;
+        }
+
+        let id = oid::ObjectId::parse_str(id).map_err(|e| service::StoreError::InvalidInput {
+            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
+        })?;
+
+        let result = self
+            .coll
+            .update_one(
+                filter_of(id, channel_id, expected),
+                trash_update(Utc::now()),
+                None,
+            )
             .await;
 
         match result {
-            Ok(dr) => {
-                if dr.deleted_count > 0 {
+            Ok(ur) => {
+                if ur.matched_count > 0 {
                     Ok(())
                 } else {
-                    Err(service::StoreError::DocumentNotfound.into())
+                    Err(self.not_written(id, channel_id, None).await?)
                 }
             }
             Err(e) => Err(service::StoreError::InternalError {
@@ -248,4 +1317,865 @@ impl service::Store for Repo {
             .into()),
         }
     }
+
+    /// Patch the experiment and write the event of the patch to the outbox in a transaction.
+    async fn patch_with_event(
+        &self,
+        filter: bson::Document,
+        update: UpdateModifications,
+        opts: FindOneAndUpdateOptions,
+        event: impl FnOnce(&service::Experiment) -> Event,
+    ) -> Result<Option<Document>> {
+        let internal_error = |e: mongodb::error::Error| service::StoreError::InternalError {
+            message: e.to_string(),
+        };
+        let client = self
+            .client
+            .as_ref()
+            .ok_or_else(|| service::StoreError::InternalError {
+                message: "the outbox needs the client of the collection".to_owned(),
+            })?;
+
+        let mut session = client.start_session(None).await.map_err(internal_error)?;
+        session
+            .start_transaction(None)
+            .await
+            .map_err(internal_error)?;
+
+        let result: Result<Option<Document>> = async {
+            let patched = self
+                .coll
+                .find_one_and_update_with_session(filter, update, opts, &mut session)
+                .await?;
+            if let Some(ref document) = patched {
+                let data = service::Experiment::from(document.clone());
+                self.record_in_session(event(&data), &mut session).await?;
+            }
+            Ok(patched)
+        }
+        .await;
+
+        match result {
+            Ok(patched) => {
+                session.commit_transaction().await.map_err(internal_error)?;
+                Ok(patched)
+            }
+            Err(e) => {
+                let _ = session.abort_transaction().await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Why a write of the experiment matched no document, it is missing or at another version.
+    async fn not_written(
+        &self,
+        oid: oid::ObjectId,
+        channel_id: &str,
+        session: Option<&mut ClientSession>,
+    ) -> Result<anyhow::Error> {
+        let filter = doc! {"_id": oid, "channel_id": channel_id, "deleted_at": Bson::Null};
+        let count = match session {
+            Some(session) => {
+                self.coll
+                    .count_documents_with_session(filter, None, session)
+                    .await?
+            }
+            None => self.coll.count_documents(filter, None).await?,
+        };
+
+        Ok(if count > 0 {
+            service::StoreError::Stale.into()
+        } else {
+            service::StoreError::DocumentNotfound.into()
+        })
+    }
+
+    /// Write the event to the outbox, if any, in the transaction of the session.
+    async fn record_in_session(&self, event: Event, session: &mut ClientSession) -> Result<()> {
+        if let Some(outbox) = &self.outbox {
+            outbox
+                .insert_one_with_session(outbox::Document::new(event), None, session)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Load the experiment as a `T`, the `Document` itself or its `Projected` read.
+    async fn find_in_channel<T>(
+        &self,
+        id: &str,
+        channel_id: &str,
+        opts: Option<FindOneOptions>,
+    ) -> Result<service::Experiment>
+    where
+        T: DeserializeOwned + Into<Document> + Unpin + Send + Sync,
+    {
+        let id = oid::ObjectId::parse_str(id).map_err(|e| service::StoreError::InvalidInput {
+            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
+        })?;
+
+        let result = self
+            .coll
+            .clone_with_type::<T>()
+            .find_one(doc! {"_id": id, "deleted_at": Bson::Null}, opts)
+            .await;
+
+        let doc: Document = result
+            .map_err(|e| -> service::StoreError {
+                service::StoreError::InternalError {
+                    message: e.to_string(),
+                }
+            })?
+            .ok_or(service::StoreError::DocumentNotfound)?
+            .into();
+
+        if doc.channel_id != channel_id {
+            return Err(service::StoreError::UnauthorizedAccess.into());
+        }
+
+        Ok(doc.into())
+    }
+
+    /// Bring the indexes of the collection in line with `indexes`, missing indexes are
+    /// created and the ones of an older definition are replaced. Indexes the application does
+    /// not define are reported but kept. With `check_only` nothing is changed.
+    pub async fn reconcile_indexes(&self, check_only: bool) -> Result<Vec<IndexStatus>> {
+        let existing: Vec<IndexModel> = match self.coll.list_indexes(None).await {
+            Ok(cursor) => cursor.try_collect().await?,
+            Err(e) if is_namespace_missing(&e) => vec![],
+            Err(e) => return Err(e.into()),
+        };
+
+        let desired = indexes();
+        let mut report = vec![];
+        for model in desired.iter() {
+            let name = index_name(model);
+            let drift = drift(model, existing.iter().find(|m| index_name(m) == name));
+            let fixed = match (drift, check_only) {
+                (Drift::Missing, false) => {
+                    self.coll.create_index(model.clone(), None).await?;
+                    true
+                }
+                (Drift::Changed, false) => {
+                    self.coll.drop_index(&name, None).await?;
+                    self.coll.create_index(model.clone(), None).await?;
+                    true
+                }
+                _ => false,
+            };
+            report.push(IndexStatus { name, drift, fixed });
+        }
+
+        for model in existing.iter() {
+            let name = index_name(model);
+            if name != ID_INDEX_NAME && !desired.iter().any(|m| index_name(m) == name) {
+                report.push(IndexStatus {
+                    name,
+                    drift: Drift::Unexpected,
+                    fixed: false,
+                });
+            }
+        }
+
+        Ok(report)
+    }
+}
+
+/// How long a deleted experiment is kept before the database purges it.
+pub const TRASH_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+const ID_INDEX_NAME: &str = "_id_";
+const CHANNEL_INDEX_NAME: &str = "experiment_channel";
+const CHANNEL_CREATED_INDEX_NAME: &str = "experiment_channel_created_at";
+const CHANNEL_UPDATED_INDEX_NAME: &str = "experiment_channel_updated_at";
+const STATUS_INDEX_NAME: &str = "experiment_status";
+const KEY_INDEX_NAME: &str = "experiment_key";
+const TRASH_INDEX_NAME: &str = "experiment_trash_expiry";
+const SEARCH_INDEX_NAME: &str = "experiment_search";
+
+/// How an index of the collection differs from its definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Drift {
+    InSync,
+    /// Defined but not in the collection.
+    Missing,
+    /// In the collection with another definition.
+    Changed,
+    /// In the collection but not defined.
+    Unexpected,
+}
+
+impl fmt::Display for Drift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Drift::InSync => "in sync",
+            Drift::Missing => "missing",
+            Drift::Changed => "changed",
+            Drift::Unexpected => "unexpected",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Drift of one index and whether the reconciliation fixed it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexStatus {
+    pub name: String,
+    pub drift: Drift,
+    pub fixed: bool,
+}
+
+/// The indexes of the experiment collection.
+pub fn indexes() -> Vec<IndexModel> {
+    vec![
+        // every query is scoped to a channel.
+        index(
+            doc! {"channel_id": 1},
+            IndexOptions::builder()
+                .name(CHANNEL_INDEX_NAME.to_owned())
+                .build(),
+        ),
+        // the default listing and the export, latest created first, the id breaking the ties
+        // of the pages.
+        index(
+            doc! {"channel_id": 1, "created_at": -1, "_id": -1},
+            IndexOptions::builder()
+                .name(CHANNEL_CREATED_INDEX_NAME.to_owned())
+                .build(),
+        ),
+        // the listing by the latest changes.
+        index(
+            doc! {"channel_id": 1, "updated_at": -1, "_id": -1},
+            IndexOptions::builder()
+                .name(CHANNEL_UPDATED_INDEX_NAME.to_owned())
+                .build(),
+        ),
+        // the scheduler pages through the experiments of some statuses.
+        index(
+            doc! {"status": 1, "_id": 1},
+            IndexOptions::builder()
+                .name(STATUS_INDEX_NAME.to_owned())
+                .build(),
+        ),
+        // keys are unique per channel, the experiments created before the keys have an
+        // empty one and are left out.
+        index(
+            doc! {"channel_id": 1, "key": 1},
+            IndexOptions::builder()
+                .name(KEY_INDEX_NAME.to_owned())
+                .unique(true)
+                .partial_filter_expression(doc! {"key": {"$gt": ""}})
+                .build(),
+        ),
+        // purge the trash, only a `deleted_at` stored as a date expires.
+        index(
+            doc! {"deleted_at": 1},
+            IndexOptions::builder()
+                .name(TRASH_INDEX_NAME.to_owned())
+                .expire_after(TRASH_RETENTION)
+                .partial_filter_expression(doc! {"deleted_at": {"$type": "date"}})
+                .build(),
+        ),
+        // weighted like `service::search` ranks.
+        index(
+            doc! {
+                "name": "text",
+                "description": "text",
+                "variations.group_name": "text",
+                "variations.description": "text",
+                "tags": "text",
+            },
+            IndexOptions::builder()
+                .name(SEARCH_INDEX_NAME.to_owned())
+                .weights(doc! {
+                    "name": search::NAME_WEIGHT as i32,
+                    "description": search::DESCRIPTION_WEIGHT as i32,
+                    "variations.group_name": search::GROUP_NAME_WEIGHT as i32,
+                    "variations.description": search::VARIATION_DESCRIPTION_WEIGHT as i32,
+                    "tags": search::TAG_WEIGHT as i32,
+                })
+                .build(),
+        ),
+    ]
+}
+
+fn index(keys: bson::Document, opts: IndexOptions) -> IndexModel {
+    IndexModel::builder().keys(keys).options(opts).build()
+}
+
+fn index_name(model: &IndexModel) -> String {
+    model
+        .options
+        .as_ref()
+        .and_then(|o| o.name.clone())
+        .unwrap_or_default()
+}
+
+/// Compare the index of the collection with its definition, only the parts the definitions
+/// set are compared.
+fn drift(desired: &IndexModel, existing: Option<&IndexModel>) -> Drift {
+    let existing = match existing {
+        Some(existing) => existing,
+        None => return Drift::Missing,
+    };
+
+    let default = IndexOptions::default();
+    let want = desired.options.as_ref().unwrap_or(&default);
+    let have = existing.options.as_ref().unwrap_or(&default);
+
+    // the server keeps the fields of a text index in its weights.
+    let is_text = desired.keys.values().any(|v| v.as_str() == Some("text"));
+    let same_keys = is_text || same_key_pattern(&desired.keys, &existing.keys);
+
+    let same_options = want.unique.unwrap_or(false) == have.unique.unwrap_or(false)
+        && want.expire_after == have.expire_after
+        && want.partial_filter_expression == have.partial_filter_expression
+        && (want.weights.is_none() || same_weights(&want.weights, &have.weights));
+
+    if same_keys && same_options {
+        Drift::InSync
+    } else {
+        Drift::Changed
+    }
+}
+
+/// Same fields in the same order with the same directions, whatever the number types.
+fn same_key_pattern(a: &bson::Document, b: &bson::Document) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b.iter()).all(|((ka, va), (kb, vb))| {
+            ka == kb
+                && match (as_number(va), as_number(vb)) {
+                    (Some(x), Some(y)) => x == y,
+                    _ => va == vb,
+                }
+        })
+}
+
+fn same_weights(a: &Option<bson::Document>, b: &Option<bson::Document>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).and_then(as_number) == as_number(v))
+        }
+        (a, b) => a == b,
+    }
+}
+
+fn as_number(v: &Bson) -> Option<f64> {
+    match v {
+        Bson::Int32(n) => Some(*n as f64),
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        _ => None,
+    }
+}
+
+#[async_trait]
+impl service::Store for Repo {
+    async fn save(&self, data: &mut service::Experiment) -> Result<String> {
+        if self.outbox.is_some() {
+            let mut writes = [service::Write::Insert(data.clone())];
+            self.write_with_events(&mut writes, None, || {
+                "experiment already exists, its id or key is taken".to_owned()
+            })
+            .await?;
+            if let [service::Write::Insert(saved)] = writes {
+                *data = saved;
+            }
+            return Ok(data.id.clone().unwrap_or_default());
+        }
+
+        let given_id = data.id.is_some();
+        let document = new_document(data)?;
+        if let (true, Some(oid)) = (given_id, document._id) {
+            self.coll
+                .delete_one(trashed_filter(oid, &data.channel_id), None)
+                .await
+                .map_err(|e| service::StoreError::InternalError {
+                    message: e.to_string(),
+                })?;
+        }
+
+        let result = self.coll.insert_one(document, None).await;
+        let insert_result = result.map_err(|e| {
+            if super::is_duplicate_key(&e) {
+                service::StoreError::Conflict {
+                    message: "experiment already exists, its id or key is taken".to_owned(),
+                }
+            } else {
+                service::StoreError::InternalError {
+                    message: e.to_string(),
+                }
+            }
+        })?;
+
+        if let Bson::ObjectId(ref id) = insert_result.inserted_id {
+            data.id = Some(id.to_hex());
+            let dd = oid::ObjectId::parse_str(id.to_hex());
+            match dd {
+                Ok(o) => println!("created: {}", o),
+                Err(e) => println!("error: {}", e),
+            }
+        } else {
+            return Err(service::StoreError::InternalError {
+                message: "undefined inserted id".to_string(),
+            }
+            .into());
+        }
+
+        Ok(data.id.clone().unwrap_or_default())
+    }
+
+    async fn list(&self, channel_id: &str, query: &service::ListQuery) -> Result<service::Page> {
+        let filter = list_filter(channel_id, query)?;
+        let total = self
+            .coll
+            .count_documents(filter.clone(), None)
+            .await
+            .map_err(|e| service::StoreError::InternalError {
+                message: e.to_string(),
+            })?;
+
+        let field = sort_field(query.sort);
+        let (direction, operator) = match query.order {
+            service::SortOrder::Asc => (1, "$gt"),
+            service::SortOrder::Desc => (-1, "$lt"),
+        };
+
+        let mut page_filter = filter;
+        if let Some(ref cursor) = query.cursor {
+            let key = Bson::try_from(cursor.key.clone())?;
+            let id = oid::ObjectId::parse_str(&cursor.id).map_err(|_| {
+                service::UserError::ValidationError {
+                    message: "invalid cursor".to_owned(),
+                }
+            })?;
+            let mut conditions = match page_filter.remove("$and") {
+                Some(Bson::Array(conditions)) => conditions,
+                _ => vec![],
+            };
+            conditions.push(Bson::Document(doc! {"$or": [
+                {field: {operator: key.clone()}},
+                {field: key, "_id": {operator: id}},
+            ]}));
+            page_filter.insert("$and", conditions);
+        }
+
+        // one more than the limit tells whether there is a next page.
+        let opts = FindOptions::builder()
+            .sort(doc! {field: direction, "_id": direction})
+            .limit(query.limit + 1)
+            .projection(query.fields.as_ref().map(|p| mongo_projection(p, &[field])))
+            .build();
+
+        let internal_error = |e: mongodb::error::Error| service::StoreError::InternalError {
+            message: e.to_string(),
+        };
+        let mut docs: Vec<Document> = match query.fields {
+            None => {
+                self.coll
+                    .find(page_filter, opts)
+                    .await
+                    .map_err(internal_error)?
+                    .try_collect()
+                    .map_err(internal_error)
+                    .await?
+            }
+            Some(_) => {
+                self.coll
+                    .clone_with_type::<Projected>()
+                    .find(page_filter, opts)
+                    .await
+                    .map_err(internal_error)?
+                    .map_ok(Document::from)
+                    .try_collect()
+                    .map_err(internal_error)
+                    .await?
+            }
+        };
+
+        let mut next_cursor = None;
+        if docs.len() as i64 > query.limit {
+            docs.truncate(query.limit as usize);
+            if let Some(last) = docs.last() {
+                let key = bson::to_document(last)?
+                    .get(field)
+                    .cloned()
+                    .unwrap_or(Bson::Null);
+                next_cursor = Some(
+                    service::Cursor {
+                        key: key.into_relaxed_extjson(),
+                        id: last._id.map(|v| v.to_hex()).unwrap_or_default(),
+                        query: query.fingerprint(),
+                    }
+                    .encode(),
+                );
+            }
+        }
+
+        Ok(service::Page {
+            items: docs.into_iter().map(|d| d.into()).collect(),
+            next_cursor,
+            total,
+        })
+    }
+
+    async fn get(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.find_in_channel::<Document>(id, channel_id, None).await
+    }
+
+    async fn get_fresh(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.get(id, channel_id).await
+    }
+
+    async fn get_by_key(&self, key: &str, channel_id: &str) -> Result<service::Experiment> {
+        let doc = self
+            .coll
+            .find_one(
+                doc! {"channel_id": channel_id, "key": key, "deleted_at": Bson::Null},
+                None,
+            )
+            .await
+            .map_err(|e| service::StoreError::InternalError {
+                message: e.to_string(),
+            })?
+            .ok_or(service::StoreError::DocumentNotfound)?;
+
+        Ok(doc.into())
+    }
+
+    async fn get_projected(
+        &self,
+        id: &str,
+        channel_id: &str,
+        projection: &Projection,
+    ) -> Result<service::Experiment> {
+        let opts = FindOneOptions::builder()
+            .projection(mongo_projection(projection, &[]))
+            .build();
+
+        self.find_in_channel::<Projected>(id, channel_id, Some(opts))
+            .await
+    }
+
+    async fn update(&self, data: &mut service::Experiment) -> Result<()> {
+        self.replace(data, None).await
+    }
+
+    async fn update_if(
+        &self,
+        data: &mut service::Experiment,
+        version: service::Version,
+    ) -> Result<()> {
+        self.replace(data, Some(version)).await
+    }
+
+    async fn patch(
+        &self,
+        id: &str,
+        channel_id: &str,
+        patch: &service::Patch,
+    ) -> Result<Option<service::Experiment>> {
+        let oid = parse_id(id)?;
+        let now = bson::DateTime::now().to_chrono();
+        let (filter, update, array_filters) = patch_query(oid, channel_id, patch, now)?;
+        let opts = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .array_filters((!array_filters.is_empty()).then_some(array_filters))
+            .build();
+
+        let patched = if self.outbox.is_some() {
+            self.patch_with_event(filter, update, opts, |data| patch_event(patch, data, now))
+                .await?
+        } else {
+            self.coll
+                .find_one_and_update(filter, update, opts)
+                .await
+                .map_err(|e| service::StoreError::InternalError {
+                    message: e.to_string(),
+                })?
+        };
+
+        match patched {
+            Some(document) => Ok(Some(document.into())),
+            // the experiment is there but not in the state the patch expects.
+            None => match self.not_written(oid, channel_id, None).await? {
+                e if matches!(e.downcast_ref(), Some(service::StoreError::Stale)) => Ok(None),
+                e => Err(e),
+            },
+        }
+    }
+
+    async fn write_atomically(&self, writes: &mut [service::Write]) -> Result<bool> {
+        let client = match &self.client {
+            Some(client) => client,
+            None => return Ok(false),
+        };
+
+        let mut session =
+            client
+                .start_session(None)
+                .await
+                .map_err(|e| service::StoreError::InternalError {
+                    message: e.to_string(),
+                })?;
+        if session.start_transaction(None).await.is_err() {
+            return Ok(false);
+        }
+
+        if let Err(e) = self.write_in_session(writes, None, &mut session).await {
+            let _ = session.abort_transaction().await;
+
+            return match e.downcast_ref::<mongodb::error::Error>() {
+                Some(e) if super::is_transaction_unsupported(e) => Ok(false),
+                Some(e) if super::is_duplicate_key(e) => Err(service::StoreError::Conflict {
+                    message: e.to_string(),
+                }
+                .into()),
+                Some(e) => Err(service::StoreError::InternalError {
+                    message: e.to_string(),
+                }
+                .into()),
+                None => Err(e),
+            };
+        }
+
+        session
+            .commit_transaction()
+            .await
+            .map_err(|e| service::StoreError::InternalError {
+                message: e.to_string(),
+            })?;
+
+        Ok(true)
+    }
+
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
+        self.remove(id, channel_id, None).await
+    }
+
+    async fn delete_if(&self, id: &str, channel_id: &str, version: service::Version) -> Result<()> {
+        self.remove(id, channel_id, Some(version)).await
+    }
+
+    async fn list_by_status(
+        &self,
+        statuses: &[service::Status],
+        after: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<service::Experiment>> {
+        let includes_default = statuses.contains(&service::Status::default());
+        let statuses: Vec<Bson> = statuses
+            .iter()
+            .map(|s| bson::to_bson(&Status::from(*s)))
+            .collect::<Result<_, _>>()?;
+
+        // documents stored before the status was introduced are in the default status.
+        let mut filter = if includes_default {
+            doc! {"$or": [{"status": {"$in": statuses}}, {"status": {"$exists": false}}]}
+        } else {
+            doc! {"status": {"$in": statuses}}
+        };
+        filter.insert("deleted_at", Bson::Null);
+        if let Some(after) = after {
+            filter.insert("_id", doc! {"$gt": parse_id(&after)?});
+        }
+
+        let opts = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .limit(limit)
+            .build();
+        let cursor =
+            self.coll
+                .find(filter, opts)
+                .await
+                .map_err(|e| service::StoreError::InternalError {
+                    message: e.to_string(),
+                })?;
+
+        let docs: Vec<service::Experiment> = cursor
+            .map_ok(|d| d.into())
+            .try_collect()
+            .map_err(|e| service::StoreError::InternalError {
+                message: e.to_string(),
+            })
+            .await?;
+
+        Ok(docs)
+    }
+
+    async fn search(&self, channel_id: &str, q: &str, limit: i64) -> Result<Vec<search::Hit>> {
+        let opts = FindOptions::builder()
+            .projection(doc! {"score": {"$meta": "textScore"}})
+            .sort(doc! {"score": {"$meta": "textScore"}})
+            .limit(limit)
+            .build();
+
+        let cursor = self
+            .coll
+            .clone_with_type::<bson::Document>()
+            .find(
+                doc! {"channel_id": channel_id, "deleted_at": Bson::Null, "$text": {"$search": q}},
+                opts,
+            )
+            .await
+            .map_err(|e| service::StoreError::InternalError {
+                message: e.to_string(),
+            })?;
+
+        let docs: Vec<bson::Document> = cursor
+            .try_collect()
+            .map_err(|e| service::StoreError::InternalError {
+                message: e.to_string(),
+            })
+            .await?;
+
+        docs.into_iter()
+            .map(|mut d| {
+                let score = d
+                    .remove("score")
+                    .and_then(|s| s.as_f64())
+                    .unwrap_or_default();
+                let doc: Document = bson::from_document(d)?;
+                Ok(search::Hit {
+                    experiment: doc.into(),
+                    score,
+                })
+            })
+            .collect()
+    }
+
+    async fn tag_counts(&self, channel_id: &str) -> Result<Vec<tag::TagCount>> {
+        let pipeline = vec![
+            doc! {"$match": {"channel_id": channel_id, "deleted_at": Bson::Null}},
+            doc! {"$unwind": "$tags"},
+            doc! {"$group": {"_id": "$tags", "count": {"$sum": 1}}},
+            doc! {"$sort": {"count": -1, "_id": 1}},
+        ];
+
+        let cursor = self.coll.aggregate(pipeline, None).await.map_err(|e| {
+            service::StoreError::InternalError {
+                message: e.to_string(),
+            }
+        })?;
+
+        let docs: Vec<bson::Document> = cursor
+            .try_collect()
+            .map_err(|e| service::StoreError::InternalError {
+                message: e.to_string(),
+            })
+            .await?;
+
+        Ok(docs
+            .into_iter()
+            .map(|d| tag::TagCount {
+                tag: d.get_str("_id").unwrap_or_default().to_owned(),
+                count: d
+                    .get("count")
+                    .and_then(|c| c.as_i64().or_else(|| c.as_i32().map(i64::from)))
+                    .unwrap_or_default() as u64,
+            })
+            .collect())
+    }
+}
+
+/// Integration tests, they run against the deployment of `MONGO_TEST_URL` and are skipped
+/// when it is not set, e.g. `mongodb://localhost:27017`.
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn repo() -> Repo {
+        let url = std::env::var("MONGO_TEST_URL").expect("MONGO_TEST_URL is not set");
+        let client = mongodb::Client::with_uri_str(&url).await.unwrap();
+        let repo = Repo::new(client.database("enigma_test").collection("experiments"));
+        repo.reconcile_indexes(false).await.unwrap();
+        repo
+    }
+
+    crate::repository::conformance::store_conformance!(
+        repo(),
+        ignore = "needs a MongoDB database at MONGO_TEST_URL"
+    );
+
+    #[test]
+    fn test_mongo_projection_keeps_the_paths() {
+        let projection = Projection::parse(
+            "variations.indicator,classing.bandit.epsilon,active_interval.0,created_at.x",
+        )
+        .unwrap();
+        assert_eq!(
+            mongo_projection(&projection, &["created_at"]),
+            doc! {
+                "channel_id": 1,
+                "active_interval": 1,
+                "classing.bandit.epsilon": 1,
+                "created_at": 1,
+                "variations.indicator": 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_read_a_document_without_key() {
+        let stored = doc! {
+            "_id": oid::ObjectId::new(),
+            "schema_version": SCHEMA_VERSION,
+            "name": "before the keys",
+            "description": "",
+            "active_interval": Bson::Null,
+            "variations": [],
+            "classing": {"strategy": "", "persistent_mode": ""},
+            "owner": Bson::Null,
+            "channel_id": "channel_a",
+            "created_at": bson::DateTime::now(),
+            "updated_at": bson::DateTime::now(),
+            "deleted_at": Bson::Null,
+        };
+
+        let experiment: service::Experiment =
+            bson::from_document::<Document>(stored).unwrap().into();
+        assert_eq!(experiment.key, "");
+        assert_eq!(experiment.name, "before the keys");
+    }
+
+    #[test]
+    fn test_only_the_projected_read_has_defaults() {
+        let partial = doc! {
+            "_id": oid::ObjectId::new(),
+            "channel_id": "channel_a",
+            "variations": [{"indicator": "blue"}, {"indicator": "red"}],
+            "classing": {"bandit": {"epsilon": 0.1}},
+        };
+
+        assert!(bson::from_document::<Document>(partial.clone()).is_err());
+
+        let doc: Document = bson::from_document::<Projected>(partial).unwrap().into();
+        assert_eq!(doc.channel_id, "channel_a");
+        assert_eq!(doc.variations.len(), 2);
+        assert_eq!(doc.variations[1].indicator, "red");
+        assert!(doc.variations[1].values.is_empty());
+        assert_eq!(doc.classing.bandit.map(|b| b.epsilon), Some(0.1));
+        assert!(doc.name.is_empty());
+    }
+
+    #[test]
+    fn test_nested_timestamps_are_dates() {
+        let at = bson::DateTime::from_millis(1656400000000);
+        let kill_switch = KillSwitch {
+            scope: KillScope::Experiment,
+            reason: "broken".to_owned(),
+            triggered_by: None,
+            triggered_at: at.to_chrono(),
+        };
+        let stored = bson::to_document(&kill_switch).unwrap();
+        assert_eq!(stored.get_datetime("triggered_at"), Ok(&at));
+
+        // the milliseconds written before are still read.
+        let legacy = doc! {"changed_at": 1656400000000_i64, "weights": {"blue": 100}};
+        let change: WeightChange = bson::from_document(legacy).unwrap();
+        assert_eq!(change.changed_at, at.to_chrono());
+    }
 }
@@ -0,0 +1,174 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::doc,
+    bson::oid,
+    options::{FindOptions, IndexOptions},
+    Collection, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use super::is_duplicate_key;
+use crate::service::experiment as experiment_service;
+use crate::service::history as service;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Document {
+    pub _id: Option<oid::ObjectId>,
+    pub experiment_id: String,
+    pub channel_id: String,
+    pub version: i64,
+    #[serde(flatten)]
+    pub kind: service::Kind,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub occurred_at: DateTime<Utc>,
+}
+
+impl From<service::Change> for Document {
+    fn from(change: service::Change) -> Self {
+        Self {
+            _id: None,
+            experiment_id: change.experiment_id,
+            channel_id: change.channel_id,
+            version: change.version,
+            kind: change.kind,
+            occurred_at: change.occurred_at,
+        }
+    }
+}
+
+impl From<Document> for service::Change {
+    fn from(doc: Document) -> Self {
+        Self {
+            experiment_id: doc.experiment_id,
+            channel_id: doc.channel_id,
+            version: doc.version,
+            kind: doc.kind,
+            occurred_at: doc.occurred_at,
+        }
+    }
+}
+
+/// Log of the experiment changes in a mongo collection, the documents are only inserted.
+pub struct Repo {
+    coll: Collection<Document>,
+}
+
+impl Repo {
+    pub fn new(coll: Collection<Document>) -> Self {
+        Self { coll }
+    }
+
+    /// Create the unique index which keeps a single change per version of an experiment.
+    pub async fn create_indexes(&self) -> Result<()> {
+        let index = IndexModel::builder()
+            .keys(doc! {"experiment_id": 1, "version": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+
+        self.coll.create_index(index, None).await?;
+        Ok(())
+    }
+}
+
+fn internal_error(e: mongodb::error::Error) -> experiment_service::StoreError {
+    experiment_service::StoreError::InternalError {
+        message: e.to_string(),
+    }
+}
+
+#[async_trait]
+impl service::Log for Repo {
+    async fn append(&self, change: &service::Change) -> Result<()> {
+        match self
+            .coll
+            .insert_one(Document::from(change.clone()), None)
+            .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) if is_duplicate_key(&e) => Err(experiment_service::StoreError::Conflict {
+                message: format!(
+                    "version {} of experiment {} is taken",
+                    change.version, change.experiment_id
+                ),
+            }
+            .into()),
+            Err(e) => Err(internal_error(e).into()),
+        }
+    }
+
+    async fn changes_of(&self, experiment_id: &str) -> Result<Vec<service::Change>> {
+        let opts = FindOptions::builder().sort(doc! {"version": 1}).build();
+        let docs: Vec<Document> = self
+            .coll
+            .find(doc! {"experiment_id": experiment_id}, opts)
+            .await
+            .map_err(internal_error)?
+            .try_collect()
+            .await
+            .map_err(internal_error)?;
+
+        Ok(docs.into_iter().map(service::Change::from).collect())
+    }
+
+    async fn all(&self) -> Result<Vec<service::Change>> {
+        let opts = FindOptions::builder()
+            .sort(doc! {"experiment_id": 1, "version": 1})
+            .build();
+        let docs: Vec<Document> = self
+            .coll
+            .find(None, opts)
+            .await
+            .map_err(internal_error)?
+            .try_collect()
+            .await
+            .map_err(internal_error)?;
+
+        Ok(docs.into_iter().map(service::Change::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{Experiment, Status};
+
+    #[test]
+    fn test_document_round_trip() {
+        let occurred_at = DateTime::parse_from_rfc3339("2022-07-01T10:00:00.123Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let changes = [
+            service::Kind::Created(Box::new(Experiment {
+                name: "checkout".to_owned(),
+                created_at: Some(occurred_at),
+                ..Default::default()
+            })),
+            service::Kind::StatusChanged {
+                from: Status::Scheduled,
+                to: Status::Running,
+            },
+            service::Kind::Deleted,
+        ];
+
+        for (i, kind) in changes.into_iter().enumerate() {
+            let change = service::Change {
+                experiment_id: "62bb13dfea2b3ea78771e305".to_owned(),
+                channel_id: "channel_a".to_owned(),
+                version: i as i64 + 1,
+                kind,
+                occurred_at,
+            };
+
+            let stored = bson::to_document(&Document::from(change.clone())).unwrap();
+            assert!(stored.get_datetime("occurred_at").is_ok());
+            let loaded: Document = bson::from_document(stored).unwrap();
+            assert_eq!(
+                serde_json::to_value(service::Change::from(loaded)).unwrap(),
+                serde_json::to_value(change).unwrap()
+            );
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    bson::doc,
+    bson::oid,
+    options::{DropIndexOptions, IndexOptions},
+    Collection, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use super::{is_duplicate_key, is_index_conflict};
+use crate::service::experiment as experiment_service;
+use crate::service::idempotency as service;
+
+const EXPIRY_INDEX_NAME: &str = "idempotency_expiry";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Document {
+    pub _id: Option<oid::ObjectId>,
+    pub channel_id: String,
+    pub key: String,
+    pub fingerprint: String,
+    pub response: Option<service::Response>,
+    // a bson date so the expiry index removes the document.
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<service::Record> for Document {
+    fn from(data: service::Record) -> Self {
+        Self {
+            _id: None,
+            channel_id: data.channel_id,
+            key: data.key,
+            fingerprint: data.fingerprint,
+            response: data.response,
+            created_at: data.created_at,
+        }
+    }
+}
+
+impl From<Document> for service::Record {
+    fn from(doc: Document) -> Self {
+        Self {
+            channel_id: doc.channel_id,
+            key: doc.key,
+            fingerprint: doc.fingerprint,
+            response: doc.response,
+            created_at: doc.created_at,
+        }
+    }
+}
+
+pub struct Repo {
+    coll: Collection<Document>,
+    window: Duration,
+}
+
+impl Repo {
+    pub fn new(coll: Collection<Document>, window: Duration) -> Self {
+        Self { coll, window }
+    }
+
+    /// Create the unique index of the keys and the index expiring the records after the
+    /// window, the expiry index is recreated when the window has changed.
+    pub async fn create_indexes(&self) -> Result<()> {
+        let unique = IndexModel::builder()
+            .keys(doc! {"channel_id": 1, "key": 1})
+            .options(IndexOptions::builder().unique(true).build())
+            .build();
+        self.coll.create_index(unique, None).await?;
+
+        let expiry = IndexModel::builder()
+            .keys(doc! {"created_at": 1})
+            .options(
+                IndexOptions::builder()
+                    .name(EXPIRY_INDEX_NAME.to_owned())
+                    .expire_after(self.window)
+                    .build(),
+            )
+            .build();
+        match self.coll.create_index(expiry.clone(), None).await {
+            Err(e) if is_index_conflict(&e) => {
+                self.coll
+                    .drop_index(EXPIRY_INDEX_NAME, DropIndexOptions::default())
+                    .await?;
+                self.coll.create_index(expiry, None).await?;
+            }
+            result => {
+                result?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn expired_at(&self) -> Result<bson::DateTime> {
+        Ok(bson::DateTime::from_chrono(
+            Utc::now() - chrono::Duration::from_std(self.window)?,
+        ))
+    }
+}
+
+fn internal_error(e: mongodb::error::Error) -> experiment_service::StoreError {
+    experiment_service::StoreError::InternalError {
+        message: e.to_string(),
+    }
+}
+
+#[async_trait]
+impl service::Store for Repo {
+    async fn reserve(&self, record: &service::Record) -> Result<Option<service::Record>> {
+        // the expiry index runs about every minute, an expired record may still be there.
+        self.coll
+            .delete_one(
+                doc! {
+                    "channel_id": &record.channel_id,
+                    "key": &record.key,
+                    "created_at": {"$lte": self.expired_at()?},
+                },
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+
+        let mut document = Document::from(record.clone());
+        document._id = Some(oid::ObjectId::new());
+
+        match self.coll.insert_one(document, None).await {
+            Ok(_) => Ok(None),
+            Err(e) if is_duplicate_key(&e) => {
+                let existing = self
+                    .coll
+                    .find_one(
+                        doc! {"channel_id": &record.channel_id, "key": &record.key},
+                        None,
+                    )
+                    .await
+                    .map_err(internal_error)?;
+                Ok(existing.map(|d| d.into()))
+            }
+            Err(e) => Err(internal_error(e).into()),
+        }
+    }
+
+    async fn complete(
+        &self,
+        channel_id: &str,
+        key: &str,
+        response: &service::Response,
+    ) -> Result<()> {
+        self.coll
+            .update_one(
+                doc! {"channel_id": channel_id, "key": key},
+                doc! {"$set": {"response": bson::to_bson(response)?}},
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+
+    async fn release(&self, channel_id: &str, key: &str) -> Result<()> {
+        self.coll
+            .delete_one(doc! {"channel_id": channel_id, "key": key}, None)
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+}
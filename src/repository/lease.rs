@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mongodb::{
+    bson::doc,
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+
+use super::is_duplicate_key;
+use crate::service::experiment as experiment_service;
+use crate::service::scheduler as service;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Document {
+    pub _id: String,
+    pub holder: String,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub expires_at: DateTime<Utc>,
+}
+
+pub struct Repo {
+    coll: Collection<Document>,
+}
+
+impl Repo {
+    pub fn new(coll: Collection<Document>) -> Self {
+        Self { coll }
+    }
+}
+
+#[async_trait]
+impl service::Lease for Repo {
+    async fn acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<bool> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::from_std(ttl)?;
+
+        // match the lease when we already hold it or when it has expired, otherwise the
+        // upsert collides with the existing `_id` and another replica keeps the lease.
+        let filter = doc! {
+            "_id": name,
+            "$or": [
+                {"holder": holder},
+                {"expires_at": {"$lte": bson::DateTime::from_chrono(now)}},
+            ],
+        };
+        let update = doc! {
+            "$set": {"holder": holder, "expires_at": bson::DateTime::from_chrono(expires_at)},
+        };
+        let opts = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+
+        match self.coll.find_one_and_update(filter, update, opts).await {
+            Ok(_) => Ok(true),
+            Err(e) if is_duplicate_key(&e) => Ok(false),
+            Err(e) => Err(experiment_service::StoreError::InternalError {
+                message: e.to_string(),
+            }
+            .into()),
+        }
+    }
+}
@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use mongodb::bson::oid;
+
+use crate::service::experiment as service;
+use crate::service::projection::Projection;
+use crate::service::search;
+use crate::service::tag;
+
+/// Experiment store kept in the process memory, for the development and the tests without
+/// a database. It behaves like `repository::experiment::Repo`: ids are object ids, the
+/// experiments are scoped to their channel and keys are unique per channel.
+#[derive(Debug, Default)]
+pub struct Repo {
+    experiments: Mutex<HashMap<String, service::Experiment>>,
+}
+
+impl Repo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check and stamp the experiment like `save` does, without keeping it.
+    pub(crate) fn prepare_save(&self, data: &mut service::Experiment) -> Result<()> {
+        let experiments = self.experiments.lock().unwrap();
+        data.id = Some(stamp_new(&experiments, data)?);
+        Ok(())
+    }
+
+    /// Check and stamp the experiment like `update` does without keeping it, returns the
+    /// stored one.
+    pub(crate) fn prepare_update(
+        &self,
+        data: &mut service::Experiment,
+        expected: Option<service::Version>,
+    ) -> Result<service::Experiment> {
+        let experiments = self.experiments.lock().unwrap();
+        let id = stamp_replacement(&experiments, data, expected)?;
+        Ok(experiments[&id].clone())
+    }
+
+    /// Check the experiment can be deleted like `delete` does, returns its id.
+    pub(crate) fn prepare_delete(
+        &self,
+        id: &str,
+        channel_id: &str,
+        expected: Option<service::Version>,
+    ) -> Result<String> {
+        let experiments = self.experiments.lock().unwrap();
+        removable_id(&experiments, id, channel_id, expected)
+    }
+
+    /// Patch the experiment like `patch` does without keeping it, returns the stored one and
+    /// the patched one.
+    pub(crate) fn prepare_patch(
+        &self,
+        id: &str,
+        channel_id: &str,
+        patch: &service::Patch,
+    ) -> Result<Option<(service::Experiment, service::Experiment)>> {
+        let experiments = self.experiments.lock().unwrap();
+        let id = removable_id(&experiments, id, channel_id, None)?;
+        let current = &experiments[&id];
+        Ok(patched(current, patch).map(|data| (current.clone(), data)))
+    }
+
+    /// Keep the experiment as it is, replacing the one of the same id.
+    pub(crate) fn put(&self, data: service::Experiment) {
+        let mut experiments = self.experiments.lock().unwrap();
+        experiments.insert(data.id.clone().unwrap_or_default(), data);
+    }
+
+    pub(crate) fn forget(&self, id: &str) {
+        self.experiments.lock().unwrap().remove(id);
+    }
+
+    /// Replace every experiment with the given ones.
+    pub(crate) fn restore(&self, data: impl IntoIterator<Item = service::Experiment>) {
+        let mut experiments = self.experiments.lock().unwrap();
+        *experiments = data
+            .into_iter()
+            .map(|e| (e.id.clone().unwrap_or_default(), e))
+            .collect();
+    }
+}
+
+fn parse_id(id: &str) -> Result<oid::ObjectId> {
+    oid::ObjectId::parse_str(id).map_err(|e| {
+        service::StoreError::InvalidInput {
+            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
+        }
+        .into()
+    })
+}
+
+/// Whether another experiment of the channel holds the key.
+fn is_key_taken(
+    experiments: &HashMap<String, service::Experiment>,
+    data: &service::Experiment,
+) -> bool {
+    !data.key.is_empty()
+        && experiments
+            .values()
+            .any(|e| e.channel_id == data.channel_id && e.key == data.key && e.id != data.id)
+}
+
+fn insert(
+    experiments: &mut HashMap<String, service::Experiment>,
+    data: &mut service::Experiment,
+) -> Result<()> {
+    let id = stamp_new(experiments, data)?;
+    experiments.insert(id, data.clone());
+    Ok(())
+}
+
+/// Check the new experiment can be inserted and stamp it, returns its id.
+fn stamp_new(
+    experiments: &HashMap<String, service::Experiment>,
+    data: &mut service::Experiment,
+) -> Result<String> {
+    // an experiment brought from elsewhere, e.g. an import, keeps its id.
+    let id = match &data.id {
+        Some(id) => parse_id(id)?.to_hex(),
+        None => oid::ObjectId::new().to_hex(),
+    };
+    if experiments.contains_key(&id) || is_key_taken(experiments, data) {
+        return Err(service::StoreError::Conflict {
+            message: "experiment already exists, its id or key is taken".to_owned(),
+        }
+        .into());
+    }
+
+    let now = Utc::now();
+    data.id = Some(id.clone());
+    data.updated_at = Some(now);
+    if data.created_at.is_none() {
+        data.created_at = Some(now);
+    }
+    Ok(id)
+}
+
+fn replace(
+    experiments: &mut HashMap<String, service::Experiment>,
+    data: &mut service::Experiment,
+    expected: Option<service::Version>,
+) -> Result<()> {
+    let id = stamp_replacement(experiments, data, expected)?;
+    experiments.insert(id, data.clone());
+    Ok(())
+}
+
+/// Check the experiment can replace the stored one, at the `expected` version if any, and
+/// stamp it, returns its id.
+fn stamp_replacement(
+    experiments: &HashMap<String, service::Experiment>,
+    data: &mut service::Experiment,
+    expected: Option<service::Version>,
+) -> Result<String> {
+    let id = parse_id(&data.id.clone().unwrap_or_default())?.to_hex();
+    let current = match experiments.get(&id) {
+        Some(current) if current.channel_id == data.channel_id => current,
+        _ => return Err(service::StoreError::DocumentNotfound.into()),
+    };
+    check_version(current, expected)?;
+    if is_key_taken(experiments, data) {
+        return Err(service::StoreError::Conflict {
+            message: format!("key `{}` is taken", data.key),
+        }
+        .into());
+    }
+
+    data.updated_at = Some(service::Version::of(current).next());
+    Ok(id)
+}
+
+/// The experiment patched and stamped, none when the patch does not apply to it.
+fn patched(current: &service::Experiment, patch: &service::Patch) -> Option<service::Experiment> {
+    let mut data = current.clone();
+    if !patch.apply(&mut data) {
+        return None;
+    }
+    data.updated_at = Some(service::Version::of(current).next());
+    Some(data)
+}
+
+fn remove(
+    experiments: &mut HashMap<String, service::Experiment>,
+    id: &str,
+    channel_id: &str,
+    expected: Option<service::Version>,
+) -> Result<()> {
+    let id = removable_id(experiments, id, channel_id, expected)?;
+    experiments.remove(&id);
+    Ok(())
+}
+
+/// Check the experiment of the channel can be removed, at the `expected` version if any,
+/// returns its id.
+fn removable_id(
+    experiments: &HashMap<String, service::Experiment>,
+    id: &str,
+    channel_id: &str,
+    expected: Option<service::Version>,
+) -> Result<String> {
+    let id = parse_id(id)?.to_hex();
+    match experiments.get(&id) {
+        Some(current) if current.channel_id == channel_id => {
+            check_version(current, expected)?;
+            Ok(id)
+        }
+        _ => Err(service::StoreError::DocumentNotfound.into()),
+    }
+}
+
+fn check_version(current: &service::Experiment, expected: Option<service::Version>) -> Result<()> {
+    match expected {
+        Some(version) if version != service::Version::of(current) => {
+            Err(service::StoreError::Stale.into())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Whether the experiment matches the filters of the query, the pagination aside.
+fn matches(experiment: &service::Experiment, query: &service::ListQuery) -> bool {
+    if query.status.is_some_and(|s| s != experiment.status) {
+        return false;
+    }
+    if let Some(ref owner_id) = query.owner_id {
+        let owner = experiment.owner.as_ref().and_then(|o| o.get("id"));
+        if owner.and_then(|id| id.as_str()) != Some(owner_id) {
+            return false;
+        }
+    }
+    if let Some(ref tag) = query.tag {
+        if !experiment.tags.contains(tag) {
+            return false;
+        }
+    }
+    if let Some(ref prefix) = query.name_prefix {
+        if !experiment.name.starts_with(prefix.as_str()) {
+            return false;
+        }
+    }
+    if let Some(at) = query.active_at {
+        if let Some(service::Interval(start, end)) = &experiment.active_interval {
+            if start.is_some_and(|s| s > at) || end.is_some_and(|e| e <= at) {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Sort key of the experiment, the timestamps are formatted so they sort as strings.
+fn sort_key(experiment: &service::Experiment, sort: service::SortField) -> Option<String> {
+    let format =
+        |dt: &Option<DateTime<Utc>>| dt.map(|dt| dt.to_rfc3339_opts(SecondsFormat::Nanos, true));
+    match sort {
+        service::SortField::CreatedAt => format(&experiment.created_at),
+        service::SortField::UpdatedAt => format(&experiment.updated_at),
+        service::SortField::Name => Some(experiment.name.clone()),
+    }
+}
+
+#[async_trait]
+impl service::Store for Repo {
+    async fn save(&self, data: &mut service::Experiment) -> Result<String> {
+        let mut experiments = self.experiments.lock().unwrap();
+        insert(&mut experiments, data)?;
+
+        Ok(data.id.clone().unwrap_or_default())
+    }
+
+    async fn list(&self, channel_id: &str, query: &service::ListQuery) -> Result<service::Page> {
+        let experiments = self.experiments.lock().unwrap();
+
+        let mut items: Vec<(Option<String>, String, &service::Experiment)> = experiments
+            .iter()
+            .filter(|(_, e)| e.channel_id == channel_id && matches(e, query))
+            .map(|(id, e)| (sort_key(e, query.sort), id.clone(), e))
+            .collect();
+        let total = items.len() as u64;
+
+        items.sort_by(|a, b| (&a.0, &a.1).cmp(&(&b.0, &b.1)));
+        if query.order == service::SortOrder::Desc {
+            items.reverse();
+        }
+
+        if let Some(ref cursor) = query.cursor {
+            let key: Option<String> = serde_json::from_value(cursor.key.clone()).map_err(|_| {
+                service::UserError::ValidationError {
+                    message: "invalid cursor".to_owned(),
+                }
+            })?;
+            let after = (key, cursor.id.clone());
+            items.retain(|(k, id, _)| match query.order {
+                service::SortOrder::Asc => (k, id) > (&after.0, &after.1),
+                service::SortOrder::Desc => (k, id) < (&after.0, &after.1),
+            });
+        }
+
+        let mut next_cursor = None;
+        if items.len() as i64 > query.limit {
+            items.truncate(query.limit.max(0) as usize);
+            if let Some((key, id, _)) = items.last() {
+                next_cursor = Some(
+                    service::Cursor {
+                        key: serde_json::to_value(key)?,
+                        id: id.clone(),
+                        query: query.fingerprint(),
+                    }
+                    .encode(),
+                );
+            }
+        }
+
+        Ok(service::Page {
+            items: items.into_iter().map(|(_, _, e)| e.clone()).collect(),
+            next_cursor,
+            total,
+        })
+    }
+
+    async fn get(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        let id = parse_id(id)?.to_hex();
+        let experiments = self.experiments.lock().unwrap();
+
+        let experiment = experiments
+            .get(&id)
+            .ok_or(service::StoreError::DocumentNotfound)?;
+        if experiment.channel_id != channel_id {
+            return Err(service::StoreError::UnauthorizedAccess.into());
+        }
+
+        Ok(experiment.clone())
+    }
+
+    async fn get_fresh(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.get(id, channel_id).await
+    }
+
+    async fn get_by_key(&self, key: &str, channel_id: &str) -> Result<service::Experiment> {
+        let experiments = self.experiments.lock().unwrap();
+
+        experiments
+            .values()
+            .find(|e| e.channel_id == channel_id && e.key == key)
+            .cloned()
+            .ok_or_else(|| service::StoreError::DocumentNotfound.into())
+    }
+
+    async fn get_projected(
+        &self,
+        id: &str,
+        channel_id: &str,
+        _projection: &Projection,
+    ) -> Result<service::Experiment> {
+        // every field is in memory already.
+        self.get(id, channel_id).await
+    }
+
+    async fn update(&self, data: &mut service::Experiment) -> Result<()> {
+        let mut experiments = self.experiments.lock().unwrap();
+        replace(&mut experiments, data, None)
+    }
+
+    async fn update_if(
+        &self,
+        data: &mut service::Experiment,
+        version: service::Version,
+    ) -> Result<()> {
+        let mut experiments = self.experiments.lock().unwrap();
+        replace(&mut experiments, data, Some(version))
+    }
+
+    async fn patch(
+        &self,
+        id: &str,
+        channel_id: &str,
+        patch: &service::Patch,
+    ) -> Result<Option<service::Experiment>> {
+        let mut experiments = self.experiments.lock().unwrap();
+        let id = removable_id(&experiments, id, channel_id, None)?;
+
+        let data = patched(&experiments[&id], patch);
+        if let Some(ref data) = data {
+            experiments.insert(id, data.clone());
+        }
+        Ok(data)
+    }
+
+    async fn write_atomically(&self, writes: &mut [service::Write]) -> Result<bool> {
+        let mut experiments = self.experiments.lock().unwrap();
+
+        // the writes apply to a copy which replaces the experiments once they all succeed.
+        let mut staged = experiments.clone();
+        for write in writes.iter_mut() {
+            match write {
+                service::Write::Insert(data) => insert(&mut staged, data)?,
+                service::Write::Replace(data) => replace(&mut staged, data, None)?,
+                service::Write::Delete { id, channel_id } => {
+                    remove(&mut staged, id, channel_id, None)?
+                }
+            }
+        }
+        *experiments = staged;
+
+        Ok(true)
+    }
+
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
+        let mut experiments = self.experiments.lock().unwrap();
+        remove(&mut experiments, id, channel_id, None)
+    }
+
+    async fn delete_if(&self, id: &str, channel_id: &str, version: service::Version) -> Result<()> {
+        let mut experiments = self.experiments.lock().unwrap();
+        remove(&mut experiments, id, channel_id, Some(version))
+    }
+
+    async fn list_by_status(
+        &self,
+        statuses: &[service::Status],
+        after: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<service::Experiment>> {
+        let experiments = self.experiments.lock().unwrap();
+
+        let mut matching: Vec<&service::Experiment> = experiments
+            .iter()
+            .filter(|(id, e)| statuses.contains(&e.status) && Some(*id) > after.as_ref())
+            .map(|(_, e)| e)
+            .collect();
+        matching.sort_by(|a, b| a.id.cmp(&b.id));
+
+        Ok(matching
+            .into_iter()
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn search(&self, channel_id: &str, q: &str, limit: i64) -> Result<Vec<search::Hit>> {
+        let experiments: Vec<service::Experiment> = {
+            let experiments = self.experiments.lock().unwrap();
+            experiments
+                .values()
+                .filter(|e| e.channel_id == channel_id)
+                .cloned()
+                .collect()
+        };
+
+        Ok(search::rank(experiments, q, limit))
+    }
+
+    async fn tag_counts(&self, channel_id: &str) -> Result<Vec<tag::TagCount>> {
+        let experiments = self.experiments.lock().unwrap();
+
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for experiment in experiments.values().filter(|e| e.channel_id == channel_id) {
+            for tag in experiment.tags.iter() {
+                *counts.entry(tag).or_default() += 1;
+            }
+        }
+
+        let mut counts: Vec<tag::TagCount> = counts
+            .into_iter()
+            .map(|(tag, count)| tag::TagCount {
+                tag: tag.to_owned(),
+                count,
+            })
+            .collect();
+        counts.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+
+        Ok(counts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::repository::conformance::store_conformance!(async { Repo::new() });
+}
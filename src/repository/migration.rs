@@ -0,0 +1,437 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use bson::{doc, Bson};
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{options::FindOptions, Collection};
+use serde::{Deserialize, Serialize};
+
+use super::experiment::{read_timestamp, SCHEMA_VERSION};
+use super::is_duplicate_key;
+
+/// A change of the stored shape. Running it again, e.g. after it was interrupted, must be
+/// harmless: it only touches the documents of an older `schema_version` and stamps them.
+#[async_trait]
+pub trait Migration {
+    /// The `schema_version` of the documents it leaves, the migrations run in its order.
+    fn version(&self) -> i32;
+    fn name(&self) -> &'static str;
+    /// Apply the migration, returns the number of documents changed.
+    async fn up(&self) -> Result<u64>;
+}
+
+/// Defined struct represents an applied migration.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Record {
+    #[serde(rename = "_id")]
+    pub version: i32,
+    pub name: String,
+    pub modified: u64,
+    #[serde(with = "bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    pub applied_at: DateTime<Utc>,
+}
+
+/// Run the migrations not applied yet and record them in the migrations collection.
+pub struct Migrator {
+    records: Collection<Record>,
+    migrations: Vec<Box<dyn Migration + Send + Sync>>,
+}
+
+impl Migrator {
+    pub fn new(
+        records: Collection<Record>,
+        mut migrations: Vec<Box<dyn Migration + Send + Sync>>,
+    ) -> Self {
+        migrations.sort_by_key(|m| m.version());
+        Self {
+            records,
+            migrations,
+        }
+    }
+
+    /// Version and name of the migrations not applied yet, in the order they run.
+    pub async fn pending(&self) -> Result<Vec<(i32, &'static str)>> {
+        let applied = self.applied_versions().await?;
+        Ok(self
+            .migrations
+            .iter()
+            .filter(|m| !applied.contains(&m.version()))
+            .map(|m| (m.version(), m.name()))
+            .collect())
+    }
+
+    /// Apply the pending migrations in order, stops at the first failure.
+    pub async fn run(&self) -> Result<Vec<Record>> {
+        let applied = self.applied_versions().await?;
+
+        let mut records = vec![];
+        for migration in self.migrations.iter() {
+            if applied.contains(&migration.version()) {
+                continue;
+            }
+
+            let modified = migration.up().await.map_err(|e| {
+                anyhow::anyhow!(
+                    "migration {} {} failed: {}",
+                    migration.version(),
+                    migration.name(),
+                    e
+                )
+            })?;
+            let record = Record {
+                version: migration.version(),
+                name: migration.name().to_owned(),
+                modified,
+                applied_at: Utc::now(),
+            };
+
+            // another instance ran it at the same time, which is harmless.
+            match self.records.insert_one(&record, None).await {
+                Err(e) if is_duplicate_key(&e) => {}
+                result => {
+                    result?;
+                }
+            }
+            records.push(record);
+        }
+
+        Ok(records)
+    }
+
+    async fn applied_versions(&self) -> Result<HashSet<i32>> {
+        let records: Vec<Record> = self.records.find(None, None).await?.try_collect().await?;
+        Ok(records.into_iter().map(|r| r.version).collect())
+    }
+}
+
+/// The migrations of the experiment collection, the last one leaves `SCHEMA_VERSION`.
+pub fn experiment_migrations(
+    coll: Collection<bson::Document>,
+) -> Vec<Box<dyn Migration + Send + Sync>> {
+    let migrations: Vec<Box<dyn Migration + Send + Sync>> = vec![
+        Box::new(TimestampsAsDates { coll: coll.clone() }),
+        Box::new(DropRedundantId { coll: coll.clone() }),
+        Box::new(NestedTimestampsAsDates { coll }),
+    ];
+    debug_assert_eq!(migrations.last().map(|m| m.version()), Some(SCHEMA_VERSION));
+    migrations
+}
+
+/// Filter of the documents older than the version, the ones before the versioning have none.
+fn older_than(version: i32) -> bson::Document {
+    doc! {"schema_version": {"$not": {"$gte": version}}}
+}
+
+/// Store `created_at`, `updated_at`, `deleted_at` and the bounds of `active_interval` as
+/// dates, they were milliseconds for the first and RFC 3339 strings for the others.
+struct TimestampsAsDates {
+    coll: Collection<bson::Document>,
+}
+
+#[async_trait]
+impl Migration for TimestampsAsDates {
+    fn version(&self) -> i32 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "timestamps_as_dates"
+    }
+
+    async fn up(&self) -> Result<u64> {
+        let opts = FindOptions::builder()
+            .projection(
+                doc! {"created_at": 1, "updated_at": 1, "deleted_at": 1, "active_interval": 1},
+            )
+            .build();
+        let mut cursor = self.coll.find(older_than(self.version()), opts).await?;
+
+        let mut modified = 0;
+        while let Some(document) = cursor.try_next().await? {
+            let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+            let mut set = timestamps_as_dates(&document)?;
+            set.insert("schema_version", self.version());
+
+            // a document rewritten meanwhile is in the new shape already.
+            let filter = doc! {"$and": [{"_id": id}, older_than(self.version())]};
+            let result = self
+                .coll
+                .update_one(filter, doc! {"$set": set}, None)
+                .await?;
+            modified += result.modified_count;
+        }
+
+        Ok(modified)
+    }
+}
+
+/// The timestamp as a date, whatever shape it was stored in.
+fn as_date(v: &Bson) -> Result<Bson> {
+    Ok(read_timestamp(v)
+        .map_err(anyhow::Error::msg)?
+        .map(|dt| Bson::DateTime(bson::DateTime::from_chrono(dt)))
+        .unwrap_or(Bson::Null))
+}
+
+/// The timestamps of the document as dates, the fields it does not have are left out.
+fn timestamps_as_dates(document: &bson::Document) -> Result<bson::Document> {
+    let mut set = bson::Document::new();
+    for field in ["created_at", "updated_at", "deleted_at"] {
+        if let Some(v) = document.get(field) {
+            set.insert(field, as_date(v)?);
+        }
+    }
+    if let Some(Bson::Array(bounds)) = document.get("active_interval") {
+        let bounds = bounds.iter().map(as_date).collect::<Result<Vec<_>>>()?;
+        set.insert("active_interval", bounds);
+    }
+
+    Ok(set)
+}
+
+/// Remove the `id` field kept alongside `_id`.
+struct DropRedundantId {
+    coll: Collection<bson::Document>,
+}
+
+#[async_trait]
+impl Migration for DropRedundantId {
+    fn version(&self) -> i32 {
+        2
+    }
+
+    fn name(&self) -> &'static str {
+        "drop_redundant_id"
+    }
+
+    async fn up(&self) -> Result<u64> {
+        let result = self
+            .coll
+            .update_many(
+                older_than(self.version()),
+                doc! {"$unset": {"id": ""}, "$set": {"schema_version": self.version()}},
+                None,
+            )
+            .await?;
+        Ok(result.modified_count)
+    }
+}
+
+/// Store the timestamps of the kill switch and the bandit as dates, they were milliseconds.
+struct NestedTimestampsAsDates {
+    coll: Collection<bson::Document>,
+}
+
+#[async_trait]
+impl Migration for NestedTimestampsAsDates {
+    fn version(&self) -> i32 {
+        3
+    }
+
+    fn name(&self) -> &'static str {
+        "nested_timestamps_as_dates"
+    }
+
+    async fn up(&self) -> Result<u64> {
+        let opts = FindOptions::builder()
+            .projection(doc! {
+                "kill_switch.triggered_at": 1,
+                "classing.bandit.recomputed_at": 1,
+                "classing.bandit.history.changed_at": 1,
+            })
+            .build();
+        let mut cursor = self.coll.find(older_than(self.version()), opts).await?;
+
+        let mut modified = 0;
+        while let Some(document) = cursor.try_next().await? {
+            let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+            let mut set = nested_timestamps_as_dates(&document)?;
+            set.insert("schema_version", self.version());
+
+            let filter = doc! {"$and": [{"_id": id}, older_than(self.version())]};
+            let result = self
+                .coll
+                .update_one(filter, doc! {"$set": set}, None)
+                .await?;
+            modified += result.modified_count;
+        }
+
+        Ok(modified)
+    }
+}
+
+/// The timestamps of the kill switch and the bandit of the document as dates, by their path
+/// so the changes of the history pushed meanwhile are kept.
+fn nested_timestamps_as_dates(document: &bson::Document) -> Result<bson::Document> {
+    let mut set = bson::Document::new();
+    if let Some(v) = document
+        .get_document("kill_switch")
+        .ok()
+        .and_then(|k| k.get("triggered_at"))
+    {
+        set.insert("kill_switch.triggered_at", as_date(v)?);
+    }
+
+    let bandit = document
+        .get_document("classing")
+        .and_then(|c| c.get_document("bandit"));
+    if let Ok(bandit) = bandit {
+        if let Some(v) = bandit.get("recomputed_at") {
+            set.insert("classing.bandit.recomputed_at", as_date(v)?);
+        }
+        for (i, change) in bandit
+            .get_array("history")
+            .into_iter()
+            .flatten()
+            .enumerate()
+        {
+            if let Some(v) = change.as_document().and_then(|c| c.get("changed_at")) {
+                set.insert(
+                    format!("classing.bandit.history.{}.changed_at", i),
+                    as_date(v)?,
+                );
+            }
+        }
+    }
+
+    Ok(set)
+}
+
+/// The tests of `run` run against the deployment of `MONGO_TEST_URL` and are skipped when it
+/// is not set.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::oid;
+
+    fn date(ms: i64) -> Bson {
+        Bson::DateTime(bson::DateTime::from_millis(ms))
+    }
+
+    #[test]
+    fn test_timestamps_as_dates_from_milliseconds() {
+        let document = doc! {
+            "created_at": 1656400000000_i64,
+            "updated_at": 1656400001000_i64,
+            "deleted_at": Bson::Null,
+            "active_interval": [1656400000000_i64, "2022-07-01T00:00:00Z"],
+        };
+        assert_eq!(
+            timestamps_as_dates(&document).unwrap(),
+            doc! {
+                "created_at": date(1656400000000),
+                "updated_at": date(1656400001000),
+                "deleted_at": Bson::Null,
+                "active_interval": [date(1656400000000), date(1656633600000)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_timestamps_as_dates_keeps_the_dates() {
+        let document = doc! {
+            "created_at": date(1656400000000),
+            "updated_at": date(1656400001000),
+            "active_interval": [date(1656400000000), Bson::Null],
+        };
+        assert_eq!(timestamps_as_dates(&document).unwrap(), document);
+    }
+
+    #[test]
+    fn test_timestamps_as_dates_leaves_out_the_missing_fields() {
+        assert_eq!(timestamps_as_dates(&doc! {}).unwrap(), doc! {});
+        assert_eq!(
+            timestamps_as_dates(&doc! {"name": "checkout", "created_at": 1656400000000_i64})
+                .unwrap(),
+            doc! {"created_at": date(1656400000000)}
+        );
+        assert!(timestamps_as_dates(&doc! {"created_at": true}).is_err());
+    }
+
+    #[test]
+    fn test_nested_timestamps_as_dates() {
+        let document = doc! {
+            "kill_switch": {"triggered_at": 1656400000000_i64},
+            "classing": {"bandit": {
+                "recomputed_at": date(1656400001000),
+                "history": [{"changed_at": 1656400002000_i64}, {"changed_at": 1656400003000_i64}],
+            }},
+        };
+        assert_eq!(
+            nested_timestamps_as_dates(&document).unwrap(),
+            doc! {
+                "kill_switch.triggered_at": date(1656400000000),
+                "classing.bandit.recomputed_at": date(1656400001000),
+                "classing.bandit.history.0.changed_at": date(1656400002000),
+                "classing.bandit.history.1.changed_at": date(1656400003000),
+            }
+        );
+        assert_eq!(
+            nested_timestamps_as_dates(&doc! {"kill_switch": Bson::Null, "classing": {}}).unwrap(),
+            doc! {}
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_run_is_idempotent() {
+        let url = match std::env::var("MONGO_TEST_URL") {
+            Ok(url) => url,
+            Err(_) => return,
+        };
+        let client = mongodb::Client::with_uri_str(&url).await.unwrap();
+        let db = client.database("enigma_test");
+        let suffix = oid::ObjectId::new().to_hex();
+        let coll = db.collection::<bson::Document>(&format!("experiments_{}", suffix));
+        let records = db.collection::<Record>(&format!("migrations_{}", suffix));
+
+        let id = oid::ObjectId::new();
+        coll.insert_one(
+            doc! {
+                "_id": id,
+                "id": id.to_hex(),
+                "created_at": 1656400000000_i64,
+                "updated_at": "2022-07-01T00:00:00Z",
+                "kill_switch": {"triggered_at": 1656400000000_i64},
+                "classing": {"bandit": {"history": [{"changed_at": 1656400000000_i64}]}},
+            },
+            None,
+        )
+        .await
+        .unwrap();
+
+        let migrator = Migrator::new(records.clone(), experiment_migrations(coll.clone()));
+        let applied = migrator.run().await.unwrap();
+        let versions: Vec<i32> = applied.iter().map(|r| r.version).collect();
+        assert_eq!(versions, vec![1, 2, SCHEMA_VERSION]);
+        assert!(applied.iter().all(|r| r.modified == 1));
+
+        let stored = coll
+            .find_one(doc! {"_id": id}, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(stored.get_i32("schema_version").unwrap(), SCHEMA_VERSION);
+        assert!(stored.get("id").is_none());
+        assert!(stored.get_datetime("created_at").is_ok());
+        assert!(stored.get_datetime("updated_at").is_ok());
+        let kill_switch = stored.get_document("kill_switch").unwrap();
+        assert!(kill_switch.get_datetime("triggered_at").is_ok());
+
+        // the second run finds every migration recorded and changes nothing.
+        assert!(migrator.pending().await.unwrap().is_empty());
+        assert!(migrator.run().await.unwrap().is_empty());
+        assert_eq!(records.count_documents(None, None).await.unwrap(), 3);
+        assert_eq!(
+            coll.find_one(doc! {"_id": id}, None)
+                .await
+                .unwrap()
+                .unwrap(),
+            stored
+        );
+
+        coll.drop(None).await.unwrap();
+        records.drop(None).await.unwrap();
+    }
+}
@@ -1 +1,60 @@
+use mongodb::error::{Error, ErrorKind, WriteFailure};
+
+pub mod assignment;
+pub mod cache;
+#[cfg(test)]
+pub(crate) mod conformance;
+pub mod event_sourced;
 pub mod experiment;
+pub mod history;
+pub mod idempotency;
+pub mod lease;
+pub mod memory;
+pub mod migration;
+pub mod outbox;
+pub mod postgres;
+pub mod sqlite;
+pub mod webhook;
+
+const DUPLICATE_KEY_CODE: i32 = 11000;
+const INDEX_OPTIONS_CONFLICT_CODE: i32 = 85;
+const INDEX_KEY_SPECS_CONFLICT_CODE: i32 = 86;
+const ILLEGAL_OPERATION_CODE: i32 = 20;
+const NAMESPACE_NOT_FOUND_CODE: i32 = 26;
+
+/// Check whether the mongo error is caused by a unique index violation.
+pub(crate) fn is_duplicate_key(err: &Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Command(e) => e.code == DUPLICATE_KEY_CODE,
+        ErrorKind::Write(WriteFailure::WriteError(e)) => e.code == DUPLICATE_KEY_CODE,
+        _ => false,
+    }
+}
+
+/// Check whether the mongo error is caused by an index existing with another definition.
+pub(crate) fn is_index_conflict(err: &Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Command(e) => {
+            e.code == INDEX_OPTIONS_CONFLICT_CODE || e.code == INDEX_KEY_SPECS_CONFLICT_CODE
+        }
+        _ => false,
+    }
+}
+
+/// Check whether the mongo error is caused by a deployment without transactions, e.g. a
+/// standalone server.
+pub(crate) fn is_transaction_unsupported(err: &Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Command(e) => e.code == ILLEGAL_OPERATION_CODE,
+        ErrorKind::Transaction { .. } => true,
+        _ => false,
+    }
+}
+
+/// Check whether the mongo error is caused by a collection that does not exist yet.
+pub(crate) fn is_namespace_missing(err: &Error) -> bool {
+    match err.kind.as_ref() {
+        ErrorKind::Command(e) => e.code == NAMESPACE_NOT_FOUND_CODE,
+        _ => false,
+    }
+}
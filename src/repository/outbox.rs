@@ -0,0 +1,250 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid},
+    options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument},
+    Collection, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::service::event::Event;
+use crate::service::experiment as experiment_service;
+use crate::service::outbox as service;
+
+/// How long the delivered entries are kept.
+pub const DELIVERED_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Document {
+    pub _id: oid::ObjectId,
+    pub event: Event,
+    pub attempts: i32,
+    pub next_attempt_at: bson::DateTime,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<bson::DateTime>,
+}
+
+impl Document {
+    /// A new entry of the event, due right away.
+    pub fn new(event: Event) -> Self {
+        Self {
+            _id: oid::ObjectId::new(),
+            event,
+            attempts: 0,
+            next_attempt_at: bson::DateTime::now(),
+            last_error: None,
+            delivered_at: None,
+        }
+    }
+}
+
+impl From<Document> for service::Entry {
+    fn from(doc: Document) -> Self {
+        Self {
+            id: doc._id.to_hex(),
+            event: doc.event,
+            attempts: doc.attempts.max(0) as u32,
+            next_attempt_at: doc.next_attempt_at.to_chrono(),
+            last_error: doc.last_error,
+            delivered_at: doc.delivered_at.map(|dt| dt.to_chrono()),
+        }
+    }
+}
+
+/// Outbox in a mongo collection, `repository::experiment::Repo::with_outbox` writes to it.
+pub struct Repo {
+    coll: Collection<Document>,
+}
+
+impl Repo {
+    pub fn new(coll: Collection<Document>) -> Self {
+        Self { coll }
+    }
+
+    /// Create the index of the due entries and the one expiring the delivered entries.
+    pub async fn create_indexes(&self) -> Result<()> {
+        let due = IndexModel::builder()
+            .keys(doc! {"delivered_at": 1, "next_attempt_at": 1})
+            .build();
+        let expiry = IndexModel::builder()
+            .keys(doc! {"delivered_at": 1})
+            .options(
+                IndexOptions::builder()
+                    .name("outbox_delivered_expiry".to_owned())
+                    .expire_after(DELIVERED_RETENTION)
+                    .partial_filter_expression(doc! {"delivered_at": {"$type": "date"}})
+                    .build(),
+            )
+            .build();
+
+        self.coll.create_indexes([due, expiry], None).await?;
+        Ok(())
+    }
+}
+
+fn internal_error(e: mongodb::error::Error) -> experiment_service::StoreError {
+    experiment_service::StoreError::InternalError {
+        message: e.to_string(),
+    }
+}
+
+fn parse_id(id: &str) -> Result<oid::ObjectId> {
+    oid::ObjectId::parse_str(id).map_err(|e| {
+        experiment_service::StoreError::InvalidInput {
+            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
+        }
+        .into()
+    })
+}
+
+#[async_trait]
+impl service::Outbox for Repo {
+    async fn claim(
+        &self,
+        limit: i64,
+        now: DateTime<Utc>,
+        lease: Duration,
+        max_attempts: u32,
+    ) -> Result<Vec<service::Entry>> {
+        let now = bson::DateTime::from_chrono(now);
+        let due = doc! {"delivered_at": null, "next_attempt_at": {"$lte": now}};
+
+        let mut filter = due.clone();
+        filter.insert("attempts", doc! {"$lt": max_attempts as i64});
+        let opts = FindOptions::builder()
+            .sort(doc! {"_id": 1})
+            .limit(limit)
+            .build();
+        let candidates: Vec<Document> = self
+            .coll
+            .find(filter, opts)
+            .await
+            .map_err(internal_error)?
+            .try_collect()
+            .await
+            .map_err(internal_error)?;
+
+        // another dispatcher may have claimed some of them meanwhile.
+        let until = bson::DateTime::from_millis(now.timestamp_millis() + lease.as_millis() as i64);
+        let mut claimed = vec![];
+        for candidate in candidates {
+            let mut filter = due.clone();
+            filter.insert("_id", candidate._id);
+            let opts = FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build();
+            let doc = self
+                .coll
+                .find_one_and_update(filter, doc! {"$set": {"next_attempt_at": until}}, opts)
+                .await
+                .map_err(internal_error)?;
+            claimed.extend(doc.map(service::Entry::from));
+        }
+
+        Ok(claimed)
+    }
+
+    async fn delivered(&self, id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.coll
+            .update_one(
+                doc! {"_id": parse_id(id)?},
+                doc! {"$set": {"delivered_at": bson::DateTime::from_chrono(at)}},
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+
+    async fn failed(&self, id: &str, error: &str, retry_at: DateTime<Utc>) -> Result<()> {
+        self.coll
+            .update_one(
+                doc! {"_id": parse_id(id)?},
+                doc! {
+                    "$inc": {"attempts": 1},
+                    "$set": {
+                        "last_error": error,
+                        "next_attempt_at": bson::DateTime::from_chrono(retry_at),
+                    },
+                },
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+}
+
+/// The tests but the first run against the deployment of `MONGO_TEST_URL` and are skipped
+/// when it is not set.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::outbox::Outbox;
+
+    async fn repo() -> Option<Repo> {
+        let url = std::env::var("MONGO_TEST_URL").ok()?;
+        let client = mongodb::Client::with_uri_str(&url).await.unwrap();
+        let coll = client
+            .database("enigma_test")
+            .collection(&format!("outbox_{}", oid::ObjectId::new().to_hex()));
+        let repo = Repo::new(coll);
+        repo.create_indexes().await.unwrap();
+        Some(repo)
+    }
+
+    #[test]
+    fn test_document_round_trip() {
+        let event = Event::deleted("62bb13dfea2b3ea78771e305", "channel_a", Utc::now());
+        let doc = Document::new(event);
+
+        let stored = bson::to_document(&doc).unwrap();
+        assert!(stored.get_datetime("next_attempt_at").is_ok());
+        assert_eq!(stored.get("delivered_at"), Some(&bson::Bson::Null));
+
+        let entry = service::Entry::from(bson::from_document::<Document>(stored).unwrap());
+        assert_eq!(entry.id, doc._id.to_hex());
+        assert_eq!(entry.event.experiment_id, "62bb13dfea2b3ea78771e305");
+    }
+
+    #[actix_web::test]
+    async fn test_claim() {
+        let repo = match repo().await {
+            Some(repo) => repo,
+            None => return,
+        };
+        // the entries are due from their insertion.
+        let now = Utc::now() + chrono::Duration::seconds(1);
+        let lease = Duration::from_secs(60);
+        for _ in 0..2 {
+            let event = Event::deleted("62bb13dfea2b3ea78771e305", "channel_a", now);
+            repo.coll
+                .insert_one(Document::new(event), None)
+                .await
+                .unwrap();
+        }
+
+        let claimed = repo.claim(10, now, lease, 3).await.unwrap();
+        assert_eq!(claimed.len(), 2);
+        assert!(repo.claim(10, now, lease, 3).await.unwrap().is_empty());
+
+        repo.delivered(&claimed[0].id, now).await.unwrap();
+        repo.failed(&claimed[1].id, "unreachable", now)
+            .await
+            .unwrap();
+
+        let later = now + chrono::Duration::minutes(2);
+        let retried = repo.claim(10, later, lease, 3).await.unwrap();
+        assert_eq!(retried.len(), 1);
+        assert_eq!(retried[0].attempts, 1);
+        assert_eq!(retried[0].last_error.as_deref(), Some("unreachable"));
+
+        // the entries which failed too often are left alone.
+        assert!(repo.claim(10, later, lease, 1).await.unwrap().is_empty());
+        repo.coll.drop(None).await.unwrap();
+    }
+}
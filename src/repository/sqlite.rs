@@ -0,0 +1,698 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, TimeZone, Utc};
+use mongodb::bson::oid;
+use rusqlite::{
+    params, params_from_iter, types::Value, Connection, ErrorCode, OptionalExtension,
+    TransactionBehavior,
+};
+
+use crate::service::experiment as service;
+use crate::service::projection::Projection;
+use crate::service::search;
+use crate::service::tag;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS experiments (
+    id TEXT PRIMARY KEY,
+    channel_id TEXT NOT NULL,
+    key TEXT NOT NULL DEFAULT '',
+    name TEXT NOT NULL,
+    description TEXT NOT NULL,
+    status TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    variations TEXT NOT NULL,
+    classing TEXT NOT NULL,
+    factorial TEXT,
+    owner TEXT,
+    kill_switch TEXT,
+    active_from INTEGER,
+    active_until INTEGER,
+    created_at INTEGER,
+    updated_at INTEGER,
+    deleted_at INTEGER
+);
+CREATE INDEX IF NOT EXISTS experiment_channel ON experiments (channel_id);
+CREATE INDEX IF NOT EXISTS experiment_channel_updated_at ON experiments (channel_id, updated_at);
+CREATE UNIQUE INDEX IF NOT EXISTS experiment_key ON experiments (channel_id, key) WHERE key <> '';
+";
+
+const COLUMNS: &str = "id, channel_id, key, name, description, status, tags, variations, \
+    classing, factorial, owner, kill_switch, active_from, active_until, created_at, \
+    updated_at, deleted_at";
+
+/// Experiment store in an embedded SQLite database, for the single node installations.
+/// The variations and the other nested parts are stored as json columns, the timestamps
+/// as milliseconds.
+#[derive(Clone)]
+pub struct Repo {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Repo {
+    /// Open the database file, creating it and its schema when missing.
+    pub fn open(path: &str) -> Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Open a database living as long as the store, for the tests.
+    pub fn open_in_memory() -> Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> Result<Self> {
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Run the statements on a blocking thread, SQLite calls block.
+    async fn with_conn<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    {
+        let conn = self.conn.clone();
+        actix_web::rt::task::spawn_blocking(move || f(&mut conn.lock().unwrap())).await?
+    }
+}
+
+fn parse_id(id: &str) -> Result<oid::ObjectId> {
+    oid::ObjectId::parse_str(id).map_err(|e| {
+        service::StoreError::InvalidInput {
+            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
+        }
+        .into()
+    })
+}
+
+/// Translate the errors of SQLite into the errors of the store.
+fn store_error(err: rusqlite::Error, conflict: impl FnOnce() -> String) -> anyhow::Error {
+    match err {
+        rusqlite::Error::SqliteFailure(e, _) if e.code == ErrorCode::ConstraintViolation => {
+            service::StoreError::Conflict {
+                message: conflict(),
+            }
+            .into()
+        }
+        e => service::StoreError::InternalError {
+            message: e.to_string(),
+        }
+        .into(),
+    }
+}
+
+fn internal_error(err: rusqlite::Error) -> anyhow::Error {
+    store_error(err, String::new)
+}
+
+fn to_millis(dt: &Option<DateTime<Utc>>) -> Option<i64> {
+    dt.map(|dt| dt.timestamp_millis())
+}
+
+fn from_millis(ms: Option<i64>) -> Option<DateTime<Utc>> {
+    ms.and_then(|ms| Utc.timestamp_millis_opt(ms).single())
+}
+
+fn to_json<T: serde::Serialize>(v: &T) -> Result<String> {
+    Ok(serde_json::to_string(v)?)
+}
+
+fn status_name(status: service::Status) -> Result<String> {
+    Ok(serde_json::to_value(status)?
+        .as_str()
+        .unwrap_or_default()
+        .to_owned())
+}
+
+/// Truncate the timestamps to the stored milliseconds, so the experiment handed back after
+/// a write is the same as the one loaded later.
+fn to_stored_precision(data: &mut service::Experiment) {
+    let truncate = |v: &mut Option<DateTime<Utc>>| *v = from_millis(to_millis(v));
+    truncate(&mut data.created_at);
+    truncate(&mut data.updated_at);
+    truncate(&mut data.deleted_at);
+    if let Some(interval) = data.active_interval.as_mut() {
+        truncate(&mut interval.0);
+        truncate(&mut interval.1);
+    }
+}
+
+/// Values of the columns of the experiment, in the order of `COLUMNS`.
+fn row_values(data: &service::Experiment) -> Result<Vec<Value>> {
+    let interval = data.active_interval.as_ref();
+    let opt_json = |v: Option<String>| v.map(Value::Text).unwrap_or(Value::Null);
+    let opt_int = |v: Option<i64>| v.map(Value::Integer).unwrap_or(Value::Null);
+
+    Ok(vec![
+        Value::Text(data.id.clone().unwrap_or_default()),
+        Value::Text(data.channel_id.clone()),
+        Value::Text(data.key.clone()),
+        Value::Text(data.name.clone()),
+        Value::Text(data.description.clone()),
+        Value::Text(status_name(data.status)?),
+        Value::Text(to_json(&data.tags)?),
+        Value::Text(to_json(&data.variations)?),
+        Value::Text(to_json(&data.classing)?),
+        opt_json(data.factorial.as_ref().map(to_json).transpose()?),
+        opt_json(data.owner.as_ref().map(to_json).transpose()?),
+        opt_json(data.kill_switch.as_ref().map(to_json).transpose()?),
+        opt_int(interval.and_then(|i| to_millis(&i.0))),
+        opt_int(interval.and_then(|i| to_millis(&i.1))),
+        opt_int(to_millis(&data.created_at)),
+        opt_int(to_millis(&data.updated_at)),
+        opt_int(to_millis(&data.deleted_at)),
+    ])
+}
+
+/// Read the experiment of a row selected with `COLUMNS`.
+fn from_row(row: &rusqlite::Row) -> Result<service::Experiment> {
+    let json = |i: usize| -> Result<Option<serde_json::Value>> {
+        Ok(row
+            .get::<_, Option<String>>(i)?
+            .map(|s| serde_json::from_str(&s))
+            .transpose()?)
+    };
+    let decode =
+        |i: usize| -> Result<serde_json::Value> { Ok(json(i)?.unwrap_or(serde_json::Value::Null)) };
+
+    let active_from = from_millis(row.get(12)?);
+    let active_until = from_millis(row.get(13)?);
+
+    Ok(service::Experiment {
+        id: Some(row.get(0)?),
+        channel_id: row.get(1)?,
+        key: row.get(2)?,
+        name: row.get(3)?,
+        description: row.get(4)?,
+        status: serde_json::from_value(serde_json::Value::String(row.get(5)?))?,
+        tags: serde_json::from_value(decode(6)?)?,
+        variations: serde_json::from_value(decode(7)?)?,
+        classing: serde_json::from_value(decode(8)?)?,
+        factorial: json(9)?.map(serde_json::from_value).transpose()?,
+        owner: json(10)?,
+        kill_switch: json(11)?.map(serde_json::from_value).transpose()?,
+        active_interval: match (active_from, active_until) {
+            (None, None) => None,
+            (from, until) => Some(service::Interval(from, until)),
+        },
+        created_at: from_millis(row.get(14)?),
+        updated_at: from_millis(row.get(15)?),
+        deleted_at: from_millis(row.get(16)?),
+    })
+}
+
+fn select(conn: &Connection, clause: &str, values: Vec<Value>) -> Result<Vec<service::Experiment>> {
+    let sql = format!("SELECT {} FROM experiments {}", COLUMNS, clause);
+    let mut stmt = conn.prepare(&sql).map_err(internal_error)?;
+    let mut rows = stmt
+        .query(params_from_iter(values))
+        .map_err(internal_error)?;
+
+    let mut experiments = vec![];
+    while let Some(row) = rows.next().map_err(internal_error)? {
+        experiments.push(from_row(row)?);
+    }
+    Ok(experiments)
+}
+
+fn insert(conn: &Connection, data: &mut service::Experiment) -> Result<()> {
+    // an experiment brought from elsewhere, e.g. an import, keeps its id.
+    let id = match &data.id {
+        Some(id) => parse_id(id)?,
+        None => oid::ObjectId::new(),
+    };
+
+    let now = Utc::now();
+    data.id = Some(id.to_hex());
+    data.updated_at = Some(now);
+    if data.created_at.is_none() {
+        data.created_at = Some(now);
+    }
+    to_stored_precision(data);
+
+    let placeholders = vec!["?"; 17].join(", ");
+    conn.execute(
+        &format!(
+            "INSERT INTO experiments ({}) VALUES ({})",
+            COLUMNS, placeholders
+        ),
+        params_from_iter(row_values(data)?),
+    )
+    .map_err(|e| {
+        store_error(e, || {
+            "experiment already exists, its id or key is taken".to_owned()
+        })
+    })?;
+
+    Ok(())
+}
+
+/// Replace the stored experiment, at the `expected` version if any.
+fn replace(
+    conn: &Connection,
+    data: &mut service::Experiment,
+    expected: Option<service::Version>,
+) -> Result<()> {
+    let id = parse_id(&data.id.clone().unwrap_or_default())?;
+    data.id = Some(id.to_hex());
+    data.updated_at = Some(expected.map(|v| v.next()).unwrap_or_else(Utc::now));
+    to_stored_precision(data);
+
+    let assignments = COLUMNS
+        .split(',')
+        .map(|c| format!("{} = ?", c.trim()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let mut values = row_values(data)?;
+    values.push(Value::Text(id.to_hex()));
+    values.push(Value::Text(data.channel_id.clone()));
+    let (condition, version) = version_condition(expected);
+    values.extend(version);
+
+    let changed = conn
+        .execute(
+            &format!(
+                "UPDATE experiments SET {} WHERE id = ? AND channel_id = ?{}",
+                assignments, condition
+            ),
+            params_from_iter(values),
+        )
+        .map_err(|e| store_error(e, || format!("key `{}` is taken", data.key)))?;
+    if changed == 0 {
+        return Err(not_written(conn, &id.to_hex(), &data.channel_id)?);
+    }
+
+    Ok(())
+}
+
+/// Delete the stored experiment, at the `expected` version if any.
+fn remove(
+    conn: &Connection,
+    id: &str,
+    channel_id: &str,
+    expected: Option<service::Version>,
+) -> Result<()> {
+    let id = parse_id(id)?.to_hex();
+    let mut values = vec![Value::Text(id.clone()), Value::Text(channel_id.to_owned())];
+    let (condition, version) = version_condition(expected);
+    values.extend(version);
+
+    let changed = conn
+        .execute(
+            &format!(
+                "DELETE FROM experiments WHERE id = ? AND channel_id = ?{}",
+                condition
+            ),
+            params_from_iter(values),
+        )
+        .map_err(internal_error)?;
+    if changed == 0 {
+        return Err(not_written(conn, &id, channel_id)?);
+    }
+
+    Ok(())
+}
+
+/// Patch the stored experiment in a transaction, none when the patch does not apply to it.
+fn apply_patch(
+    conn: &mut Connection,
+    id: &str,
+    channel_id: &str,
+    patch: &service::Patch,
+) -> Result<Option<service::Experiment>> {
+    let id = parse_id(id)?.to_hex();
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .map_err(internal_error)?;
+
+    let values = vec![Value::Text(id), Value::Text(channel_id.to_owned())];
+    let current = select(&tx, "WHERE id = ? AND channel_id = ?", values)?
+        .pop()
+        .ok_or(service::StoreError::DocumentNotfound)?;
+    let mut data = current.clone();
+    if !patch.apply(&mut data) {
+        return Ok(None);
+    }
+    replace(&tx, &mut data, Some(service::Version::of(&current)))?;
+    tx.commit().map_err(internal_error)?;
+
+    Ok(Some(data))
+}
+
+/// Condition of a write on the `expected` version, and its value.
+fn version_condition(expected: Option<service::Version>) -> (&'static str, Option<Value>) {
+    match expected {
+        Some(service::Version(at)) => (
+            " AND updated_at IS ?",
+            Some(to_millis(&at).map(Value::Integer).unwrap_or(Value::Null)),
+        ),
+        None => ("", None),
+    }
+}
+
+/// Why a write of the experiment matched no row, it is missing or at another version.
+fn not_written(conn: &Connection, id: &str, channel_id: &str) -> Result<anyhow::Error> {
+    let exists = conn
+        .query_row(
+            "SELECT 1 FROM experiments WHERE id = ? AND channel_id = ?",
+            params![id, channel_id],
+            |_| Ok(()),
+        )
+        .optional()
+        .map_err(internal_error)?
+        .is_some();
+
+    Ok(if exists {
+        service::StoreError::Stale.into()
+    } else {
+        service::StoreError::DocumentNotfound.into()
+    })
+}
+
+fn sort_column(sort: service::SortField) -> &'static str {
+    match sort {
+        service::SortField::CreatedAt => "created_at",
+        service::SortField::UpdatedAt => "updated_at",
+        service::SortField::Name => "name",
+    }
+}
+
+/// Build the `WHERE` clause of the experiments matching the query, the pagination aside.
+fn list_filter(channel_id: &str, query: &service::ListQuery) -> Result<(String, Vec<Value>)> {
+    let mut conditions = vec!["channel_id = ?".to_owned()];
+    let mut values = vec![Value::Text(channel_id.to_owned())];
+
+    if let Some(status) = query.status {
+        conditions.push("status = ?".to_owned());
+        values.push(Value::Text(status_name(status)?));
+    }
+    if let Some(ref owner_id) = query.owner_id {
+        conditions.push("json_extract(owner, '$.id') = ?".to_owned());
+        values.push(Value::Text(owner_id.clone()));
+    }
+    if let Some(ref tag) = query.tag {
+        conditions
+            .push("EXISTS (SELECT 1 FROM json_each(experiments.tags) WHERE value = ?)".to_owned());
+        values.push(Value::Text(tag.clone()));
+    }
+    if let Some(ref prefix) = query.name_prefix {
+        // `substr` rather than `LIKE`, which ignores the case and has wildcards.
+        conditions.push("substr(name, 1, length(?)) = ?".to_owned());
+        values.push(Value::Text(prefix.clone()));
+        values.push(Value::Text(prefix.clone()));
+    }
+    if let Some(active_at) = query.active_at {
+        conditions.push("(active_from IS NULL OR active_from <= ?)".to_owned());
+        conditions.push("(active_until IS NULL OR active_until > ?)".to_owned());
+        values.push(Value::Integer(active_at.timestamp_millis()));
+        values.push(Value::Integer(active_at.timestamp_millis()));
+    }
+
+    Ok((format!("WHERE {}", conditions.join(" AND ")), values))
+}
+
+fn list(conn: &Connection, channel_id: &str, query: &service::ListQuery) -> Result<service::Page> {
+    let (filter, values) = list_filter(channel_id, query)?;
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM experiments {}", filter),
+            params_from_iter(values.clone()),
+            |row| row.get(0),
+        )
+        .map_err(internal_error)?;
+
+    let column = sort_column(query.sort);
+    let (direction, operator) = match query.order {
+        service::SortOrder::Asc => ("ASC", ">"),
+        service::SortOrder::Desc => ("DESC", "<"),
+    };
+
+    let mut clause = filter;
+    let mut values = values;
+    if let Some(ref cursor) = query.cursor {
+        let key = match &cursor.key {
+            serde_json::Value::Number(n) => Value::Integer(n.as_i64().unwrap_or_default()),
+            serde_json::Value::String(s) => Value::Text(s.clone()),
+            _ => {
+                return Err(service::UserError::ValidationError {
+                    message: "invalid cursor".to_owned(),
+                }
+                .into())
+            }
+        };
+        clause.push_str(&format!(
+            " AND ({col} {op} ? OR ({col} = ? AND id {op} ?))",
+            col = column,
+            op = operator
+        ));
+        values.extend([key.clone(), key, Value::Text(cursor.id.clone())]);
+    }
+
+    // one more than the limit tells whether there is a next page.
+    clause.push_str(&format!(
+        " ORDER BY {col} {dir}, id {dir} LIMIT ?",
+        col = column,
+        dir = direction
+    ));
+    values.push(Value::Integer(query.limit + 1));
+
+    let mut items = select(conn, &clause, values)?;
+
+    let mut next_cursor = None;
+    if items.len() as i64 > query.limit {
+        items.truncate(query.limit.max(0) as usize);
+        if let Some(last) = items.last() {
+            let key = match query.sort {
+                service::SortField::CreatedAt => serde_json::json!(to_millis(&last.created_at)),
+                service::SortField::UpdatedAt => serde_json::json!(to_millis(&last.updated_at)),
+                service::SortField::Name => serde_json::json!(last.name),
+            };
+            next_cursor = Some(
+                service::Cursor {
+                    key,
+                    id: last.id.clone().unwrap_or_default(),
+                    query: query.fingerprint(),
+                }
+                .encode(),
+            );
+        }
+    }
+
+    Ok(service::Page {
+        items,
+        next_cursor,
+        total: total as u64,
+    })
+}
+
+#[async_trait]
+impl service::Store for Repo {
+    async fn save(&self, data: &mut service::Experiment) -> Result<String> {
+        let mut experiment = data.clone();
+        *data = self
+            .with_conn(move |conn| {
+                insert(conn, &mut experiment)?;
+                Ok(experiment)
+            })
+            .await?;
+
+        Ok(data.id.clone().unwrap_or_default())
+    }
+
+    async fn list(&self, channel_id: &str, query: &service::ListQuery) -> Result<service::Page> {
+        let channel_id = channel_id.to_owned();
+        let query = query.clone();
+        self.with_conn(move |conn| list(conn, &channel_id, &query))
+            .await
+    }
+
+    async fn get(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        let id = parse_id(id)?.to_hex();
+        let experiment = self
+            .with_conn(move |conn| {
+                Ok(select(conn, "WHERE id = ?", vec![Value::Text(id)])?
+                    .pop()
+                    .ok_or(service::StoreError::DocumentNotfound)?)
+            })
+            .await?;
+
+        if experiment.channel_id != channel_id {
+            return Err(service::StoreError::UnauthorizedAccess.into());
+        }
+
+        Ok(experiment)
+    }
+
+    async fn get_fresh(&self, id: &str, channel_id: &str) -> Result<service::Experiment> {
+        self.get(id, channel_id).await
+    }
+
+    async fn get_by_key(&self, key: &str, channel_id: &str) -> Result<service::Experiment> {
+        let values = vec![
+            Value::Text(channel_id.to_owned()),
+            Value::Text(key.to_owned()),
+        ];
+        self.with_conn(move |conn| {
+            Ok(select(conn, "WHERE channel_id = ? AND key = ?", values)?
+                .pop()
+                .ok_or(service::StoreError::DocumentNotfound)?)
+        })
+        .await
+    }
+
+    async fn get_projected(
+        &self,
+        id: &str,
+        channel_id: &str,
+        _projection: &Projection,
+    ) -> Result<service::Experiment> {
+        // the nested parts are json columns, loading the row whole is as cheap.
+        self.get(id, channel_id).await
+    }
+
+    async fn update(&self, data: &mut service::Experiment) -> Result<()> {
+        let mut experiment = data.clone();
+        *data = self
+            .with_conn(move |conn| {
+                replace(conn, &mut experiment, None)?;
+                Ok(experiment)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn update_if(
+        &self,
+        data: &mut service::Experiment,
+        version: service::Version,
+    ) -> Result<()> {
+        let mut experiment = data.clone();
+        *data = self
+            .with_conn(move |conn| {
+                replace(conn, &mut experiment, Some(version))?;
+                Ok(experiment)
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn patch(
+        &self,
+        id: &str,
+        channel_id: &str,
+        patch: &service::Patch,
+    ) -> Result<Option<service::Experiment>> {
+        let (id, channel_id, change) = (id.to_owned(), channel_id.to_owned(), patch.clone());
+        self.with_conn(move |conn| apply_patch(conn, &id, &channel_id, &change))
+            .await
+    }
+
+    async fn write_atomically(&self, writes: &mut [service::Write]) -> Result<bool> {
+        let mut staged = writes.to_vec();
+        let written = self
+            .with_conn(move |conn| {
+                let tx = conn.transaction().map_err(internal_error)?;
+                for write in staged.iter_mut() {
+                    match write {
+                        service::Write::Insert(data) => insert(&tx, data)?,
+                        service::Write::Replace(data) => replace(&tx, data, None)?,
+                        service::Write::Delete { id, channel_id } => {
+                            remove(&tx, id, channel_id, None)?
+                        }
+                    }
+                }
+                tx.commit().map_err(internal_error)?;
+                Ok(staged)
+            })
+            .await?;
+
+        writes.clone_from_slice(&written);
+        Ok(true)
+    }
+
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
+        let id = id.to_owned();
+        let channel_id = channel_id.to_owned();
+        self.with_conn(move |conn| remove(conn, &id, &channel_id, None))
+            .await
+    }
+
+    async fn delete_if(&self, id: &str, channel_id: &str, version: service::Version) -> Result<()> {
+        let id = id.to_owned();
+        let channel_id = channel_id.to_owned();
+        self.with_conn(move |conn| remove(conn, &id, &channel_id, Some(version)))
+            .await
+    }
+
+    async fn list_by_status(
+        &self,
+        statuses: &[service::Status],
+        after: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<service::Experiment>> {
+        if statuses.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut values = statuses
+            .iter()
+            .map(|s| Ok(Value::Text(status_name(*s)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut clause = format!("WHERE status IN ({})", vec!["?"; values.len()].join(", "));
+        if let Some(after) = after {
+            clause.push_str(" AND id > ?");
+            values.push(Value::Text(after));
+        }
+        clause.push_str(" ORDER BY id LIMIT ?");
+        values.push(Value::Integer(limit));
+        self.with_conn(move |conn| select(conn, &clause, values))
+            .await
+    }
+
+    async fn search(&self, channel_id: &str, q: &str, limit: i64) -> Result<Vec<search::Hit>> {
+        let values = vec![Value::Text(channel_id.to_owned())];
+        let experiments = self
+            .with_conn(move |conn| select(conn, "WHERE channel_id = ?", values))
+            .await?;
+
+        Ok(search::rank(experiments, q, limit))
+    }
+
+    async fn tag_counts(&self, channel_id: &str) -> Result<Vec<tag::TagCount>> {
+        let channel_id = channel_id.to_owned();
+        self.with_conn(move |conn| {
+            let mut stmt = conn
+                .prepare(
+                    "SELECT t.value, COUNT(*) AS count \
+                     FROM experiments, json_each(experiments.tags) AS t \
+                     WHERE channel_id = ? GROUP BY t.value ORDER BY count DESC, t.value",
+                )
+                .map_err(internal_error)?;
+            let counts = stmt
+                .query_map(params![channel_id], |row| {
+                    Ok(tag::TagCount {
+                        tag: row.get(0)?,
+                        count: row.get::<_, i64>(1)? as u64,
+                    })
+                })
+                .map_err(internal_error)?
+                .collect::<rusqlite::Result<Vec<_>>>()
+                .map_err(internal_error)?;
+            Ok(counts)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::repository::conformance::store_conformance!(async { Repo::open_in_memory().unwrap() });
+}
@@ -0,0 +1,454 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid},
+    options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument},
+    Collection, IndexModel,
+};
+use serde::{Deserialize, Serialize};
+
+use super::is_duplicate_key;
+use crate::service::event::{Event, Kind};
+use crate::service::experiment as experiment_service;
+use crate::service::webhook as service;
+
+/// How long the deliveries are kept.
+pub const DELIVERY_RETENTION: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SubscriptionDocument {
+    pub _id: oid::ObjectId,
+    pub channel_id: String,
+    pub url: String,
+    pub secret: String,
+    pub events: Vec<Kind>,
+    pub active: bool,
+    pub created_at: Option<bson::DateTime>,
+    pub updated_at: Option<bson::DateTime>,
+}
+
+impl TryFrom<&service::Subscription> for SubscriptionDocument {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &service::Subscription) -> Result<Self> {
+        Ok(Self {
+            _id: parse_id(&s.id)?,
+            channel_id: s.channel_id.clone(),
+            url: s.url.clone(),
+            secret: s.secret.clone(),
+            events: s.events.clone(),
+            active: s.active,
+            created_at: s.created_at.map(bson::DateTime::from_chrono),
+            updated_at: s.updated_at.map(bson::DateTime::from_chrono),
+        })
+    }
+}
+
+impl From<SubscriptionDocument> for service::Subscription {
+    fn from(doc: SubscriptionDocument) -> Self {
+        Self {
+            id: doc._id.to_hex(),
+            channel_id: doc.channel_id,
+            url: doc.url,
+            secret: doc.secret,
+            events: doc.events,
+            active: doc.active,
+            created_at: doc.created_at.map(|dt| dt.to_chrono()),
+            updated_at: doc.updated_at.map(|dt| dt.to_chrono()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DeliveryDocument {
+    pub _id: oid::ObjectId,
+    pub subscription_id: String,
+    pub channel_id: String,
+    pub event: Event,
+    pub status: service::DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: bson::DateTime,
+    pub last_error: Option<String>,
+    pub response_status: Option<i32>,
+    pub created_at: bson::DateTime,
+    pub delivered_at: Option<bson::DateTime>,
+}
+
+impl TryFrom<&service::Delivery> for DeliveryDocument {
+    type Error = anyhow::Error;
+
+    fn try_from(d: &service::Delivery) -> Result<Self> {
+        Ok(Self {
+            _id: parse_id(&d.id)?,
+            subscription_id: d.subscription_id.clone(),
+            channel_id: d.channel_id.clone(),
+            event: d.event.clone(),
+            status: d.status,
+            attempts: d.attempts as i32,
+            next_attempt_at: bson::DateTime::from_chrono(d.next_attempt_at),
+            last_error: d.last_error.clone(),
+            response_status: d.response_status.map(i32::from),
+            created_at: bson::DateTime::from_chrono(d.created_at),
+            delivered_at: d.delivered_at.map(bson::DateTime::from_chrono),
+        })
+    }
+}
+
+impl From<DeliveryDocument> for service::Delivery {
+    fn from(doc: DeliveryDocument) -> Self {
+        Self {
+            id: doc._id.to_hex(),
+            subscription_id: doc.subscription_id,
+            channel_id: doc.channel_id,
+            event: doc.event,
+            status: doc.status,
+            attempts: doc.attempts.max(0) as u32,
+            next_attempt_at: doc.next_attempt_at.to_chrono(),
+            last_error: doc.last_error,
+            response_status: doc.response_status.map(|s| s as u16),
+            created_at: doc.created_at.to_chrono(),
+            delivered_at: doc.delivered_at.map(|dt| dt.to_chrono()),
+        }
+    }
+}
+
+/// Webhook subscriptions and their deliveries in two mongo collections.
+pub struct Repo {
+    subscriptions: Collection<SubscriptionDocument>,
+    deliveries: Collection<DeliveryDocument>,
+}
+
+impl Repo {
+    pub fn new(
+        subscriptions: Collection<SubscriptionDocument>,
+        deliveries: Collection<DeliveryDocument>,
+    ) -> Self {
+        Self {
+            subscriptions,
+            deliveries,
+        }
+    }
+
+    /// Create the indexes of the subscriptions of a channel, of the log of a subscription,
+    /// of the due deliveries and the one expiring the old deliveries.
+    pub async fn create_indexes(&self) -> Result<()> {
+        let channel = IndexModel::builder().keys(doc! {"channel_id": 1}).build();
+        self.subscriptions.create_index(channel, None).await?;
+
+        let log = IndexModel::builder()
+            .keys(doc! {"subscription_id": 1, "_id": -1})
+            .build();
+        let due = IndexModel::builder()
+            .keys(doc! {"status": 1, "next_attempt_at": 1})
+            .build();
+        let expiry = IndexModel::builder()
+            .keys(doc! {"created_at": 1})
+            .options(
+                IndexOptions::builder()
+                    .name("webhook_delivery_expiry".to_owned())
+                    .expire_after(DELIVERY_RETENTION)
+                    .build(),
+            )
+            .build();
+        self.deliveries
+            .create_indexes([log, due, expiry], None)
+            .await?;
+        Ok(())
+    }
+}
+
+fn internal_error(e: mongodb::error::Error) -> experiment_service::StoreError {
+    experiment_service::StoreError::InternalError {
+        message: e.to_string(),
+    }
+}
+
+fn parse_id(id: &str) -> Result<oid::ObjectId> {
+    oid::ObjectId::parse_str(id).map_err(|e| {
+        experiment_service::StoreError::InvalidInput {
+            message: format!("{} id({}) {}", "invalid id pattern", id, &e.to_string()),
+        }
+        .into()
+    })
+}
+
+fn pending() -> bson::Bson {
+    bson::to_bson(&service::DeliveryStatus::Pending).unwrap_or_default()
+}
+
+#[async_trait]
+impl service::Store for Repo {
+    async fn save(&self, subscription: &service::Subscription) -> Result<()> {
+        let doc = SubscriptionDocument::try_from(subscription)?;
+        match self.subscriptions.insert_one(doc, None).await {
+            Ok(_) => Ok(()),
+            Err(e) if is_duplicate_key(&e) => Err(experiment_service::StoreError::Conflict {
+                message: format!("webhook {} already exists", subscription.id),
+            }
+            .into()),
+            Err(e) => Err(internal_error(e).into()),
+        }
+    }
+
+    async fn get(&self, id: &str, channel_id: &str) -> Result<service::Subscription> {
+        self.subscriptions
+            .find_one(doc! {"_id": parse_id(id)?, "channel_id": channel_id}, None)
+            .await
+            .map_err(internal_error)?
+            .map(service::Subscription::from)
+            .ok_or_else(|| experiment_service::StoreError::DocumentNotfound.into())
+    }
+
+    async fn list(&self, channel_id: &str) -> Result<Vec<service::Subscription>> {
+        let opts = FindOptions::builder().sort(doc! {"_id": 1}).build();
+        let docs: Vec<SubscriptionDocument> = self
+            .subscriptions
+            .find(doc! {"channel_id": channel_id}, opts)
+            .await
+            .map_err(internal_error)?
+            .try_collect()
+            .await
+            .map_err(internal_error)?;
+
+        Ok(docs.into_iter().map(service::Subscription::from).collect())
+    }
+
+    async fn update(&self, subscription: &service::Subscription) -> Result<()> {
+        let doc = SubscriptionDocument::try_from(subscription)?;
+        let result = self
+            .subscriptions
+            .replace_one(
+                doc! {"_id": doc._id, "channel_id": &doc.channel_id},
+                &doc,
+                None,
+            )
+            .await
+            .map_err(internal_error)?;
+
+        if result.matched_count == 0 {
+            return Err(experiment_service::StoreError::DocumentNotfound.into());
+        }
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
+        let result = self
+            .subscriptions
+            .delete_one(doc! {"_id": parse_id(id)?, "channel_id": channel_id}, None)
+            .await
+            .map_err(internal_error)?;
+
+        if result.deleted_count == 0 {
+            return Err(experiment_service::StoreError::DocumentNotfound.into());
+        }
+        self.deliveries
+            .delete_many(doc! {"subscription_id": id}, None)
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+
+    async fn matching(&self, event: &Event) -> Result<Vec<service::Subscription>> {
+        let kind = bson::to_bson(&event.kind)?;
+        let filter = doc! {
+            "channel_id": &event.channel_id,
+            "active": true,
+            "$or": [{"events": {"$size": 0}}, {"events": kind}],
+        };
+        let docs: Vec<SubscriptionDocument> = self
+            .subscriptions
+            .find(filter, None)
+            .await
+            .map_err(internal_error)?
+            .try_collect()
+            .await
+            .map_err(internal_error)?;
+
+        Ok(docs.into_iter().map(service::Subscription::from).collect())
+    }
+
+    async fn enqueue(&self, delivery: &service::Delivery) -> Result<()> {
+        self.deliveries
+            .insert_one(DeliveryDocument::try_from(delivery)?, None)
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+
+    async fn deliveries(
+        &self,
+        subscription_id: &str,
+        channel_id: &str,
+        limit: i64,
+    ) -> Result<Vec<service::Delivery>> {
+        let opts = FindOptions::builder()
+            .sort(doc! {"_id": -1})
+            .limit(limit)
+            .build();
+        let docs: Vec<DeliveryDocument> = self
+            .deliveries
+            .find(
+                doc! {"subscription_id": subscription_id, "channel_id": channel_id},
+                opts,
+            )
+            .await
+            .map_err(internal_error)?
+            .try_collect()
+            .await
+            .map_err(internal_error)?;
+
+        Ok(docs.into_iter().map(service::Delivery::from).collect())
+    }
+
+    async fn claim(
+        &self,
+        limit: i64,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Vec<service::Delivery>> {
+        let now = bson::DateTime::from_chrono(now);
+        let due = doc! {"status": pending(), "next_attempt_at": {"$lte": now}};
+
+        let opts = FindOptions::builder()
+            .sort(doc! {"next_attempt_at": 1})
+            .limit(limit)
+            .build();
+        let candidates: Vec<DeliveryDocument> = self
+            .deliveries
+            .find(due.clone(), opts)
+            .await
+            .map_err(internal_error)?
+            .try_collect()
+            .await
+            .map_err(internal_error)?;
+
+        // another worker may have claimed some of them meanwhile.
+        let until = bson::DateTime::from_millis(now.timestamp_millis() + lease.as_millis() as i64);
+        let mut claimed = vec![];
+        for candidate in candidates {
+            let mut filter = due.clone();
+            filter.insert("_id", candidate._id);
+            let opts = FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build();
+            let doc = self
+                .deliveries
+                .find_one_and_update(filter, doc! {"$set": {"next_attempt_at": until}}, opts)
+                .await
+                .map_err(internal_error)?;
+            claimed.extend(doc.map(service::Delivery::from));
+        }
+
+        Ok(claimed)
+    }
+
+    async fn record(&self, delivery: &service::Delivery) -> Result<()> {
+        let doc = DeliveryDocument::try_from(delivery)?;
+        self.deliveries
+            .replace_one(doc! {"_id": doc._id}, &doc, None)
+            .await
+            .map_err(internal_error)?;
+        Ok(())
+    }
+}
+
+/// The tests but the first run against the deployment of `MONGO_TEST_URL` and are skipped
+/// when it is not set.
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::webhook::Store;
+
+    async fn repo() -> Option<Repo> {
+        let url = std::env::var("MONGO_TEST_URL").ok()?;
+        let client = mongodb::Client::with_uri_str(&url).await.unwrap();
+        let db = client.database("enigma_test");
+        let suffix = oid::ObjectId::new().to_hex();
+        let repo = Repo::new(
+            db.collection(&format!("webhooks_{}", suffix)),
+            db.collection(&format!("webhook_deliveries_{}", suffix)),
+        );
+        repo.create_indexes().await.unwrap();
+        Some(repo)
+    }
+
+    fn subscription(events: Vec<Kind>) -> service::Subscription {
+        service::Subscription {
+            id: oid::ObjectId::new().to_hex(),
+            channel_id: "channel_a".to_owned(),
+            url: "http://127.0.0.1/hook".to_owned(),
+            secret: "it's a secret key".to_owned(),
+            events,
+            active: true,
+            created_at: Some(Utc::now()),
+            updated_at: Some(Utc::now()),
+        }
+    }
+
+    #[test]
+    fn test_document_round_trip() {
+        let hook = subscription(vec![Kind::Deleted]);
+        let event = Event::deleted("62bb13dfea2b3ea78771e305", "channel_a", Utc::now());
+        let delivery = service::Delivery::new(&hook, event);
+
+        let stored = bson::to_document(&DeliveryDocument::try_from(&delivery).unwrap()).unwrap();
+        assert_eq!(stored.get_str("status"), Ok("pending"));
+        assert!(stored.get_datetime("next_attempt_at").is_ok());
+        let loaded =
+            service::Delivery::from(bson::from_document::<DeliveryDocument>(stored).unwrap());
+        assert_eq!(loaded.id, delivery.id);
+
+        let stored = bson::to_document(&SubscriptionDocument::try_from(&hook).unwrap()).unwrap();
+        let loaded = service::Subscription::from(
+            bson::from_document::<SubscriptionDocument>(stored).unwrap(),
+        );
+        assert_eq!(loaded.events, vec![Kind::Deleted]);
+    }
+
+    #[actix_web::test]
+    async fn test_matching_and_claim() {
+        let repo = match repo().await {
+            Some(repo) => repo,
+            None => return,
+        };
+        let all = subscription(vec![]);
+        let deletes = subscription(vec![Kind::Deleted]);
+        repo.save(&all).await.unwrap();
+        repo.save(&deletes).await.unwrap();
+
+        let event = Event::deleted("62bb13dfea2b3ea78771e305", "channel_a", Utc::now());
+        assert_eq!(repo.matching(&event).await.unwrap().len(), 2);
+        let event = Event::test("channel_a");
+        assert_eq!(repo.matching(&event).await.unwrap().len(), 1);
+        assert!(repo.get(&all.id, "channel_b").await.is_err());
+
+        repo.enqueue(&service::Delivery::new(&all, event))
+            .await
+            .unwrap();
+        // the deliveries are due from their insertion.
+        let now = Utc::now() + chrono::Duration::seconds(1);
+        let lease = Duration::from_secs(60);
+        let claimed = repo.claim(10, now, lease).await.unwrap();
+        assert_eq!(claimed.len(), 1);
+        assert!(repo.claim(10, now, lease).await.unwrap().is_empty());
+
+        let mut delivery = claimed[0].clone();
+        delivery.status = service::DeliveryStatus::Delivered;
+        repo.record(&delivery).await.unwrap();
+        let log = repo.deliveries(&all.id, "channel_a", 10).await.unwrap();
+        assert_eq!(log[0].status, service::DeliveryStatus::Delivered);
+
+        repo.delete(&all.id, "channel_a").await.unwrap();
+        assert!(repo
+            .deliveries(&all.id, "channel_a", 10)
+            .await
+            .unwrap()
+            .is_empty());
+        repo.subscriptions.drop(None).await.unwrap();
+        repo.deliveries.drop(None).await.unwrap();
+    }
+}
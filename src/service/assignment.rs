@@ -0,0 +1,297 @@
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+
+use super::evaluation::{self, Evaluation, Reason};
+use super::experiment::{Experiment, UserError};
+
+/// How the variation given to a unit is kept, parsed from `Classing::persistent_mode`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PersistentMode {
+    /// Nothing is stored, the unit follows the current weights.
+    None,
+    /// The first variation given to the unit is kept.
+    Key,
+    /// The first variation given to the unit within a context is kept.
+    KeyContextual,
+}
+
+impl PersistentMode {
+    pub fn parse(mode: &str) -> Result<Self> {
+        match mode {
+            "" | "none" => Ok(Self::None),
+            "key" => Ok(Self::Key),
+            "key_contextual" => Ok(Self::KeyContextual),
+            _ => Err(UserError::ValidationError {
+                message: format!(
+                    "unknown persistent mode `{}`, expect one of none, key, key_contextual",
+                    mode
+                ),
+            }
+            .into()),
+        }
+    }
+}
+
+/// Defined struct represents the variation a unit has been given.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Assignment {
+    pub channel_id: String,
+    pub experiment_id: String,
+    pub unit: String,
+    /// Context of the assignment, empty unless the mode is `key_contextual`.
+    pub context: String,
+    pub indicator: String,
+    #[serde(with = "ts_milliseconds")]
+    pub assigned_at: DateTime<Utc>,
+}
+
+/// Defined the contract of the assignment storage.
+#[automock]
+#[async_trait]
+pub trait Store {
+    async fn find(
+        &self,
+        channel_id: &str,
+        experiment_id: &str,
+        unit: &str,
+        context: &str,
+    ) -> Result<Option<Assignment>>;
+    /// Insert the assignment unless the unit is already assigned in the same context,
+    /// returns the assignment that is kept.
+    async fn insert(&self, assignment: &Assignment) -> Result<Assignment>;
+    /// List the assignments of the unit, of every experiment when `experiment_id` is none.
+    async fn list(
+        &self,
+        channel_id: &str,
+        unit: &str,
+        experiment_id: Option<String>,
+    ) -> Result<Vec<Assignment>>;
+    /// Delete the assignments of the unit in the experiment, returns how many were deleted.
+    async fn delete(&self, channel_id: &str, unit: &str, experiment_id: &str) -> Result<u64>;
+}
+
+/// Assignment store kept in the process memory, only suitable when a single instance
+/// is running and the assignments may be lost on restart.
+#[derive(Debug, Default)]
+pub struct LocalStore {
+    assignments: Mutex<Vec<Assignment>>,
+}
+
+#[async_trait]
+impl Store for LocalStore {
+    async fn find(
+        &self,
+        channel_id: &str,
+        experiment_id: &str,
+        unit: &str,
+        context: &str,
+    ) -> Result<Option<Assignment>> {
+        let assignments = self.assignments.lock().unwrap();
+
+        Ok(assignments
+            .iter()
+            .find(|a| {
+                a.channel_id == channel_id
+                    && a.experiment_id == experiment_id
+                    && a.unit == unit
+                    && a.context == context
+            })
+            .cloned())
+    }
+
+    async fn insert(&self, assignment: &Assignment) -> Result<Assignment> {
+        let mut assignments = self.assignments.lock().unwrap();
+
+        let existing = assignments.iter().find(|a| {
+            a.channel_id == assignment.channel_id
+                && a.experiment_id == assignment.experiment_id
+                && a.unit == assignment.unit
+                && a.context == assignment.context
+        });
+        if let Some(existing) = existing {
+            return Ok(existing.clone());
+        }
+
+        assignments.push(assignment.clone());
+        Ok(assignment.clone())
+    }
+
+    async fn list(
+        &self,
+        channel_id: &str,
+        unit: &str,
+        experiment_id: Option<String>,
+    ) -> Result<Vec<Assignment>> {
+        let assignments = self.assignments.lock().unwrap();
+
+        Ok(assignments
+            .iter()
+            .filter(|a| {
+                a.channel_id == channel_id
+                    && a.unit == unit
+                    && experiment_id
+                        .as_ref()
+                        .map(|id| &a.experiment_id == id)
+                        .unwrap_or(true)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn delete(&self, channel_id: &str, unit: &str, experiment_id: &str) -> Result<u64> {
+        let mut assignments = self.assignments.lock().unwrap();
+
+        let before = assignments.len();
+        assignments.retain(|a| {
+            !(a.channel_id == channel_id && a.unit == unit && a.experiment_id == experiment_id)
+        });
+
+        Ok((before - assignments.len()) as u64)
+    }
+}
+
+/// Evaluate the experiment for the unit, keeping the first variation given to the unit
+/// according to the experiment's persistent mode. Control served because the
+/// experiment is killed or not running is never persisted.
+pub async fn evaluate(
+    store: &(impl Store + ?Sized),
+    experiment: &Experiment,
+    unit: &str,
+    context: &str,
+) -> Result<Option<Evaluation>> {
+    let evaluated = match evaluation::evaluate(experiment, unit) {
+        Some(evaluated) => evaluated,
+        None => return Ok(None),
+    };
+
+    let context = match PersistentMode::parse(&experiment.classing.persistent_mode)? {
+        PersistentMode::None => return Ok(Some(evaluated)),
+        PersistentMode::Key => "",
+        PersistentMode::KeyContextual => context,
+    };
+    if evaluated.reason != Reason::Assigned {
+        return Ok(Some(evaluated));
+    }
+
+    let experiment_id = experiment.id.clone().unwrap_or_default();
+    let (kept, is_new) = match store
+        .find(&experiment.channel_id, &experiment_id, unit, context)
+        .await?
+    {
+        Some(assignment) => (assignment, false),
+        None => {
+            let assignment = Assignment {
+                channel_id: experiment.channel_id.clone(),
+                experiment_id,
+                unit: unit.to_owned(),
+                context: context.to_owned(),
+                indicator: evaluated.indicator.clone(),
+                assigned_at: Utc::now(),
+            };
+            (store.insert(&assignment).await?, true)
+        }
+    };
+
+    if is_new && kept.indicator == evaluated.indicator {
+        return Ok(Some(evaluated));
+    }
+
+    // the kept variation may have been removed from the experiment since.
+    match experiment
+        .variations
+        .iter()
+        .find(|v| v.indicator == kept.indicator)
+    {
+        Some(variation) => Ok(Some(Evaluation {
+            indicator: variation.indicator.clone(),
+            values: variation.values.clone(),
+            reason: Reason::Persisted,
+        })),
+        None => Ok(Some(evaluated)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{Classing, Status, Variance};
+
+    fn experiment(persistent_mode: &str, weights: (i32, i32)) -> Experiment {
+        let variance = |indicator: &str, weight| Variance {
+            group_name: indicator.to_owned(),
+            description: String::default(),
+            indicator: indicator.to_owned(),
+            weight,
+            values: Default::default(),
+            levels: Default::default(),
+        };
+
+        Experiment {
+            id: Some("exp".to_owned()),
+            status: Status::Running,
+            variations: vec![
+                variance("control", weights.0),
+                variance("treatment", weights.1),
+            ],
+            classing: Classing {
+                persistent_mode: persistent_mode.to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_evaluate_keeps_first_assignment() {
+        let store = LocalStore::default();
+
+        let first = evaluate(&store, &experiment("key", (0, 1)), "unit", "")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(first.indicator, "treatment");
+        assert_eq!(first.reason, Reason::Assigned);
+
+        // the weights changed but the unit keeps its variation.
+        let second = evaluate(&store, &experiment("key", (1, 0)), "unit", "")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(second.indicator, "treatment");
+        assert_eq!(second.reason, Reason::Persisted);
+
+        assert_eq!(store.delete("", "unit", "exp").await.unwrap(), 1);
+        let reset = evaluate(&store, &experiment("key", (1, 0)), "unit", "")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reset.indicator, "control");
+    }
+
+    #[actix_web::test]
+    async fn test_evaluate_contextual_and_none() {
+        let store = LocalStore::default();
+
+        evaluate(&store, &experiment("key_contextual", (0, 1)), "unit", "a")
+            .await
+            .unwrap();
+        let other = evaluate(&store, &experiment("key_contextual", (1, 0)), "unit", "b")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(other.indicator, "control");
+        assert_eq!(store.list("", "unit", None).await.unwrap().len(), 2);
+
+        evaluate(&store, &experiment("none", (0, 1)), "other", "")
+            .await
+            .unwrap();
+        assert!(store.list("", "other", None).await.unwrap().is_empty());
+
+        assert!(PersistentMode::parse("forever").is_err());
+    }
+}
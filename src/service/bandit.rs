@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use chrono::{
+    serde::{ts_milliseconds, ts_milliseconds_option},
+    DateTime, Duration, Utc,
+};
+use rand::Rng;
+use rand_distr::{Beta, Distribution};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+use super::experiment::{Experiment, Patch, Store, UserError};
+
+/// Value of `Classing::strategy` that makes the weights driven by a bandit.
+pub const STRATEGY: &str = "bandit";
+
+/// Number of draws used to estimate the probability of each variation being the best.
+const THOMPSON_DRAWS: usize = 1000;
+
+/// Total of the weights a bandit distributes between the variations.
+const TOTAL_WEIGHT: f64 = 100.0;
+
+/// Defined struct represents the settings and the state of a multi-armed bandit.
+#[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+#[validate(schema(function = "validate_bounds"))]
+pub struct Bandit {
+    pub algorithm: Algorithm,
+    /// Share of the traffic spread evenly for exploration, epsilon-greedy only.
+    #[serde(default = "default_epsilon")]
+    #[validate(range(min = 0.0, max = 1.0, message = "value must bound between 0 - 1"))]
+    pub epsilon: f64,
+    #[serde(default)]
+    #[validate(range(min = 0, max = 100, message = "value must bound between 0 - 100"))]
+    pub min_weight: i32,
+    #[serde(default = "default_max_weight")]
+    #[validate(range(min = 0, max = 100, message = "value must bound between 0 - 100"))]
+    pub max_weight: i32,
+    #[serde(default = "default_recompute_interval")]
+    #[validate(range(min = 60, message = "must be at least 60 seconds"))]
+    pub recompute_interval_secs: i64,
+    /// Frozen weights are not recomputed anymore.
+    #[serde(default)]
+    pub frozen: bool,
+
+    /// Rewards ingested per variation indicator.
+    #[serde(default)]
+    pub rewards: HashMap<String, Reward>,
+    #[serde(default)]
+    pub history: Vec<WeightChange>,
+    #[serde(default, with = "ts_milliseconds_option")]
+    pub recomputed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    ThompsonSampling,
+    EpsilonGreedy,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct Reward {
+    pub trials: u64,
+    pub successes: u64,
+}
+
+/// A record of the weights given to the variations.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WeightChange {
+    #[serde(with = "ts_milliseconds")]
+    pub changed_at: DateTime<Utc>,
+    pub weights: HashMap<String, i32>,
+}
+
+pub fn default_epsilon() -> f64 {
+    0.1
+}
+
+pub fn default_max_weight() -> i32 {
+    100
+}
+
+pub fn default_recompute_interval() -> i64 {
+    3600
+}
+
+fn validate_bounds(bandit: &Bandit) -> Result<(), ValidationError> {
+    if bandit.min_weight > bandit.max_weight {
+        return Err(ValidationError::new(
+            "min_weight must not exceed max_weight",
+        ));
+    }
+    Ok(())
+}
+
+impl Bandit {
+    /// Whether the weights should be recomputed at `now`.
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        !self.frozen
+            && self
+                .recomputed_at
+                .map(|at| at + Duration::seconds(self.recompute_interval_secs) <= now)
+                .unwrap_or(true)
+    }
+}
+
+/// Check the rewards can be added to the experiment's bandit.
+pub fn check_rewards(experiment: &Experiment, rewards: &HashMap<String, Reward>) -> Result<()> {
+    if experiment.classing.bandit.is_none() {
+        return Err(invalid("experiment is not a bandit".to_owned()));
+    }
+
+    for (indicator, reward) in rewards.iter() {
+        if !experiment
+            .variations
+            .iter()
+            .any(|v| &v.indicator == indicator)
+        {
+            return Err(invalid(format!("unknown variation `{}`", indicator)));
+        }
+        if reward.successes > reward.trials {
+            return Err(invalid(format!(
+                "variation `{}` has more successes than trials",
+                indicator
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Add the rewards to the experiment's bandit.
+pub fn ingest(experiment: &mut Experiment, rewards: &HashMap<String, Reward>) -> Result<()> {
+    check_rewards(experiment, rewards)?;
+    add_to(bandit_mut(experiment)?, rewards);
+    Ok(())
+}
+
+/// Add the rewards to the counters of the bandit.
+pub fn add_to(bandit: &mut Bandit, rewards: &HashMap<String, Reward>) {
+    for (indicator, reward) in rewards.iter() {
+        let total = bandit.rewards.entry(indicator.clone()).or_default();
+        total.trials += reward.trials;
+        total.successes += reward.successes;
+    }
+}
+
+/// Freeze or unfreeze the weights of the experiment's bandit.
+/// Recompute the variations' weights from the rewards and record the change.
+pub fn recompute(
+    experiment: &mut Experiment,
+    rng: &mut impl Rng,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    if let Some(change) = weigh(experiment, rng, now)? {
+        apply_weights(experiment, &change);
+    }
+    Ok(())
+}
+
+/// Compute the weights of the variations from the rewards, none without variations.
+pub fn weigh(
+    experiment: &Experiment,
+    rng: &mut impl Rng,
+    now: DateTime<Utc>,
+) -> Result<Option<WeightChange>> {
+    let bandit = match experiment.classing.bandit {
+        Some(ref bandit) => bandit,
+        None => return Err(invalid("experiment is not a bandit".to_owned())),
+    };
+
+    let rewards: Vec<Reward> = experiment
+        .variations
+        .iter()
+        .map(|v| {
+            bandit
+                .rewards
+                .get(&v.indicator)
+                .copied()
+                .unwrap_or_default()
+        })
+        .collect();
+    if rewards.is_empty() {
+        return Ok(None);
+    }
+
+    let shares = match bandit.algorithm {
+        Algorithm::ThompsonSampling => thompson_sampling(&rewards, rng),
+        Algorithm::EpsilonGreedy => epsilon_greedy(&rewards, bandit.epsilon),
+    };
+    let weights = bound(&shares, bandit.min_weight as f64, bandit.max_weight as f64);
+
+    let weights = experiment
+        .variations
+        .iter()
+        .zip(weights)
+        .map(|(variation, weight)| (variation.indicator.clone(), weight.round() as i32))
+        .collect();
+
+    Ok(Some(WeightChange {
+        changed_at: now,
+        weights,
+    }))
+}
+
+/// Give the variations their weight of the change and record it in the history.
+pub fn apply_weights(experiment: &mut Experiment, change: &WeightChange) {
+    for variation in experiment.variations.iter_mut() {
+        if let Some(weight) = change.weights.get(&variation.indicator) {
+            variation.weight = *weight;
+        }
+    }
+
+    if let Some(bandit) = experiment.classing.bandit.as_mut() {
+        bandit.recomputed_at = Some(change.changed_at);
+        bandit.history.push(change.clone());
+    }
+}
+
+/// Check the bounds of the weights leave room for the variations to share the total weight.
+pub fn check_bounds(experiment: &Experiment) -> Result<()> {
+    let bandit = match experiment.classing.bandit {
+        Some(ref bandit) => bandit,
+        None => return Ok(()),
+    };
+
+    let n = experiment.variations.len() as i32;
+    if bandit.min_weight * n > TOTAL_WEIGHT as i32 || bandit.max_weight * n < TOTAL_WEIGHT as i32 {
+        return Err(invalid(format!(
+            "min_weight and max_weight must allow {} variations to share a total weight of {}",
+            n, TOTAL_WEIGHT
+        )));
+    }
+    Ok(())
+}
+
+/// Probability of each variation being the best, estimated by sampling the
+/// Beta posterior of its success rate.
+fn thompson_sampling(rewards: &[Reward], rng: &mut impl Rng) -> Vec<f64> {
+    let posteriors: Vec<Beta<f64>> = rewards
+        .iter()
+        .map(|r| {
+            Beta::new(
+                1.0 + r.successes as f64,
+                1.0 + (r.trials - r.successes) as f64,
+            )
+            .expect("beta parameters are positive")
+        })
+        .collect();
+
+    let mut wins = vec![0usize; rewards.len()];
+    for _ in 0..THOMPSON_DRAWS {
+        let best = posteriors
+            .iter()
+            .map(|p| p.sample(rng))
+            .enumerate()
+            .fold(
+                (0, f64::MIN),
+                |best, (i, v)| if v > best.1 { (i, v) } else { best },
+            )
+            .0;
+        wins[best] += 1;
+    }
+
+    wins.into_iter()
+        .map(|w| w as f64 / THOMPSON_DRAWS as f64)
+        .collect()
+}
+
+/// The best observed variation takes `1 - epsilon` of the traffic and `epsilon`
+/// is spread evenly for exploration.
+fn epsilon_greedy(rewards: &[Reward], epsilon: f64) -> Vec<f64> {
+    let rate = |r: &Reward| {
+        if r.trials == 0 {
+            0.0
+        } else {
+            r.successes as f64 / r.trials as f64
+        }
+    };
+    let best = rewards
+        .iter()
+        .enumerate()
+        .fold((0, f64::MIN), |best, (i, r)| {
+            if rate(r) > best.1 {
+                (i, rate(r))
+            } else {
+                best
+            }
+        })
+        .0;
+
+    let explore = epsilon / rewards.len() as f64;
+    (0..rewards.len())
+        .map(|i| {
+            if i == best {
+                1.0 - epsilon + explore
+            } else {
+                explore
+            }
+        })
+        .collect()
+}
+
+/// Scale the shares to the total weight keeping every weight between `min` and `max`,
+/// what is cut from a bounded weight is redistributed to the others by their share.
+fn bound(shares: &[f64], min: f64, max: f64) -> Vec<f64> {
+    let mut weights = vec![None; shares.len()];
+
+    for _ in 0..shares.len() {
+        let fixed: f64 = weights.iter().flatten().sum();
+        let open: f64 = shares
+            .iter()
+            .zip(weights.iter())
+            .filter(|(_, w)| w.is_none())
+            .map(|(s, _)| s)
+            .sum();
+
+        let mut changed = false;
+        for (share, weight) in shares.iter().zip(weights.iter_mut()) {
+            if weight.is_some() {
+                continue;
+            }
+            let scaled = if open > 0.0 {
+                share / open * (TOTAL_WEIGHT - fixed)
+            } else {
+                0.0
+            };
+            if scaled < min {
+                *weight = Some(min);
+                changed = true;
+            } else if scaled > max {
+                *weight = Some(max);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let fixed: f64 = weights.iter().flatten().sum();
+    let open: f64 = shares
+        .iter()
+        .zip(weights.iter())
+        .filter(|(_, w)| w.is_none())
+        .map(|(s, _)| s)
+        .sum();
+
+    shares
+        .iter()
+        .zip(weights)
+        .map(|(share, weight)| {
+            weight.unwrap_or(if open > 0.0 {
+                share / open * (TOTAL_WEIGHT - fixed)
+            } else {
+                0.0
+            })
+        })
+        .collect()
+}
+
+pub async fn add_rewards(
+    repo: &impl Store,
+    id: &str,
+    channel_id: &str,
+    rewards: &HashMap<String, Reward>,
+) -> Result<Experiment> {
+    let experiment = repo.get(id, channel_id).await?;
+    check_rewards(&experiment, rewards)?;
+
+    // the counters are incremented in place, so concurrent rewards all count.
+    let patch = Patch::Rewards(rewards.clone());
+    repo.patch(id, channel_id, &patch)
+        .await?
+        .ok_or_else(|| invalid("experiment is not a bandit".to_owned()))
+}
+
+pub async fn freeze(
+    repo: &impl Store,
+    id: &str,
+    channel_id: &str,
+    frozen: bool,
+) -> Result<Experiment> {
+    // frozen in place, so the rewards counted meanwhile are kept.
+    repo.patch(id, channel_id, &Patch::Freeze(frozen))
+        .await?
+        .ok_or_else(|| invalid("experiment is not a bandit".to_owned()))
+}
+
+fn bandit_mut(experiment: &mut Experiment) -> Result<&mut Bandit> {
+    experiment
+        .classing
+        .bandit
+        .as_mut()
+        .ok_or_else(|| invalid("experiment is not a bandit".to_owned()))
+}
+
+fn invalid(message: String) -> anyhow::Error {
+    UserError::ValidationError { message }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{Classing, Variance};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn experiment(algorithm: Algorithm) -> Experiment {
+        let variance = |indicator: &str| Variance {
+            group_name: indicator.to_owned(),
+            description: String::default(),
+            indicator: indicator.to_owned(),
+            weight: 50,
+            values: HashMap::new(),
+            levels: HashMap::new(),
+        };
+
+        Experiment {
+            variations: vec![variance("control"), variance("treatment")],
+            classing: Classing {
+                strategy: STRATEGY.to_owned(),
+                persistent_mode: String::default(),
+                bandit: Some(Bandit {
+                    algorithm,
+                    epsilon: 0.2,
+                    min_weight: 10,
+                    max_weight: 100,
+                    recompute_interval_secs: 3600,
+                    frozen: false,
+                    rewards: HashMap::new(),
+                    history: vec![],
+                    recomputed_at: None,
+                }),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn rewards() -> HashMap<String, Reward> {
+        HashMap::from([
+            (
+                "control".to_owned(),
+                Reward {
+                    trials: 1000,
+                    successes: 50,
+                },
+            ),
+            (
+                "treatment".to_owned(),
+                Reward {
+                    trials: 1000,
+                    successes: 150,
+                },
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_thompson_sampling_recompute() {
+        let mut experiment = experiment(Algorithm::ThompsonSampling);
+        ingest(&mut experiment, &rewards()).unwrap();
+
+        let now = Utc::now();
+        let mut rng = StdRng::seed_from_u64(7);
+        recompute(&mut experiment, &mut rng, now).unwrap();
+
+        // the treatment is clearly better but the control keeps its floor.
+        assert_eq!(experiment.variations[0].weight, 10);
+        assert_eq!(experiment.variations[1].weight, 90);
+
+        let bandit = experiment.classing.bandit.as_ref().unwrap();
+        assert_eq!(bandit.history.len(), 1);
+        assert_eq!(bandit.history[0].weights["treatment"], 90);
+        assert!(!bandit.is_due(now));
+    }
+
+    #[test]
+    fn test_epsilon_greedy_recompute() {
+        let mut experiment = experiment(Algorithm::EpsilonGreedy);
+        ingest(&mut experiment, &rewards()).unwrap();
+
+        let mut rng = StdRng::seed_from_u64(7);
+        recompute(&mut experiment, &mut rng, Utc::now()).unwrap();
+
+        assert_eq!(experiment.variations[0].weight, 10);
+        assert_eq!(experiment.variations[1].weight, 90);
+    }
+
+    #[test]
+    fn test_ingest_rejects_unknown_variation() {
+        let mut experiment = experiment(Algorithm::EpsilonGreedy);
+        let rewards = HashMap::from([("unknown".to_owned(), Reward::default())]);
+
+        assert!(ingest(&mut experiment, &rewards).is_err());
+    }
+
+    #[test]
+    fn test_history_is_kept_whole() {
+        let mut experiment = experiment(Algorithm::EpsilonGreedy);
+        ingest(&mut experiment, &rewards()).unwrap();
+
+        let start = Utc::now();
+        let mut rng = StdRng::seed_from_u64(7);
+        for i in 0..150 {
+            let now = start + Duration::seconds(i as i64);
+            recompute(&mut experiment, &mut rng, now).unwrap();
+        }
+
+        let history = &experiment.classing.bandit.as_ref().unwrap().history;
+        assert_eq!(history.len(), 150);
+        assert_eq!(history[0].changed_at, start);
+    }
+
+    #[test]
+    fn test_check_bounds() {
+        let mut experiment = experiment(Algorithm::EpsilonGreedy);
+        assert!(check_bounds(&experiment).is_ok());
+
+        // two variations cannot each get at least 60.
+        experiment.classing.bandit.as_mut().unwrap().min_weight = 60;
+        assert!(check_bounds(&experiment).is_err());
+
+        // nor share 100 with at most 40 each.
+        let bandit = experiment.classing.bandit.as_mut().unwrap();
+        bandit.min_weight = 0;
+        bandit.max_weight = 40;
+        assert!(check_bounds(&experiment).is_err());
+    }
+
+    #[test]
+    fn test_frozen_is_not_due() {
+        let mut experiment = experiment(Algorithm::EpsilonGreedy);
+        assert!(Patch::Freeze(true).apply(&mut experiment));
+
+        assert!(!experiment.classing.bandit.unwrap().is_due(Utc::now()));
+    }
+}
@@ -0,0 +1,426 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::experiment::{self, Experiment, Status, Store, UserError, Write};
+use super::key;
+
+/// Upper bound of the operations of one batch.
+pub const MAX_OPERATIONS: usize = 100;
+
+/// A change of the batch.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    Create {
+        experiment: Experiment,
+    },
+    /// Replace the definition of the experiment, see `experiment::carry_over` for what is kept.
+    Update {
+        experiment: Experiment,
+    },
+    Delete {
+        id: String,
+    },
+    SetStatus {
+        id: String,
+        status: Status,
+    },
+}
+
+/// Result of one operation of the batch.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Outcome {
+    pub index: usize,
+    pub id: Option<String>,
+    pub ok: bool,
+    pub message: Option<String>,
+}
+
+/// Result of a batch, `applied` tells whether every operation took effect. When it is false
+/// none did.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Report {
+    pub applied: bool,
+    /// Whether the store applied the batch in a transaction rather than one by one.
+    pub transaction: bool,
+    pub outcomes: Vec<Outcome>,
+    /// Status changes made by the batch, the experiment and the status it left.
+    #[serde(skip)]
+    pub status_changes: Vec<(Experiment, Status)>,
+}
+
+/// A validated operation, the write and the experiment it replaces or deletes.
+struct Planned {
+    write: Write,
+    before: Option<Experiment>,
+}
+
+/// Validate every operation then apply them all or none of them.
+pub async fn apply(
+    repo: &impl Store,
+    channel_id: &str,
+    operations: Vec<Operation>,
+) -> Result<Report> {
+    if operations.is_empty() || operations.len() > MAX_OPERATIONS {
+        return Err(UserError::ValidationError {
+            message: format!("a batch has between 1 - {} operations", MAX_OPERATIONS),
+        }
+        .into());
+    }
+
+    let mut seen = HashSet::new();
+    let mut keys = HashSet::new();
+    let mut planned = vec![];
+    for op in operations {
+        planned.push(plan(repo, channel_id, op, &mut seen, &mut keys).await);
+    }
+
+    if planned.iter().any(|p| p.is_err()) {
+        let outcomes = planned
+            .iter()
+            .enumerate()
+            .map(|(index, p)| Outcome {
+                index,
+                id: p.as_ref().ok().and_then(|p| id_of(&p.write)),
+                ok: p.is_ok(),
+                message: p.as_ref().err().map(|e| e.to_string()),
+            })
+            .collect();
+
+        return Ok(Report {
+            applied: false,
+            transaction: false,
+            outcomes,
+            status_changes: vec![],
+        });
+    }
+
+    let planned: Vec<Planned> = planned.into_iter().map(|p| p.unwrap()).collect();
+    let mut writes: Vec<Write> = planned.iter().map(|p| p.write.clone()).collect();
+
+    let transaction = repo.write_atomically(&mut writes).await?;
+    let failure = match transaction {
+        true => None,
+        false => write_one_by_one(repo, &mut writes, &planned).await,
+    };
+
+    let outcomes = writes
+        .iter()
+        .enumerate()
+        .map(|(index, w)| {
+            let message = match &failure {
+                None => None,
+                Some((i, e)) if *i == index => Some(e.to_string()),
+                Some(_) => Some("not applied, another operation failed".to_owned()),
+            };
+            Outcome {
+                index,
+                id: id_of(w),
+                ok: failure.is_none(),
+                message,
+            }
+        })
+        .collect();
+
+    let status_changes = match failure {
+        Some(_) => vec![],
+        None => writes
+            .iter()
+            .zip(&planned)
+            .filter_map(|(w, p)| match (w, &p.before) {
+                (Write::Replace(after), Some(before)) if after.status != before.status => {
+                    Some((after.clone(), before.status))
+                }
+                _ => None,
+            })
+            .collect(),
+    };
+
+    Ok(Report {
+        applied: failure.is_none(),
+        transaction,
+        outcomes,
+        status_changes,
+    })
+}
+
+async fn plan(
+    repo: &impl Store,
+    channel_id: &str,
+    op: Operation,
+    seen: &mut HashSet<String>,
+    keys: &mut HashSet<String>,
+) -> Result<Planned> {
+    let id = match &op {
+        Operation::Create { .. } => None,
+        Operation::Update { experiment } => {
+            Some(
+                experiment
+                    .id
+                    .clone()
+                    .ok_or_else(|| UserError::ValidationError {
+                        message: "id is required to update an experiment".to_owned(),
+                    })?,
+            )
+        }
+        Operation::Delete { id } | Operation::SetStatus { id, .. } => Some(id.clone()),
+    };
+
+    let before = match &id {
+        Some(id) => {
+            if !seen.insert(id.clone()) {
+                return Err(UserError::ValidationError {
+                    message: "an experiment is changed by one operation at most".to_owned(),
+                }
+                .into());
+            }
+            Some(repo.get_fresh(id, channel_id).await?)
+        }
+        None => None,
+    };
+
+    let write = match (op, before.clone()) {
+        (Operation::Create { mut experiment }, _) => {
+            experiment.id = None;
+            experiment.channel_id = channel_id.to_owned();
+            experiment.status = Status::default();
+            experiment.kill_switch = None;
+            key::assign(repo, &mut experiment, keys).await?;
+            experiment::prepare(&mut experiment)?;
+            Write::Insert(experiment)
+        }
+        (Operation::Update { mut experiment }, Some(before)) => {
+            experiment::carry_over(&before, &mut experiment);
+            key::assign(repo, &mut experiment, keys).await?;
+            experiment::check(&experiment)?;
+            Write::Replace(experiment)
+        }
+        (Operation::Delete { id }, _) => Write::Delete {
+            id,
+            channel_id: channel_id.to_owned(),
+        },
+        (Operation::SetStatus { status, .. }, Some(mut experiment)) => {
+            check_transition(&experiment, status)?;
+            experiment.status = status;
+            Write::Replace(experiment)
+        }
+        (_, None) => unreachable!("the experiment is loaded for every operation with an id"),
+    };
+
+    Ok(Planned { write, before })
+}
+
+/// Check the status can be changed by hand, the scheduler starts the experiments.
+fn check_transition(experiment: &Experiment, to: Status) -> Result<()> {
+    if experiment.kill_switch.is_some() {
+        return Err(UserError::ValidationError {
+            message: "the experiment is killed, release it first".to_owned(),
+        }
+        .into());
+    }
+
+    let allowed = matches!(
+        (experiment.status, to),
+        (Status::Running, Status::Paused)
+            | (Status::Paused, Status::Running)
+            | (
+                Status::Scheduled | Status::Running | Status::Paused,
+                Status::Ended
+            )
+    );
+    if !allowed {
+        return Err(UserError::ValidationError {
+            message: format!(
+                "status cannot change from {:?} to {:?}",
+                experiment.status, to
+            ),
+        }
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Fallback of the stores without transactions, applies the writes in order and reverts
+/// the applied ones when one fails. Returns the index and error of the failed write.
+async fn write_one_by_one(
+    repo: &impl Store,
+    writes: &mut [Write],
+    planned: &[Planned],
+) -> Option<(usize, anyhow::Error)> {
+    for index in 0..writes.len() {
+        let result = match &mut writes[index] {
+            Write::Insert(e) => repo.save(e).await.map(|_| ()),
+            Write::Replace(e) => repo.update(e).await,
+            Write::Delete { id, channel_id } => repo.delete(id, channel_id).await,
+        };
+
+        if let Err(e) = result {
+            for (write, p) in writes[..index].iter().zip(planned).rev() {
+                if let Err(e) = revert(repo, write, p.before.clone()).await {
+                    println!("batch: failed to revert {:?}: {}", id_of(write), e);
+                }
+            }
+            return Some((index, e));
+        }
+    }
+
+    None
+}
+
+async fn revert(repo: &impl Store, write: &Write, before: Option<Experiment>) -> Result<()> {
+    match (write, before) {
+        (Write::Insert(e), _) => {
+            repo.delete(&e.id.clone().unwrap_or_default(), &e.channel_id)
+                .await
+        }
+        (Write::Replace(_), Some(mut before)) => repo.update(&mut before).await,
+        (Write::Delete { .. }, Some(mut before)) => repo.save(&mut before).await.map(|_| ()),
+        (_, None) => Ok(()),
+    }
+}
+
+fn id_of(write: &Write) -> Option<String> {
+    match write {
+        Write::Insert(e) | Write::Replace(e) => e.id.clone(),
+        Write::Delete { id, .. } => Some(id.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{Classing, MockStore, StoreError, Variance};
+
+    fn experiment(id: Option<&str>, status: Status) -> Experiment {
+        Experiment {
+            id: id.map(str::to_owned),
+            name: "experiment".to_owned(),
+            variations: vec![Variance {
+                group_name: "control".to_owned(),
+                description: String::new(),
+                indicator: "control".to_owned(),
+                weight: 100,
+                values: Default::default(),
+                levels: Default::default(),
+            }],
+            classing: Classing {
+                strategy: "random".to_owned(),
+                ..Default::default()
+            },
+            status,
+            ..Default::default()
+        }
+    }
+
+    fn mock_store() -> MockStore {
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(StoreError::DocumentNotfound.into()));
+        mock_store.expect_get_fresh().returning(|id, _| match id {
+            "running" => Ok(experiment(Some("running"), Status::Running)),
+            "ended" => Ok(experiment(Some("ended"), Status::Ended)),
+            _ => Err(StoreError::DocumentNotfound.into()),
+        });
+        mock_store
+    }
+
+    #[actix_web::test]
+    async fn test_apply_invalid() {
+        let mut mock_store = mock_store();
+        mock_store.expect_write_atomically().never();
+
+        let ops = vec![
+            Operation::Create {
+                experiment: experiment(None, Status::Running),
+            },
+            Operation::SetStatus {
+                id: "ended".to_owned(),
+                status: Status::Running,
+            },
+            Operation::Delete {
+                id: "missing".to_owned(),
+            },
+        ];
+
+        let report = apply(&mock_store, "channel", ops).await.unwrap();
+        assert!(!report.applied);
+        let oks: Vec<bool> = report.outcomes.iter().map(|o| o.ok).collect();
+        assert_eq!(oks, vec![true, false, false]);
+    }
+
+    #[actix_web::test]
+    async fn test_apply_transaction() {
+        let mut mock_store = mock_store();
+        mock_store
+            .expect_write_atomically()
+            .withf(|w| {
+                matches!(&w[0], Write::Insert(e) if e.status == Status::Scheduled)
+                    && matches!(&w[1], Write::Replace(e) if e.status == Status::Paused)
+            })
+            .returning(|w| {
+                if let Write::Insert(e) = &mut w[0] {
+                    e.id = Some("new".to_owned());
+                }
+                Ok(true)
+            });
+
+        let ops = vec![
+            Operation::Create {
+                experiment: experiment(None, Status::Running),
+            },
+            Operation::SetStatus {
+                id: "running".to_owned(),
+                status: Status::Paused,
+            },
+        ];
+
+        let report = apply(&mock_store, "channel", ops).await.unwrap();
+        assert!(report.applied && report.transaction);
+        assert_eq!(report.outcomes[0].id.as_deref(), Some("new"));
+        assert_eq!(report.status_changes.len(), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_apply_fallback_reverts() {
+        let mut mock_store = mock_store();
+        mock_store
+            .expect_write_atomically()
+            .returning(|_| Ok(false));
+        mock_store.expect_save().times(1).returning(|e| {
+            e.id = Some("new".to_owned());
+            Ok("new".to_owned())
+        });
+        mock_store
+            .expect_delete()
+            .withf(|id, _| id == "running")
+            .times(1)
+            .returning(|_, _| {
+                Err(StoreError::InternalError {
+                    message: "down".to_owned(),
+                }
+                .into())
+            });
+        mock_store
+            .expect_delete()
+            .withf(|id, _| id == "new")
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        let ops = vec![
+            Operation::Create {
+                experiment: experiment(None, Status::Scheduled),
+            },
+            Operation::Delete {
+                id: "running".to_owned(),
+            },
+        ];
+
+        let report = apply(&mock_store, "channel", ops).await.unwrap();
+        assert!(!report.applied && !report.transaction);
+        assert_eq!(report.outcomes[1].message.as_deref(), Some("down"));
+    }
+}
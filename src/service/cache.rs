@@ -0,0 +1,39 @@
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+
+/// Counters of the experiment cache of a channel.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Stats {
+    /// Whether the experiments are cached at all.
+    pub enabled: bool,
+    /// Number of the cached reads of the channel.
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Number of the reads dropped to bound the size of the cache.
+    pub evictions: u64,
+    /// Number of the times the channel was dropped by a write or a flush.
+    pub invalidations: u64,
+}
+
+/// Defined the contract to look into and flush the cache of the experiment store.
+#[automock]
+pub trait Control {
+    fn stats(&self, channel_id: &str) -> Stats;
+    /// Drop the cached reads of the channel, returns how many were dropped.
+    fn flush(&self, channel_id: &str) -> usize;
+}
+
+/// Control of a store which is not cached.
+#[derive(Debug, Default)]
+pub struct NoCache;
+
+impl Control for NoCache {
+    fn stats(&self, _channel_id: &str) -> Stats {
+        Stats::default()
+    }
+
+    fn flush(&self, _channel_id: &str) -> usize {
+        0
+    }
+}
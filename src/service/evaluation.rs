@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use super::experiment::{Experiment, Status, Variance};
+
+/// Defined struct represents the variation served to a unit.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Evaluation {
+    pub indicator: String,
+    pub values: HashMap<String, serde_json::Value>,
+    pub reason: Reason,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Reason {
+    /// The unit is bucketed into the variation by its weight.
+    Assigned,
+    /// The unit keeps the variation it has been given before.
+    Persisted,
+    /// The experiment is not running so the control is served.
+    NotRunning,
+    /// The kill switch forces the control to be served.
+    Killed,
+}
+
+impl Evaluation {
+    fn new(variation: &Variance, reason: Reason) -> Self {
+        Self {
+            indicator: variation.indicator.clone(),
+            values: variation.values.clone(),
+            reason,
+        }
+    }
+}
+
+/// Pick the variation served to `unit`. The same unit always lands in the same
+/// variation as long as the weights do not change. Returns `None` when the
+/// experiment has no variation.
+pub fn evaluate(experiment: &Experiment, unit: &str) -> Option<Evaluation> {
+    let control = experiment.control()?;
+
+    if experiment.kill_switch.is_some() {
+        return Some(Evaluation::new(control, Reason::Killed));
+    }
+    if experiment.status != Status::Running {
+        return Some(Evaluation::new(control, Reason::NotRunning));
+    }
+
+    let total: u64 = experiment
+        .variations
+        .iter()
+        .map(|v| v.weight.max(0) as u64)
+        .sum();
+    if total == 0 {
+        return Some(Evaluation::new(control, Reason::NotRunning));
+    }
+
+    let id = experiment.id.as_deref().unwrap_or_default();
+    let mut bucket = hash(&format!("{}:{}", id, unit)) % total;
+    for variation in experiment.variations.iter() {
+        let weight = variation.weight.max(0) as u64;
+        if bucket < weight {
+            return Some(Evaluation::new(variation, Reason::Assigned));
+        }
+        bucket -= weight;
+    }
+
+    Some(Evaluation::new(control, Reason::Assigned))
+}
+
+/// 64-bit FNV-1a, stable across builds unlike the std hasher.
+fn hash(s: &str) -> u64 {
+    s.bytes().fold(0xcbf29ce484222325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{KillScope, KillSwitch};
+    use chrono::Utc;
+
+    fn variance(indicator: &str, weight: i32) -> Variance {
+        Variance {
+            group_name: indicator.to_owned(),
+            description: String::default(),
+            indicator: indicator.to_owned(),
+            weight,
+            values: HashMap::from([("var".to_owned(), serde_json::json!(indicator))]),
+            levels: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let mut experiment = Experiment {
+            id: Some("exp".to_owned()),
+            status: Status::Running,
+            variations: vec![variance("treatment", 1), variance("control", 0)],
+            ..Default::default()
+        };
+
+        let result = evaluate(&experiment, "unit-1").unwrap();
+        assert_eq!(result.indicator, "treatment");
+        assert_eq!(result.reason, Reason::Assigned);
+
+        experiment.kill_switch = Some(KillSwitch {
+            scope: KillScope::Experiment,
+            reason: "broken".to_owned(),
+            triggered_by: None,
+            triggered_at: Utc::now(),
+        });
+        let result = evaluate(&experiment, "unit-1").unwrap();
+        assert_eq!(result.indicator, "control");
+        assert_eq!(result.values["var"], serde_json::json!("control"));
+        assert_eq!(result.reason, Reason::Killed);
+    }
+}
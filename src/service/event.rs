@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use super::experiment::{Experiment, KillSwitch, Status};
+
+/// Defined struct represents something that happened to an experiment.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Event {
+    pub kind: Kind,
+    pub experiment_id: String,
+    pub channel_id: String,
+    #[serde(with = "ts_milliseconds")]
+    pub occurred_at: DateTime<Utc>,
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Created,
+    Updated,
+    Deleted,
+    StatusChanged,
+    Killed,
+    Released,
+    WeightsChanged,
+    /// Sent to check a webhook, nothing happened to the experiment.
+    Test,
+}
+
+impl Event {
+    pub fn created(experiment: &Experiment) -> Self {
+        Self::changed(Kind::Created, experiment)
+    }
+
+    pub fn updated(experiment: &Experiment) -> Self {
+        Self::changed(Kind::Updated, experiment)
+    }
+
+    fn changed(kind: Kind, experiment: &Experiment) -> Self {
+        Self {
+            kind,
+            experiment_id: experiment.id.clone().unwrap_or_default(),
+            channel_id: experiment.channel_id.clone(),
+            occurred_at: experiment.updated_at.unwrap_or_else(Utc::now),
+            data: serde_json::json!({ "experiment": experiment }),
+        }
+    }
+
+    pub fn deleted(experiment_id: &str, channel_id: &str, occurred_at: DateTime<Utc>) -> Self {
+        Self {
+            kind: Kind::Deleted,
+            experiment_id: experiment_id.to_owned(),
+            channel_id: channel_id.to_owned(),
+            occurred_at,
+            data: serde_json::json!({}),
+        }
+    }
+
+    pub fn status_changed(
+        experiment_id: &str,
+        channel_id: &str,
+        from: Status,
+        to: Status,
+        occurred_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            kind: Kind::StatusChanged,
+            experiment_id: experiment_id.to_owned(),
+            channel_id: channel_id.to_owned(),
+            occurred_at,
+            data: serde_json::json!({ "from": from, "to": to }),
+        }
+    }
+
+    pub fn killed(experiment: &Experiment, kill_switch: &KillSwitch) -> Self {
+        Self {
+            kind: Kind::Killed,
+            experiment_id: experiment.id.clone().unwrap_or_default(),
+            channel_id: experiment.channel_id.clone(),
+            occurred_at: kill_switch.triggered_at,
+            data: serde_json::json!({
+                "scope": kill_switch.scope,
+                "reason": kill_switch.reason,
+                "triggered_by": kill_switch.triggered_by,
+            }),
+        }
+    }
+
+    pub fn weights_changed(experiment: &Experiment, occurred_at: DateTime<Utc>) -> Self {
+        let weights: HashMap<&str, i32> = experiment
+            .variations
+            .iter()
+            .map(|v| (v.indicator.as_str(), v.weight))
+            .collect();
+
+        Self {
+            kind: Kind::WeightsChanged,
+            experiment_id: experiment.id.clone().unwrap_or_default(),
+            channel_id: experiment.channel_id.clone(),
+            occurred_at,
+            data: serde_json::json!({ "weights": weights }),
+        }
+    }
+
+    /// Event of a webhook check, about no experiment.
+    pub fn test(channel_id: &str) -> Self {
+        Self {
+            kind: Kind::Test,
+            experiment_id: String::default(),
+            channel_id: channel_id.to_owned(),
+            occurred_at: Utc::now(),
+            data: serde_json::json!({}),
+        }
+    }
+
+    pub fn released(experiment: &Experiment, released_by: Option<serde_json::Value>) -> Self {
+        Self {
+            kind: Kind::Released,
+            experiment_id: experiment.id.clone().unwrap_or_default(),
+            channel_id: experiment.channel_id.clone(),
+            occurred_at: Utc::now(),
+            data: serde_json::json!({ "released_by": released_by }),
+        }
+    }
+}
+
+/// Defined the contract of where the events are sent to.
+#[automock]
+#[async_trait]
+pub trait Publisher {
+    async fn publish(&self, event: &Event) -> Result<()>;
+}
+
+/// Publisher which only writes the events to the standard output.
+#[derive(Debug, Default, Clone)]
+pub struct LogPublisher;
+
+#[async_trait]
+impl Publisher for LogPublisher {
+    async fn publish(&self, event: &Event) -> Result<()> {
+        println!("event: {}", serde_json::to_string(event)?);
+        Ok(())
+    }
+}
+
+/// Publisher handing the events to each of its publishers, fails when one of them fails.
+pub struct Fanout(pub Vec<Box<dyn Publisher + Send + Sync>>);
+
+#[async_trait]
+impl Publisher for Fanout {
+    async fn publish(&self, event: &Event) -> Result<()> {
+        let mut errors = vec![];
+        for publisher in &self.0 {
+            if let Err(e) = publisher.publish(event).await {
+                errors.push(e.to_string());
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(anyhow::anyhow!(errors.join("; ")))
+        }
+    }
+}
+
+/// Publisher appending the events to a file, a json document per line.
+#[derive(Debug)]
+pub struct FilePublisher {
+    path: PathBuf,
+    lock: Arc<Mutex<()>>,
+}
+
+impl FilePublisher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+}
+
+#[async_trait]
+impl Publisher for FilePublisher {
+    async fn publish(&self, event: &Event) -> Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let (path, lock) = (self.path.clone(), self.lock.clone());
+        actix_web::rt::task::spawn_blocking(move || -> Result<()> {
+            let _guard = lock.lock().unwrap();
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            file.write_all(&line)?;
+            Ok(())
+        })
+        .await?
+    }
+}
+
+/// Publisher posting the events as json to an url, a response other than 2xx is a failure.
+#[derive(Debug, Clone)]
+pub struct HttpPublisher {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl HttpPublisher {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(timeout).build()?,
+            url: url.to_owned(),
+        })
+    }
+}
+
+#[async_trait]
+impl Publisher for HttpPublisher {
+    async fn publish(&self, event: &Event) -> Result<()> {
+        self.client
+            .post(&self.url)
+            .json(event)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> Event {
+        Event::deleted("62bb13dfea2b3ea78771e305", "channel_a", Utc::now())
+    }
+
+    #[actix_web::test]
+    async fn test_fanout() {
+        let mut ok = MockPublisher::new();
+        ok.expect_publish().times(1).returning(|_| Ok(()));
+        let mut failing = MockPublisher::new();
+        failing
+            .expect_publish()
+            .times(1)
+            .returning(|_| Err(anyhow::anyhow!("unreachable")));
+
+        let fanout = Fanout(vec![Box::new(failing), Box::new(ok)]);
+        let err = fanout.publish(&event()).await.unwrap_err();
+        assert_eq!(err.to_string(), "unreachable");
+    }
+
+    #[actix_web::test]
+    async fn test_file_publisher() {
+        let path = std::env::temp_dir().join(format!(
+            "enigma-events-{}.jsonl",
+            bson::oid::ObjectId::new().to_hex()
+        ));
+        let publisher = FilePublisher::new(&path);
+
+        publisher.publish(&event()).await.unwrap();
+        publisher.publish(&event()).await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<Event> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].kind, Kind::Deleted);
+    }
+
+    #[actix_web::test]
+    async fn test_http_publisher() {
+        use actix_web::{web, App, HttpResponse, HttpServer};
+
+        let received = web::Data::new(Mutex::new(Vec::<Event>::new()));
+        let state = received.clone();
+        let server = HttpServer::new(move || {
+            App::new().app_data(state.clone()).route(
+                "/ok",
+                web::post().to(
+                    |e: web::Json<Event>, received: web::Data<Mutex<Vec<Event>>>| async move {
+                        received.lock().unwrap().push(e.into_inner());
+                        HttpResponse::NoContent().finish()
+                    },
+                ),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        let handle = server.run();
+        actix_web::rt::spawn(handle);
+
+        let timeout = Duration::from_secs(5);
+        let ok = HttpPublisher::new(&format!("http://{}/ok", addr), timeout).unwrap();
+        ok.publish(&event()).await.unwrap();
+        assert_eq!(received.lock().unwrap()[0].kind, Kind::Deleted);
+
+        let missing = HttpPublisher::new(&format!("http://{}/missing", addr), timeout).unwrap();
+        assert!(missing.publish(&event()).await.is_err());
+    }
+}
@@ -1,14 +1,26 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use anyhow::Result;
 use async_trait::async_trait;
-use chrono::{serde::ts_milliseconds_option, DateTime, Utc};
+use chrono::{
+    serde::{ts_milliseconds, ts_milliseconds_option},
+    DateTime, Utc,
+};
 use derive_more::{Display, Error};
 use mockall::automock;
 use serde::{Deserialize, Serialize};
 use serde_json;
+use sha2::{Digest, Sha256};
 use validator::Validate;
 
+use super::assignment;
+use super::bandit::{self, Bandit};
+use super::factorial::{self, Factorial};
+use super::key;
+use super::projection::Projection;
+use super::search;
+use super::tag::{self, TagCount};
+
 ///
 /// Defined struct represents the experiment data uses in the service.
 ///
@@ -16,27 +28,111 @@ use validator::Validate;
 #[derive(Debug, Default, Validate, Serialize, Deserialize, Clone)]
 pub struct Experiment {
     pub id: Option<String>,
+    /// Readable reference of the experiment, unique in the channel, generated from the name
+    /// when none is given.
+    #[serde(default)]
+    #[validate(custom = "key::validate_key")]
+    pub key: String,
     #[validate(length(min = 4, max = 100, message = "must have length between 4 - 100"))]
     pub name: String,
     #[validate(length(max = 500, message = "must have length atmost 500"))]
     pub description: String,
     pub active_interval: Option<Interval>,
+    #[serde(default)]
+    #[validate(custom = "tag::validate_tags")]
+    pub tags: Vec<String>,
 
     #[validate]
     pub variations: Vec<Variance>,
+    #[validate]
     pub classing: Classing,
+    /// Design the variations are generated from, kept for the analysis.
+    #[serde(default)]
+    pub factorial: Option<Factorial>,
 
     pub owner: Option<serde_json::Value>,
+    #[serde(default)]
     pub channel_id: String,
 
-    #[serde(with = "ts_milliseconds_option")]
+    #[serde(default)]
+    pub status: Status,
+    #[serde(default)]
+    pub kill_switch: Option<KillSwitch>,
+
+    #[serde(default, with = "ts_milliseconds_option")]
     pub created_at: Option<DateTime<Utc>>,
-    #[serde(with = "ts_milliseconds_option")]
+    #[serde(default, with = "ts_milliseconds_option")]
     pub updated_at: Option<DateTime<Utc>>,
-    #[serde(with = "ts_milliseconds_option")]
+    #[serde(default, with = "ts_milliseconds_option")]
     pub deleted_at: Option<DateTime<Utc>>,
 }
 
+impl Experiment {
+    /// Returns the status the experiment should move to at `now` according to its
+    /// `active_interval`, or `None` when it is already where it belongs.
+    pub fn next_status(&self, now: DateTime<Utc>) -> Option<Status> {
+        let (start, end) = match &self.active_interval {
+            Some(Interval(start, end)) => (*start, *end),
+            None => (None, None),
+        };
+        let is_closed = end.map(|end| end <= now).unwrap_or(false);
+        let is_opened = start.map(|start| start <= now).unwrap_or(true);
+
+        // a killed experiment is frozen where it is until the kill switch is released.
+        if self.kill_switch.is_some() {
+            return None;
+        }
+
+        match self.status {
+            Status::Scheduled | Status::Running | Status::Paused if is_closed => {
+                Some(Status::Ended)
+            }
+            Status::Scheduled if is_opened => Some(Status::Running),
+            _ => None,
+        }
+    }
+
+    /// Returns the control variation, the one indicated as `control` or the first one.
+    pub fn control(&self) -> Option<&Variance> {
+        self.variations
+            .iter()
+            .find(|v| v.indicator == CONTROL_INDICATOR)
+            .or_else(|| self.variations.first())
+    }
+}
+
+/// Indicator of the variation which is served when an experiment is not running.
+pub const CONTROL_INDICATOR: &str = "control";
+
+/// Lifecycle of an experiment, driven by its `active_interval`.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    #[default]
+    Scheduled,
+    Running,
+    Paused,
+    Ended,
+}
+
+/// Record of who forced an experiment back to its control variation and why.
+#[derive(Debug, Serialize, Deserialize, Validate, Clone, PartialEq)]
+pub struct KillSwitch {
+    pub scope: KillScope,
+    #[validate(length(min = 1, max = 500, message = "must have length between 1 - 500"))]
+    pub reason: String,
+    pub triggered_by: Option<serde_json::Value>,
+    #[serde(with = "ts_milliseconds")]
+    pub triggered_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KillScope {
+    Experiment,
+    Channel,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Interval(pub Option<DateTime<Utc>>, pub Option<DateTime<Utc>>);
 
@@ -51,12 +147,250 @@ pub struct Variance {
     #[validate(range(min = 0, max = 100, message = "value must bound between 0 - 100"))]
     pub weight: i32,
     pub values: HashMap<String, serde_json::Value>,
+    /// Level of each factor the variation is made of, empty unless generated from a factorial design.
+    #[serde(default)]
+    pub levels: HashMap<String, String>,
 }
 
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+#[derive(Debug, Default, Serialize, Deserialize, Validate, Clone)]
 pub struct Classing {
     pub strategy: String,
     pub persistent_mode: String,
+    /// Settings and state of the bandit when the strategy is `bandit`.
+    #[serde(default)]
+    #[validate]
+    pub bandit: Option<Bandit>,
+}
+
+/// Upper bound of the experiments returned per page.
+pub const MAX_PAGE_LIMIT: i64 = 200;
+
+/// Defined struct represents the criteria to list the experiments of a channel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListQuery {
+    pub limit: i64,
+    /// Position after which the page starts, returned as `next_cursor` by the previous page.
+    pub cursor: Option<Cursor>,
+    pub sort: SortField,
+    pub order: SortOrder,
+
+    pub status: Option<Status>,
+    pub owner_id: Option<String>,
+    pub name_prefix: Option<String>,
+    pub tag: Option<String>,
+    /// Fields of the experiments to load, every field when none.
+    pub fields: Option<Projection>,
+    /// Only the experiments whose active interval contains this time.
+    pub active_at: Option<DateTime<Utc>>,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        Self {
+            limit: 50,
+            cursor: None,
+            sort: SortField::default(),
+            order: SortOrder::default(),
+            status: None,
+            owner_id: None,
+            name_prefix: None,
+            tag: None,
+            fields: None,
+            active_at: None,
+        }
+    }
+}
+
+impl ListQuery {
+    /// Digest of the sort and the filters of the query, the cursors only continue the query
+    /// they were issued for.
+    pub fn fingerprint(&self) -> String {
+        let json = serde_json::json!([
+            self.sort,
+            self.order,
+            self.status,
+            self.owner_id,
+            self.name_prefix,
+            self.tag,
+            self.active_at.map(|at| at.timestamp_millis()),
+        ]);
+        Sha256::digest(json.to_string().as_bytes())
+            .iter()
+            .take(8)
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    Name,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+/// Position in a sorted listing, the sort key and the id of the last experiment of a page.
+/// The key is in the representation of the store which produced it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Cursor {
+    pub key: serde_json::Value,
+    pub id: String,
+    /// Fingerprint of the query the cursor was issued for.
+    pub query: String,
+}
+
+impl Cursor {
+    /// Encode the cursor into the opaque string handed to the clients.
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| {
+                UserError::ValidationError {
+                    message: "invalid cursor".to_owned(),
+                }
+                .into()
+            })
+    }
+}
+
+/// A change of a batch, resolved against the stored experiments.
+#[derive(Debug, Clone)]
+pub enum Write {
+    Insert(Experiment),
+    Replace(Experiment),
+    Delete { id: String, channel_id: String },
+}
+
+/// A change made in place to the stored experiment, the rest of it is left as stored so the
+/// changes made to it concurrently are kept.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Patch {
+    /// Move the experiment to `to` while it is still in `from` and not killed.
+    Status { from: Status, to: Status },
+    /// Add the rewards to the counters of the bandit of the experiment.
+    Rewards(HashMap<String, bandit::Reward>),
+    /// Give the variations the recomputed weights and record them in the history of the
+    /// bandit, while it is running, not killed nor frozen.
+    Weights(bandit::WeightChange),
+    /// Remove then add the tags, while the experiment ends up with `tag::MAX_TAGS` at most.
+    Tags {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Kill the experiment, the channel kill switch only while it has not ended and is not
+    /// killed already.
+    Kill(KillSwitch),
+    /// Release the kill switch of the experiment, only the one of the scope when given.
+    Release(Option<KillScope>),
+    /// Freeze the weights of the bandit of the experiment, or let them be recomputed again.
+    Freeze(bool),
+}
+
+impl Patch {
+    /// Apply the patch to the experiment, returns false leaving it untouched when it is not
+    /// in the state the patch expects.
+    pub fn apply(&self, experiment: &mut Experiment) -> bool {
+        match self {
+            Patch::Status { from, to } => {
+                if experiment.status != *from || experiment.kill_switch.is_some() {
+                    return false;
+                }
+                experiment.status = *to;
+            }
+            Patch::Rewards(rewards) => match experiment.classing.bandit.as_mut() {
+                Some(bandit) => bandit::add_to(bandit, rewards),
+                None => return false,
+            },
+            Patch::Weights(change) => {
+                let is_frozen = match experiment.classing.bandit {
+                    Some(ref bandit) => bandit.frozen,
+                    None => return false,
+                };
+                if experiment.status != Status::Running
+                    || experiment.kill_switch.is_some()
+                    || is_frozen
+                {
+                    return false;
+                }
+                bandit::apply_weights(experiment, change);
+            }
+            Patch::Tags { add, remove } => {
+                let tags = tag::merge(&experiment.tags, add, remove);
+                if tags.len() > tag::MAX_TAGS {
+                    return false;
+                }
+                experiment.tags = tags;
+            }
+            Patch::Kill(kill_switch) => {
+                if kill_switch.scope == KillScope::Channel
+                    && (experiment.status == Status::Ended || experiment.kill_switch.is_some())
+                {
+                    return false;
+                }
+                experiment.kill_switch = Some(kill_switch.clone());
+            }
+            Patch::Release(scope) => match experiment.kill_switch {
+                Some(ref ks) if scope.map(|s| s == ks.scope).unwrap_or(true) => {
+                    experiment.kill_switch = None;
+                }
+                _ => return false,
+            },
+            Patch::Freeze(frozen) => match experiment.classing.bandit.as_mut() {
+                Some(bandit) => bandit.frozen = *frozen,
+                None => return false,
+            },
+        }
+        true
+    }
+}
+
+/// Version of a stored experiment, the `updated_at` it was read with. A write expecting a
+/// version fails with `StoreError::Stale` once the experiment has changed since.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Version(pub Option<DateTime<Utc>>);
+
+impl Version {
+    pub fn of(experiment: &Experiment) -> Self {
+        Self(experiment.updated_at)
+    }
+
+    /// `updated_at` of the version following this one, a millisecond later at least so the
+    /// versions stay distinct once stored in milliseconds.
+    pub fn next(&self) -> DateTime<Utc> {
+        let now = Utc::now();
+        match self.0 {
+            Some(at) if now.timestamp_millis() <= at.timestamp_millis() => {
+                at + chrono::Duration::milliseconds(1)
+            }
+            _ => now,
+        }
+    }
+}
+
+/// A page of experiments.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct Page {
+    pub items: Vec<Experiment>,
+    /// Cursor of the next page, none on the last page.
+    pub next_cursor: Option<String>,
+    /// Number of the experiments matching the filters, regardless of the pagination.
+    pub total: u64,
 }
 
 ///
@@ -88,6 +422,13 @@ pub enum StoreError {
     InvalidInput {
         message: String,
     },
+    #[display(fmt = "{}", message)]
+    Conflict {
+        message: String,
+    },
+    /// The experiment has changed since the version the write expects.
+    #[display(fmt = "the experiment has changed, reload it and try again")]
+    Stale,
 }
 
 impl StoreError {
@@ -97,6 +438,8 @@ impl StoreError {
             StoreError::DocumentNotfound => "document_notfound".to_owned(),
             StoreError::UnauthorizedAccess => "unauthorized_access".to_owned(),
             StoreError::InvalidInput { message: _ } => "invalid_input".to_owned(),
+            StoreError::Conflict { message: _ } => "conflict".to_owned(),
+            StoreError::Stale => "stale".to_owned(),
         }
     }
 }
@@ -110,18 +453,51 @@ impl StoreError {
 #[async_trait]
 pub trait Store {
     async fn save(&self, data: &mut Experiment) -> Result<String>;
-    async fn list(&self, channel_id: &str) -> Result<Vec<Experiment>>;
+    async fn list(&self, channel_id: &str, query: &ListQuery) -> Result<Page>;
     async fn get(&self, id: &str, channel_id: &str) -> Result<Experiment>;
+    /// Get the experiment as stored, bypassing any cache, for the reads a write is based on.
+    async fn get_fresh(&self, id: &str, channel_id: &str) -> Result<Experiment>;
+    async fn get_by_key(&self, key: &str, channel_id: &str) -> Result<Experiment>;
+    /// Get the experiment loading only the fields of the projection, the fields left out
+    /// have their default value. Stores which cannot project may load every field.
+    async fn get_projected(
+        &self,
+        id: &str,
+        channel_id: &str,
+        projection: &Projection,
+    ) -> Result<Experiment>;
+    async fn update(&self, data: &mut Experiment) -> Result<()>;
+    /// Update the experiment only while it is still at the version.
+    async fn update_if(&self, data: &mut Experiment, version: Version) -> Result<()>;
+    /// Patch the experiment in place, returns it patched or none when it is not in the state
+    /// the patch expects.
+    async fn patch(&self, id: &str, channel_id: &str, patch: &Patch) -> Result<Option<Experiment>>;
+    /// Apply every write or none of them, inserted experiments get their id. Returns false
+    /// without writing anything when the store cannot apply them in a transaction.
+    async fn write_atomically(&self, writes: &mut [Write]) -> Result<bool>;
     async fn delete(&self, id: &str, channel_id: &str) -> Result<()>;
+    /// Delete the experiment only while it is still at the version.
+    async fn delete_if(&self, id: &str, channel_id: &str, version: Version) -> Result<()>;
+    /// List experiments of every channel which are in one of the given statuses, by their id
+    /// from the one after `after` if any, `limit` at most.
+    async fn list_by_status(
+        &self,
+        statuses: &[Status],
+        after: Option<String>,
+        limit: i64,
+    ) -> Result<Vec<Experiment>>;
+    /// Search the experiments of the channel by words, the most relevant first.
+    async fn search(&self, channel_id: &str, q: &str, limit: i64) -> Result<Vec<search::Hit>>;
+    /// Count the experiments of the channel per tag, the most used first.
+    async fn tag_counts(&self, channel_id: &str) -> Result<Vec<TagCount>>;
 }
 
-///
-/// Service's interface expose to the other package to use it.
-///
-
-pub async fn create(repo: &impl Store, data: Experiment) -> Result<Experiment> {
-    let mut data = data.clone();
+//
+// Service's interface expose to the other package to use it.
+//
 
+/// Check the experiment is valid to be stored.
+pub fn check(data: &Experiment) -> Result<()> {
     if let Err(e) = data.validate() {
         return Err(UserError::ValidationError {
             message: e.to_string(),
@@ -129,33 +505,421 @@ pub async fn create(repo: &impl Store, data: Experiment) -> Result<Experiment> {
         .into());
     }
 
-    match repo.save(&mut data).await {
-        Ok(inserted_id) => {
-            data.id = Some(inserted_id);
+    assignment::PersistentMode::parse(&data.classing.persistent_mode)?;
+
+    bandit::check_bounds(data)?;
 
-            Ok(data)
+    if (data.classing.strategy == bandit::STRATEGY) != data.classing.bandit.is_some() {
+        return Err(UserError::ValidationError {
+            message: format!(
+                "bandit settings are required with, and only with, the `{}` strategy",
+                bandit::STRATEGY
+            ),
         }
-        Err(err) => match err.downcast_ref::<StoreError>() {
-            Some(_) => todo!(),
-            None => todo!(),
-        },
+        .into());
     }
+
+    Ok(())
 }
 
-pub async fn list(repo: &impl Store, channel_id: &str) -> Result<Vec<Experiment>> {
-    match repo.list(channel_id).await {
-        Ok(experiments) => Ok(experiments),
-        Err(err) => Err(err),
+/// Generate the variations of the factorial design, if any, and check the new experiment.
+pub fn prepare(data: &mut Experiment) -> Result<()> {
+    if let Some(ref design) = data.factorial {
+        if !data.variations.is_empty() {
+            return Err(UserError::ValidationError {
+                message: "variations are generated from the factorial design".to_owned(),
+            }
+            .into());
+        }
+        data.variations = factorial::design(design)?;
+    }
+
+    check(data)
+}
+
+pub async fn create(repo: &impl Store, data: Experiment) -> Result<Experiment> {
+    let mut data = data.clone();
+
+    if data.key.is_empty() {
+        data.key = key::generate(repo, &data.channel_id, &data.name, &HashSet::new()).await?;
+    } else {
+        key::ensure_free(repo, &data.channel_id, &data.key, None).await?;
+    }
+    prepare(&mut data)?;
+
+    // store errors go up as they are, a key taken in between is a conflict.
+    let inserted_id = repo.save(&mut data).await?;
+    data.id = Some(inserted_id);
+
+    Ok(data)
+}
+
+/// Keep on the new definition of the experiment what is not part of the definition: its
+/// identity, owner, lifecycle and the state of its bandit.
+pub fn carry_over(current: &Experiment, data: &mut Experiment) {
+    data.id = current.id.clone();
+    if data.key.is_empty() {
+        data.key = current.key.clone();
+    }
+    data.channel_id = current.channel_id.clone();
+    data.owner = current.owner.clone();
+    data.status = current.status;
+    data.kill_switch = current.kill_switch.clone();
+    data.created_at = current.created_at;
+
+    if let (Some(new), Some(old)) = (&mut data.classing.bandit, &current.classing.bandit) {
+        new.frozen = old.frozen;
+        new.rewards = old.rewards.clone();
+        new.history = old.history.clone();
+        new.recomputed_at = old.recomputed_at;
+    }
+}
+
+/// Replace the definition of the `current` experiment with the one of `data`.
+pub async fn update(
+    repo: &impl Store,
+    current: &Experiment,
+    data: Experiment,
+) -> Result<Experiment> {
+    let mut data = data;
+
+    carry_over(current, &mut data);
+    if data.key.is_empty() {
+        data.key = key::generate(repo, &data.channel_id, &data.name, &HashSet::new()).await?;
+    }
+    prepare(&mut data)?;
+
+    // the definition replaces the one read as `current`, not a later one.
+    repo.update_if(&mut data, Version::of(current)).await?;
+
+    Ok(data)
+}
+
+pub async fn list(repo: &impl Store, channel_id: &str, query: &ListQuery) -> Result<Page> {
+    if query.limit < 1 || query.limit > MAX_PAGE_LIMIT {
+        return Err(UserError::ValidationError {
+            message: format!("limit must bound between 1 - {}", MAX_PAGE_LIMIT),
+        }
+        .into());
+    }
+    if let Some(cursor) = &query.cursor {
+        if cursor.query != query.fingerprint() {
+            return Err(UserError::ValidationError {
+                message: "cursor was issued for another sort or filters".to_owned(),
+            }
+            .into());
+        }
+    }
+
+    repo.list(channel_id, query).await
+}
+
+/// List every experiment of the channel by walking through the pages.
+pub async fn list_all(repo: &impl Store, channel_id: &str) -> Result<Vec<Experiment>> {
+    let mut query = ListQuery {
+        limit: MAX_PAGE_LIMIT,
+        ..Default::default()
+    };
+
+    let mut experiments = vec![];
+    loop {
+        let page = repo.list(channel_id, &query).await?;
+        experiments.extend(page.items);
+
+        match page.next_cursor {
+            Some(cursor) => query.cursor = Some(Cursor::decode(&cursor)?),
+            None => return Ok(experiments),
+        }
     }
 }
 
+/// Get the experiment by its id or its key.
 pub async fn get(repo: &impl Store, id: &str, channel_id: &str) -> Result<Experiment> {
+    if !key::looks_like_id(id) {
+        return repo.get_by_key(id, channel_id).await;
+    }
+
     match repo.get(id, channel_id).await {
         Ok(experiment) => Ok(experiment),
         Err(err) => Err(err),
     }
 }
 
-pub async fn delete(repo: &impl Store, id: &str, channel_id: &str) -> Result<()> {
-    repo.delete(id, channel_id).await
+/// Get the experiment by its id or its key as stored rather than cached, for the reads a
+/// write is based on.
+pub async fn get_fresh(repo: &impl Store, id: &str, channel_id: &str) -> Result<Experiment> {
+    if key::looks_like_id(id) {
+        return repo.get_fresh(id, channel_id).await;
+    }
+
+    // the key may have moved to another experiment since it was cached.
+    let experiment = repo
+        .get_fresh(&resolve_id(repo, id, channel_id).await?, channel_id)
+        .await?;
+    if experiment.key != id {
+        return Err(StoreError::DocumentNotfound.into());
+    }
+    Ok(experiment)
+}
+
+/// Returns the id of the experiment referenced by its id or its key.
+pub async fn resolve_id(repo: &impl Store, id: &str, channel_id: &str) -> Result<String> {
+    if key::looks_like_id(id) {
+        return Ok(id.to_owned());
+    }
+
+    let experiment = repo.get_by_key(id, channel_id).await?;
+    Ok(experiment.id.unwrap_or_default())
+}
+
+/// Delete the experiment by its id or its key, returns its id.
+pub async fn delete(repo: &impl Store, id: &str, channel_id: &str) -> Result<String> {
+    let id = resolve_id(repo, id, channel_id).await?;
+    repo.delete(&id, channel_id).await?;
+    Ok(id)
+}
+
+/// Delete the experiment only while it is still at the version, returns its id.
+pub async fn delete_if(repo: &impl Store, current: &Experiment) -> Result<String> {
+    let id = current.id.clone().unwrap_or_default();
+    repo.delete_if(&id, &current.channel_id, Version::of(current))
+        .await?;
+    Ok(id)
+}
+
+pub async fn kill(
+    repo: &impl Store,
+    id: &str,
+    channel_id: &str,
+    kill_switch: KillSwitch,
+) -> Result<Experiment> {
+    validate_kill_switch(&kill_switch)?;
+
+    let patch = Patch::Kill(kill_switch);
+    match repo.patch(id, channel_id, &patch).await? {
+        Some(experiment) => Ok(experiment),
+        None => repo.get(id, channel_id).await,
+    }
+}
+
+/// Release the kill switch of the experiment, one which is not killed is returned as it is.
+pub async fn release(repo: &impl Store, id: &str, channel_id: &str) -> Result<Experiment> {
+    match repo.patch(id, channel_id, &Patch::Release(None)).await? {
+        Some(experiment) => Ok(experiment),
+        None => repo.get(id, channel_id).await,
+    }
+}
+
+/// Experiments of a channel changed one by one, the failure of one leaves the others changed.
+#[derive(Debug, Default, Serialize, Deserialize, Clone)]
+pub struct ChannelChange {
+    pub changed: Vec<Experiment>,
+    /// The experiments left as they were by an error.
+    pub failed: Vec<Failure>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Failure {
+    pub id: String,
+    pub message: String,
+}
+
+impl ChannelChange {
+    async fn patch_each(repo: &impl Store, experiments: Vec<Experiment>, patch: &Patch) -> Self {
+        let mut change = Self::default();
+        for experiment in experiments {
+            let id = experiment.id.unwrap_or_default();
+            match repo.patch(&id, &experiment.channel_id, patch).await {
+                Ok(Some(experiment)) => change.changed.push(experiment),
+                // changed in between, e.g. killed or ended, it is left alone.
+                Ok(None) => {}
+                Err(e) => change.failed.push(Failure {
+                    id,
+                    message: e.to_string(),
+                }),
+            }
+        }
+        change
+    }
+}
+
+/// Kill every experiment of the channel which has not ended yet and is not already killed,
+/// each in place so the changes made to them meanwhile are kept.
+pub async fn kill_channel(
+    repo: &impl Store,
+    channel_id: &str,
+    kill_switch: KillSwitch,
+) -> Result<ChannelChange> {
+    validate_kill_switch(&kill_switch)?;
+
+    let experiments = list_all(repo, channel_id)
+        .await?
+        .into_iter()
+        .filter(|e| e.status != Status::Ended && e.kill_switch.is_none())
+        .collect();
+
+    Ok(ChannelChange::patch_each(repo, experiments, &Patch::Kill(kill_switch)).await)
+}
+
+/// Release the experiments killed by the channel kill switch, experiments killed
+/// individually stay killed.
+pub async fn release_channel(repo: &impl Store, channel_id: &str) -> Result<ChannelChange> {
+    let experiments = list_all(repo, channel_id)
+        .await?
+        .into_iter()
+        .filter(|e| matches!(e.kill_switch, Some(ref ks) if ks.scope == KillScope::Channel))
+        .collect();
+
+    let patch = Patch::Release(Some(KillScope::Channel));
+    Ok(ChannelChange::patch_each(repo, experiments, &patch).await)
+}
+
+fn validate_kill_switch(kill_switch: &KillSwitch) -> Result<()> {
+    kill_switch.validate().map_err(|e| {
+        UserError::ValidationError {
+            message: e.to_string(),
+        }
+        .into()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::memory;
+
+    fn experiment(key: &str) -> Experiment {
+        Experiment {
+            key: key.to_owned(),
+            name: "checkout button".to_owned(),
+            channel_id: "channel".to_owned(),
+            classing: Classing {
+                strategy: "random".to_owned(),
+                persistent_mode: "none".to_owned(),
+                bandit: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_create_duplicate_key() {
+        let repo = memory::Repo::new();
+        create(&repo, experiment("dup-key")).await.unwrap();
+
+        let err = create(&repo, experiment("dup-key")).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::Conflict { .. })
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_create_save_error() {
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(StoreError::DocumentNotfound.into()));
+        mock_store.expect_save().returning(|_| {
+            Err(StoreError::InternalError {
+                message: "unavailable".to_owned(),
+            }
+            .into())
+        });
+
+        let err = create(&mock_store, experiment("")).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<StoreError>(),
+            Some(StoreError::InternalError { .. })
+        ));
+    }
+
+    fn kill_switch(scope: KillScope) -> KillSwitch {
+        KillSwitch {
+            scope,
+            reason: "incident".to_owned(),
+            triggered_by: None,
+            triggered_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_patch_kill_and_release() {
+        let mut data = experiment("killed");
+        assert!(!Patch::Release(None).apply(&mut data));
+
+        assert!(Patch::Kill(kill_switch(KillScope::Experiment)).apply(&mut data));
+        // the channel kill switch leaves the killed experiments alone.
+        assert!(!Patch::Kill(kill_switch(KillScope::Channel)).apply(&mut data));
+        assert!(!Patch::Release(Some(KillScope::Channel)).apply(&mut data));
+        assert!(Patch::Release(None).apply(&mut data));
+        assert!(data.kill_switch.is_none());
+
+        data.status = Status::Ended;
+        assert!(!Patch::Kill(kill_switch(KillScope::Channel)).apply(&mut data));
+        assert!(Patch::Kill(kill_switch(KillScope::Experiment)).apply(&mut data));
+    }
+
+    #[actix_web::test]
+    async fn test_kill_keeps_the_concurrent_changes() {
+        let repo = memory::Repo::new();
+        let created = create(&repo, experiment("concurrent")).await.unwrap();
+        let id = created.id.clone().unwrap();
+
+        // tagged after the experiment to kill was read.
+        let tags = Patch::Tags {
+            add: vec!["checkout".to_owned()],
+            remove: vec![],
+        };
+        repo.patch(&id, "channel", &tags).await.unwrap();
+
+        let killed = kill(&repo, &id, "channel", kill_switch(KillScope::Experiment))
+            .await
+            .unwrap();
+        assert!(killed.kill_switch.is_some());
+        assert_eq!(killed.tags, vec!["checkout".to_owned()]);
+    }
+
+    #[actix_web::test]
+    async fn test_kill_channel_reports_the_failures() {
+        let live = |id: &str| Experiment {
+            id: Some(id.to_owned()),
+            ..experiment(id)
+        };
+        let page = Page {
+            items: vec![live("failing"), live("killed")],
+            next_cursor: None,
+            total: 2,
+        };
+
+        let mut mock_store = MockStore::new();
+        mock_store.expect_list().return_once(move |_, _| Ok(page));
+        mock_store
+            .expect_patch()
+            .times(2)
+            .returning(move |id, _, patch| {
+                if id == "failing" {
+                    return Err(StoreError::InternalError {
+                        message: "unavailable".to_owned(),
+                    }
+                    .into());
+                }
+                let mut data = live(id);
+                patch.apply(&mut data);
+                Ok(Some(data))
+            });
+
+        let change = kill_channel(&mock_store, "channel", kill_switch(KillScope::Channel))
+            .await
+            .unwrap();
+        assert_eq!(change.changed.len(), 1);
+        assert!(change.changed[0].kill_switch.is_some());
+        assert_eq!(
+            change.failed,
+            vec![Failure {
+                id: "failing".to_owned(),
+                message: "unavailable".to_owned(),
+            }]
+        );
+    }
 }
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json;
+use validator::Validate;
+
+use super::experiment::{UserError, Variance, CONTROL_INDICATOR};
+
+/// Upper bound of the generated variations, keeps the traffic per cell meaningful.
+pub const MAX_VARIATIONS: usize = 64;
+
+/// Defined struct represents a factorial design, every combination of the factors'
+/// levels becomes a variation.
+#[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+pub struct Factorial {
+    #[validate(length(min = 1, max = 6, message = "must have between 1 - 6 factors"))]
+    #[validate]
+    pub factors: Vec<Factor>,
+    #[serde(default)]
+    pub fraction: Fraction,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+pub struct Factor {
+    #[validate(length(min = 1, max = 32, message = "must have length between 1 - 32"))]
+    pub name: String,
+    #[validate(length(min = 2, max = 8, message = "must have between 2 - 8 levels"))]
+    #[validate]
+    pub levels: Vec<Level>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+pub struct Level {
+    #[validate(length(min = 1, max = 32, message = "must have length between 1 - 32"))]
+    pub name: String,
+    pub values: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Fraction {
+    /// Every combination of the levels.
+    #[default]
+    Full,
+    /// Half of the combinations of a two-level design, aliased through the
+    /// interaction of all the factors.
+    Half,
+}
+
+/// Generate the variations of the design. The combination of the first level of
+/// every factor is the control.
+pub fn design(factorial: &Factorial) -> Result<Vec<Variance>> {
+    if let Err(e) = factorial.validate() {
+        return Err(invalid(e.to_string()));
+    }
+
+    let mut combinations: Vec<Vec<usize>> = vec![vec![]];
+    for factor in factorial.factors.iter() {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|c| {
+                (0..factor.levels.len()).map(move |level| {
+                    let mut c = c.clone();
+                    c.push(level);
+                    c
+                })
+            })
+            .collect();
+    }
+
+    if factorial.fraction == Fraction::Half {
+        if factorial.factors.len() < 3 || factorial.factors.iter().any(|f| f.levels.len() != 2) {
+            return Err(invalid(
+                "half fraction requires at least 3 factors of 2 levels".to_owned(),
+            ));
+        }
+        // keep the runs sharing the sign of the control run in the highest order
+        // interaction, the defining relation is I = ±AB...K.
+        combinations.retain(|c| c.iter().sum::<usize>() % 2 == 0);
+    }
+
+    if combinations.len() > MAX_VARIATIONS {
+        return Err(invalid(format!(
+            "design generates {} variations, at most {} are allowed",
+            combinations.len(),
+            MAX_VARIATIONS
+        )));
+    }
+
+    combinations
+        .iter()
+        .map(|c| variance(&factorial.factors, c))
+        .collect()
+}
+
+fn variance(factors: &[Factor], combination: &[usize]) -> Result<Variance> {
+    let mut values = HashMap::new();
+    let mut levels = HashMap::new();
+    let mut names = vec![];
+
+    for (factor, &level) in factors.iter().zip(combination) {
+        let level = &factor.levels[level];
+        for (key, value) in level.values.iter() {
+            if values.insert(key.clone(), value.clone()).is_some() {
+                return Err(invalid(format!(
+                    "value `{}` is set by more than one factor",
+                    key
+                )));
+            }
+        }
+        levels.insert(factor.name.clone(), level.name.clone());
+        names.push(format!("{}={}", factor.name, level.name));
+    }
+
+    let indicator = if combination.iter().all(|&level| level == 0) {
+        CONTROL_INDICATOR.to_owned()
+    } else {
+        combination
+            .iter()
+            .map(|level| level.to_string())
+            .collect::<Vec<String>>()
+            .join("-")
+    };
+
+    Ok(Variance {
+        group_name: names.join(", "),
+        description: String::default(),
+        indicator,
+        weight: 1,
+        values,
+        levels,
+    })
+}
+
+fn invalid(message: String) -> anyhow::Error {
+    UserError::ValidationError { message }.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn factor(name: &str, levels: &[&str]) -> Factor {
+        Factor {
+            name: name.to_owned(),
+            levels: levels
+                .iter()
+                .map(|l| Level {
+                    name: l.to_string(),
+                    values: HashMap::from([(name.to_owned(), serde_json::json!(l))]),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_design() {
+        let mut factorial = Factorial {
+            factors: vec![
+                factor("color", &["red", "blue"]),
+                factor("size", &["s", "m", "l"]),
+            ],
+            fraction: Fraction::Full,
+        };
+
+        let variations = design(&factorial).unwrap();
+        assert_eq!(variations.len(), 6);
+        assert_eq!(variations[0].indicator, CONTROL_INDICATOR);
+        assert_eq!(variations[5].indicator, "1-2");
+        assert_eq!(variations[5].values["color"], serde_json::json!("blue"));
+        assert_eq!(variations[5].values["size"], serde_json::json!("l"));
+        assert_eq!(variations[5].levels["size"], "l");
+
+        assert!(design(&Factorial {
+            fraction: Fraction::Half,
+            ..factorial.clone()
+        })
+        .is_err());
+
+        factorial.factors = vec![
+            factor("color", &["red", "blue"]),
+            factor("size", &["s", "l"]),
+            factor("copy", &["short", "long"]),
+        ];
+        factorial.fraction = Fraction::Half;
+        let variations = design(&factorial).unwrap();
+        assert_eq!(variations.len(), 4);
+        assert_eq!(variations[0].indicator, CONTROL_INDICATOR);
+
+        factorial.factors.push(factor("badge", &["on"]));
+        assert!(design(&factorial).is_err());
+        factorial.factors.pop();
+        factorial.factors[0].levels[0].name = String::default();
+        assert!(design(&factorial).is_err());
+    }
+}
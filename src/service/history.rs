@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+
+use super::experiment::{Experiment, Status, StoreError, UserError};
+
+/// Defined struct represents a change of an experiment, the experiment is the fold of its
+/// changes in the order of their versions.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Change {
+    pub experiment_id: String,
+    pub channel_id: String,
+    /// Position of the change in the history of the experiment, starts at 1.
+    pub version: i64,
+    #[serde(flatten)]
+    pub kind: Kind,
+    #[serde(with = "ts_milliseconds")]
+    pub occurred_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", content = "data", rename_all = "snake_case")]
+pub enum Kind {
+    Created(Box<Experiment>),
+    Updated(Box<Experiment>),
+    StatusChanged { from: Status, to: Status },
+    Deleted,
+}
+
+impl Change {
+    /// Apply the change to the experiment as it was before, none when it did not exist.
+    pub fn apply(&self, experiment: Option<Experiment>) -> Result<Option<Experiment>> {
+        match (&self.kind, experiment) {
+            (Kind::Created(data), None) => Ok(Some(data.as_ref().clone())),
+            (Kind::Updated(data), Some(_)) => Ok(Some(data.as_ref().clone())),
+            (Kind::StatusChanged { to, .. }, Some(mut experiment)) => {
+                experiment.status = *to;
+                experiment.updated_at = Some(self.occurred_at);
+                Ok(Some(experiment))
+            }
+            (Kind::Deleted, Some(_)) => Ok(None),
+            _ => Err(anyhow::anyhow!(
+                "change {} of experiment {} does not apply",
+                self.version,
+                self.experiment_id
+            )),
+        }
+    }
+}
+
+/// Fold the changes of an experiment, given in the order of their versions.
+pub fn fold<'a>(changes: impl IntoIterator<Item = &'a Change>) -> Result<Option<Experiment>> {
+    let mut experiment = None;
+    for (i, change) in changes.into_iter().enumerate() {
+        if change.version != i as i64 + 1 {
+            return Err(anyhow::anyhow!(
+                "experiment {} misses the change {}",
+                change.experiment_id,
+                i + 1
+            ));
+        }
+        experiment = change.apply(experiment)?;
+    }
+    Ok(experiment)
+}
+
+/// Defined the contract of the append-only log of the changes.
+#[automock]
+#[async_trait]
+pub trait Log {
+    /// Append the change, fails with a conflict when its version is already taken.
+    async fn append(&self, change: &Change) -> Result<()>;
+    /// The changes of the experiment in the order of their versions.
+    async fn changes_of(&self, experiment_id: &str) -> Result<Vec<Change>>;
+    /// Every change, those of an experiment in the order of their versions.
+    async fn all(&self) -> Result<Vec<Change>>;
+}
+
+/// Log kept in the process memory, for the tests.
+#[derive(Debug, Default)]
+pub struct LocalLog {
+    changes: Mutex<HashMap<String, Vec<Change>>>,
+}
+
+#[async_trait]
+impl Log for LocalLog {
+    async fn append(&self, change: &Change) -> Result<()> {
+        let mut changes = self.changes.lock().unwrap();
+        let history = changes.entry(change.experiment_id.clone()).or_default();
+        if history.len() as i64 + 1 != change.version {
+            return Err(StoreError::Conflict {
+                message: format!(
+                    "version {} of experiment {} is taken",
+                    change.version, change.experiment_id
+                ),
+            }
+            .into());
+        }
+        history.push(change.clone());
+        Ok(())
+    }
+
+    async fn changes_of(&self, experiment_id: &str) -> Result<Vec<Change>> {
+        let changes = self.changes.lock().unwrap();
+        Ok(changes.get(experiment_id).cloned().unwrap_or_default())
+    }
+
+    async fn all(&self) -> Result<Vec<Change>> {
+        let changes = self.changes.lock().unwrap();
+        Ok(changes.values().flatten().cloned().collect())
+    }
+}
+
+/// Defined the contract to read the experiments as they were.
+#[automock]
+#[async_trait]
+pub trait Reader {
+    /// The experiment as it was at the time, after the changes which occurred until then.
+    async fn as_of(&self, id: &str, channel_id: &str, at: DateTime<Utc>) -> Result<Experiment>;
+}
+
+/// Reader of a store which does not keep the history.
+#[derive(Debug, Default)]
+pub struct NoHistory;
+
+#[async_trait]
+impl Reader for NoHistory {
+    async fn as_of(&self, _id: &str, _channel_id: &str, _at: DateTime<Utc>) -> Result<Experiment> {
+        Err(UserError::ValidationError {
+            message: "`as_of` needs the event-sourced store, this store keeps no history"
+                .to_owned(),
+        }
+        .into())
+    }
+}
+
+/// Read the experiment as it was from its changes.
+pub async fn as_of(
+    log: &(impl Log + ?Sized),
+    id: &str,
+    channel_id: &str,
+    at: DateTime<Utc>,
+) -> Result<Experiment> {
+    let changes = log.changes_of(id).await?;
+    if changes.first().is_some_and(|c| c.channel_id != channel_id) {
+        return Err(StoreError::UnauthorizedAccess.into());
+    }
+
+    let until: Vec<&Change> = changes.iter().filter(|c| c.occurred_at <= at).collect();
+    fold(until)?.ok_or_else(|| StoreError::DocumentNotfound.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn change(version: i64, kind: Kind, occurred_at: DateTime<Utc>) -> Change {
+        Change {
+            experiment_id: "62bb13dfea2b3ea78771e305".to_owned(),
+            channel_id: "channel_a".to_owned(),
+            version,
+            kind,
+            occurred_at,
+        }
+    }
+
+    fn experiment(name: &str) -> Box<Experiment> {
+        Box::new(Experiment {
+            id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+            name: name.to_owned(),
+            channel_id: "channel_a".to_owned(),
+            ..Default::default()
+        })
+    }
+
+    #[actix_web::test]
+    async fn test_as_of() {
+        let t0 = Utc::now() - chrono::Duration::hours(3);
+        let hour = chrono::Duration::hours(1);
+        let log = LocalLog::default();
+        for c in [
+            change(1, Kind::Created(experiment("checkout")), t0),
+            change(2, Kind::Updated(experiment("checkout v2")), t0 + hour),
+            change(
+                3,
+                Kind::StatusChanged {
+                    from: Status::Scheduled,
+                    to: Status::Running,
+                },
+                t0 + hour * 2,
+            ),
+            change(4, Kind::Deleted, t0 + hour * 3),
+        ] {
+            log.append(&c).await.unwrap();
+        }
+        let id = "62bb13dfea2b3ea78771e305";
+
+        let first = as_of(&log, id, "channel_a", t0).await.unwrap();
+        assert_eq!(first.name, "checkout");
+        let second = as_of(&log, id, "channel_a", t0 + hour).await.unwrap();
+        assert_eq!(second.name, "checkout v2");
+        let running = as_of(&log, id, "channel_a", t0 + hour * 2).await.unwrap();
+        assert_eq!(running.status, Status::Running);
+
+        let err = |r: Result<Experiment>| r.unwrap_err().downcast::<StoreError>().unwrap();
+        assert!(matches!(
+            err(as_of(&log, id, "channel_a", t0 - hour).await),
+            StoreError::DocumentNotfound
+        ));
+        assert!(matches!(
+            err(as_of(&log, id, "channel_a", t0 + hour * 3).await),
+            StoreError::DocumentNotfound
+        ));
+        assert!(matches!(
+            err(as_of(&log, id, "channel_b", t0).await),
+            StoreError::UnauthorizedAccess
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_append_conflict() {
+        let log = LocalLog::default();
+        let now = Utc::now();
+        log.append(&change(1, Kind::Created(experiment("checkout")), now))
+            .await
+            .unwrap();
+
+        let result = log
+            .append(&change(1, Kind::Created(experiment("checkout")), now))
+            .await;
+        assert!(matches!(
+            result.unwrap_err().downcast::<StoreError>().unwrap(),
+            StoreError::Conflict { .. }
+        ));
+    }
+
+    #[test]
+    fn test_fold_checks_the_versions() {
+        let now = Utc::now();
+        let changes = [
+            change(1, Kind::Created(experiment("checkout")), now),
+            change(3, Kind::Deleted, now),
+        ];
+        assert!(fold(changes.iter()).is_err());
+
+        let changes = [change(1, Kind::Deleted, now)];
+        assert!(fold(changes.iter()).is_err());
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{serde::ts_milliseconds, DateTime, Utc};
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::experiment::UserError;
+
+/// Header carrying the idempotency key of a request.
+pub const HEADER: &str = "Idempotency-Key";
+
+/// How long a key is remembered unless configured otherwise.
+pub const DEFAULT_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+pub const MAX_KEY_LENGTH: usize = 255;
+
+/// Response stored for the replays of a request.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: serde_json::Value,
+}
+
+/// Defined struct represents a request made with an idempotency key.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Record {
+    pub channel_id: String,
+    pub key: String,
+    /// Digest of the request body, a replay must carry the same body.
+    pub fingerprint: String,
+    /// The response, none while the first request is still being handled.
+    pub response: Option<Response>,
+    #[serde(with = "ts_milliseconds")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Defined the contract of the idempotency records storage. Records older than the
+/// window of the store are forgotten.
+#[automock]
+#[async_trait]
+pub trait Store {
+    /// Keep the record unless its key is already taken, returns the record that holds it.
+    async fn reserve(&self, record: &Record) -> Result<Option<Record>>;
+    /// Store the response of the reserved key.
+    async fn complete(&self, channel_id: &str, key: &str, response: &Response) -> Result<()>;
+    /// Forget the reserved key, so the request can be retried.
+    async fn release(&self, channel_id: &str, key: &str) -> Result<()>;
+}
+
+/// Idempotency store kept in the process memory, only suitable when a single instance
+/// is running.
+#[derive(Debug)]
+pub struct LocalStore {
+    window: Duration,
+    records: Mutex<HashMap<(String, String), Record>>,
+}
+
+impl LocalStore {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Default for LocalStore {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+#[async_trait]
+impl Store for LocalStore {
+    async fn reserve(&self, record: &Record) -> Result<Option<Record>> {
+        let mut records = self.records.lock().unwrap();
+        let expired_at = Utc::now() - chrono::Duration::from_std(self.window)?;
+        records.retain(|_, r| r.created_at > expired_at);
+
+        let id = (record.channel_id.clone(), record.key.clone());
+        if let Some(existing) = records.get(&id) {
+            return Ok(Some(existing.clone()));
+        }
+
+        records.insert(id, record.clone());
+        Ok(None)
+    }
+
+    async fn complete(&self, channel_id: &str, key: &str, response: &Response) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        if let Some(record) = records.get_mut(&(channel_id.to_owned(), key.to_owned())) {
+            record.response = Some(response.clone());
+        }
+        Ok(())
+    }
+
+    async fn release(&self, channel_id: &str, key: &str) -> Result<()> {
+        let mut records = self.records.lock().unwrap();
+        records.remove(&(channel_id.to_owned(), key.to_owned()));
+        Ok(())
+    }
+}
+
+/// What to do with a request carrying an idempotency key.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// First time the key is seen, handle the request then `complete` or `release` the key.
+    Proceed,
+    /// The request was handled already, respond the same.
+    Replay(Response),
+    /// The first request with the key is still being handled.
+    InProgress,
+    /// The key was used with another body.
+    Mismatch,
+}
+
+/// Digest of the request body, computed from its json value so the formatting does not matter.
+pub fn fingerprint(body: &impl Serialize) -> Result<String> {
+    let json = serde_json::to_vec(&serde_json::to_value(body)?)?;
+    Ok(Sha256::digest(&json)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Reserve the key for the request, or tell how the earlier request with the key went.
+pub async fn begin(
+    store: &(dyn Store + Send + Sync),
+    channel_id: &str,
+    key: &str,
+    fingerprint: &str,
+) -> Result<Outcome> {
+    if key.is_empty() || key.len() > MAX_KEY_LENGTH || !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(UserError::ValidationError {
+            message: format!(
+                "idempotency key must be 1 - {} printable characters",
+                MAX_KEY_LENGTH
+            ),
+        }
+        .into());
+    }
+
+    let record = Record {
+        channel_id: channel_id.to_owned(),
+        key: key.to_owned(),
+        fingerprint: fingerprint.to_owned(),
+        response: None,
+        created_at: Utc::now(),
+    };
+
+    Ok(match store.reserve(&record).await? {
+        None => Outcome::Proceed,
+        Some(existing) if existing.fingerprint != fingerprint => Outcome::Mismatch,
+        Some(Record {
+            response: Some(response),
+            ..
+        }) => Outcome::Replay(response),
+        Some(_) => Outcome::InProgress,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn test_begin() {
+        let store = LocalStore::default();
+
+        let outcome = begin(&store, "channel", "key-1", "a").await.unwrap();
+        assert_eq!(outcome, Outcome::Proceed);
+
+        let outcome = begin(&store, "channel", "key-1", "a").await.unwrap();
+        assert_eq!(outcome, Outcome::InProgress);
+
+        let response = Response {
+            status: 200,
+            body: serde_json::json!({"data": {"id": "1"}}),
+        };
+        store.complete("channel", "key-1", &response).await.unwrap();
+
+        let outcome = begin(&store, "channel", "key-1", "a").await.unwrap();
+        assert_eq!(outcome, Outcome::Replay(response));
+
+        let outcome = begin(&store, "channel", "key-1", "b").await.unwrap();
+        assert_eq!(outcome, Outcome::Mismatch);
+
+        let outcome = begin(&store, "other", "key-1", "b").await.unwrap();
+        assert_eq!(outcome, Outcome::Proceed);
+
+        assert!(begin(&store, "channel", "", "a").await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_window() {
+        let store = LocalStore::new(Duration::from_secs(0));
+
+        begin(&store, "channel", "key-1", "a").await.unwrap();
+        let outcome = begin(&store, "channel", "key-1", "b").await.unwrap();
+        assert_eq!(outcome, Outcome::Proceed);
+    }
+}
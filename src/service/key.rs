@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use validator::ValidationError;
+
+use super::experiment::{Experiment, Store, StoreError, UserError};
+
+pub const MAX_KEY_LENGTH: usize = 64;
+
+/// Length of the hex ids, a key never looks like one so either can address an experiment.
+const ID_LENGTH: usize = 24;
+
+/// How many suffixes are tried before giving up on a free generated key.
+const MAX_ATTEMPTS: usize = 20;
+
+/// Whether the reference of an experiment is an id rather than a key.
+pub fn looks_like_id(reference: &str) -> bool {
+    reference.len() == ID_LENGTH && reference.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// A key is made of lowercase letters, digits and `-` like `checkout-button-color`, it starts
+/// with a letter or a digit.
+pub fn is_valid(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_KEY_LENGTH
+        && !key.starts_with('-')
+        && !looks_like_id(key)
+        && key
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+pub fn validate_key(key: &str) -> Result<(), ValidationError> {
+    if !is_valid(key) {
+        return Err(ValidationError::new(
+            "key must have length between 1 - 64 of a-z, 0-9, - and not look like an id",
+        ));
+    }
+    Ok(())
+}
+
+/// Turn the name into a key, `Checkout Button (v2)` becomes `checkout-button-v2`.
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::new();
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_lowercase() || c.is_ascii_digit() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.truncate(MAX_KEY_LENGTH);
+    let slug = slug.trim_end_matches('-');
+
+    match slug {
+        "" => "experiment".to_owned(),
+        s if looks_like_id(s) => format!("exp-{}", s),
+        s => s.to_owned(),
+    }
+}
+
+/// Give the experiment a key free in its channel and not in `taken`, generated from its name
+/// when it has none. The key is added to `taken`, so a batch gives distinct keys.
+pub async fn assign(
+    repo: &impl Store,
+    experiment: &mut Experiment,
+    taken: &mut HashSet<String>,
+) -> Result<()> {
+    if experiment.key.is_empty() {
+        experiment.key = generate(repo, &experiment.channel_id, &experiment.name, taken).await?;
+    } else if taken.contains(&experiment.key) {
+        return Err(UserError::ValidationError {
+            message: format!(
+                "key `{}` is given to more than one experiment",
+                experiment.key
+            ),
+        }
+        .into());
+    } else {
+        ensure_free(
+            repo,
+            &experiment.channel_id,
+            &experiment.key,
+            experiment.id.as_deref(),
+        )
+        .await?;
+    }
+
+    taken.insert(experiment.key.clone());
+    Ok(())
+}
+
+/// Check the key is free in the channel, apart from the experiment `id` itself.
+pub async fn ensure_free(
+    repo: &impl Store,
+    channel_id: &str,
+    key: &str,
+    id: Option<&str>,
+) -> Result<()> {
+    match repo.get_by_key(key, channel_id).await {
+        Ok(e) if e.id.as_deref() != id => Err(StoreError::Conflict {
+            message: format!("key `{}` is taken", key),
+        }
+        .into()),
+        Ok(_) => Ok(()),
+        Err(e) => match e.downcast_ref::<StoreError>() {
+            Some(StoreError::DocumentNotfound) => Ok(()),
+            _ => Err(e),
+        },
+    }
+}
+
+/// Generate a key from the name which no experiment of the channel has yet, nor is in
+/// `taken`, suffixed by a number when the plain one is taken.
+pub async fn generate(
+    repo: &impl Store,
+    channel_id: &str,
+    name: &str,
+    taken: &HashSet<String>,
+) -> Result<String> {
+    let base = slugify(name);
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let key = match attempt {
+            1 => base.clone(),
+            n => {
+                let suffix = format!("-{}", n);
+                let mut key = base.clone();
+                key.truncate(MAX_KEY_LENGTH - suffix.len());
+                format!("{}{}", key.trim_end_matches('-'), suffix)
+            }
+        };
+
+        if taken.contains(&key) {
+            continue;
+        }
+
+        match repo.get_by_key(&key, channel_id).await {
+            Ok(_) => continue,
+            Err(e) => match e.downcast_ref::<StoreError>() {
+                Some(StoreError::DocumentNotfound) => return Ok(key),
+                _ => return Err(e),
+            },
+        }
+    }
+
+    Err(StoreError::Conflict {
+        message: format!("no free key for `{}`, give one", base),
+    }
+    .into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{Experiment, MockStore};
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Checkout Button (v2)"), "checkout-button-v2");
+        assert_eq!(slugify("  --Hello__World--  "), "hello-world");
+        assert_eq!(slugify("ทดสอบ"), "experiment");
+        assert_eq!(
+            slugify("62bb13dfea2b3ea78771e305"),
+            "exp-62bb13dfea2b3ea78771e305"
+        );
+        assert!(is_valid(&slugify(&"long name ".repeat(20))));
+    }
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("checkout-v2"));
+        assert!(!is_valid("Checkout"));
+        assert!(!is_valid("-checkout"));
+        assert!(!is_valid("62bb13dfea2b3ea78771e305"));
+    }
+
+    #[actix_web::test]
+    async fn test_generate() {
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|key, _| match key {
+                "checkout" | "checkout-2" => Ok(Experiment::default()),
+                _ => Err(StoreError::DocumentNotfound.into()),
+            });
+
+        let key = generate(&mock_store, "channel", "Checkout", &HashSet::new())
+            .await
+            .unwrap();
+        assert_eq!(key, "checkout-3");
+
+        let taken = HashSet::from(["checkout-3".to_owned()]);
+        let key = generate(&mock_store, "channel", "Checkout", &taken)
+            .await
+            .unwrap();
+        assert_eq!(key, "checkout-4");
+    }
+}
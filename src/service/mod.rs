@@ -1 +1,19 @@
+pub mod assignment;
+pub mod bandit;
+pub mod batch;
+pub mod cache;
+pub mod evaluation;
+pub mod event;
 pub mod experiment;
+pub mod factorial;
+pub mod history;
+pub mod idempotency;
+pub mod key;
+pub mod outbox;
+pub mod projection;
+pub mod scheduler;
+pub mod search;
+pub mod stream;
+pub mod tag;
+pub mod transfer;
+pub mod webhook;
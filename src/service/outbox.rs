@@ -0,0 +1,197 @@
+use std::time::Duration;
+
+use actix_web::rt::time;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+use serde::{Deserialize, Serialize};
+
+use super::event::{Event, Publisher};
+
+/// Defined struct represents an event written along with the change of an experiment, kept
+/// until it is delivered to every sink.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub id: String,
+    pub event: Event,
+    /// Number of the failed deliveries.
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// Defined the contract of the outbox the stores write the events to.
+#[automock]
+#[async_trait]
+pub trait Outbox {
+    /// Claim up to `limit` undelivered entries due at `now` which failed less than
+    /// `max_attempts` times, the oldest first. They are not claimed again before `now + lease`.
+    async fn claim(
+        &self,
+        limit: i64,
+        now: DateTime<Utc>,
+        lease: Duration,
+        max_attempts: u32,
+    ) -> Result<Vec<Entry>>;
+    async fn delivered(&self, id: &str, at: DateTime<Utc>) -> Result<()>;
+    /// Record a failed delivery, the entry is due again at `retry_at`.
+    async fn failed(&self, id: &str, error: &str, retry_at: DateTime<Utc>) -> Result<()>;
+}
+
+/// Dispatcher settings.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// How often the outbox is checked.
+    pub interval: Duration,
+    pub batch_size: i64,
+    /// How long a claimed entry is left to the dispatcher which claimed it.
+    pub lease: Duration,
+    /// Entries which failed that many times are left in the outbox undelivered.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled by each failure up to `max_backoff`.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            batch_size: 100,
+            lease: Duration::from_secs(60),
+            max_attempts: 10,
+            backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl Config {
+    /// Delay before retrying an entry which failed `attempts` times.
+    pub fn backoff_after(&self, attempts: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempts.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+/// Deliver the due entries to every sink, returns the number delivered. An entry is
+/// delivered again to every sink when one of them fails, the sinks must bear duplicates.
+pub async fn dispatch(
+    outbox: &(impl Outbox + ?Sized),
+    sinks: &[Box<dyn Publisher + Send + Sync>],
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let entries = outbox
+        .claim(config.batch_size, now, config.lease, config.max_attempts)
+        .await?;
+
+    let mut delivered = 0;
+    for entry in entries {
+        let mut errors = vec![];
+        for sink in sinks {
+            if let Err(e) = sink.publish(&entry.event).await {
+                errors.push(e.to_string());
+            }
+        }
+
+        if errors.is_empty() {
+            outbox.delivered(&entry.id, Utc::now()).await?;
+            delivered += 1;
+        } else {
+            let backoff = config.backoff_after(entry.attempts + 1);
+            let retry_at = now + chrono::Duration::from_std(backoff)?;
+            outbox
+                .failed(&entry.id, &errors.join("; "), retry_at)
+                .await?;
+        }
+    }
+
+    Ok(delivered)
+}
+
+/// Dispatch the outbox forever, every `config.interval`.
+pub async fn run(
+    outbox: &(impl Outbox + ?Sized),
+    sinks: &[Box<dyn Publisher + Send + Sync>],
+    config: &Config,
+) {
+    let mut interval = time::interval(config.interval);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = dispatch(outbox, sinks, config, Utc::now()).await {
+            println!("outbox dispatch failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::event::MockPublisher;
+    use mockall::predicate::{always, eq};
+
+    fn entry(id: &str, attempts: u32) -> Entry {
+        Entry {
+            id: id.to_owned(),
+            event: Event::deleted("62bb13dfea2b3ea78771e305", "channel_a", Utc::now()),
+            attempts,
+            next_attempt_at: Utc::now(),
+            last_error: None,
+            delivered_at: None,
+        }
+    }
+
+    #[test]
+    fn test_backoff() {
+        let config = Config {
+            backoff: Duration::from_secs(2),
+            max_backoff: Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert_eq!(config.backoff_after(1), Duration::from_secs(2));
+        assert_eq!(config.backoff_after(3), Duration::from_secs(8));
+        assert_eq!(config.backoff_after(10), Duration::from_secs(60));
+        assert_eq!(config.backoff_after(100), Duration::from_secs(60));
+    }
+
+    #[actix_web::test]
+    async fn test_dispatch() {
+        let now = Utc::now();
+        let config = Config::default();
+
+        let mut outbox = MockOutbox::new();
+        outbox
+            .expect_claim()
+            .return_once(|_, _, _, _| Ok(vec![entry("a", 0), entry("b", 2)]));
+        outbox
+            .expect_delivered()
+            .with(eq("a"), always())
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let retry_at = now + chrono::Duration::seconds(4);
+        outbox
+            .expect_failed()
+            .with(eq("b"), eq("unreachable"), eq(retry_at))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        let mut sink = MockPublisher::new();
+        let mut calls = 0;
+        sink.expect_publish().times(2).returning(move |_| {
+            calls += 1;
+            if calls == 1 {
+                Ok(())
+            } else {
+                Err(anyhow::anyhow!("unreachable"))
+            }
+        });
+        let sinks: Vec<Box<dyn Publisher + Send + Sync>> = vec![Box::new(sink)];
+
+        let delivered = dispatch(&outbox, &sinks, &config, now).await.unwrap();
+        assert_eq!(delivered, 1);
+    }
+}
@@ -0,0 +1,177 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use super::experiment::UserError;
+
+/// Top level fields of an experiment a projection may select.
+pub const FIELDS: [&str; 16] = [
+    "id",
+    "key",
+    "name",
+    "description",
+    "active_interval",
+    "tags",
+    "variations",
+    "classing",
+    "factorial",
+    "owner",
+    "channel_id",
+    "status",
+    "kill_switch",
+    "created_at",
+    "updated_at",
+    "deleted_at",
+];
+
+/// Defined struct represents the fields of an experiment a client asked for, like
+/// `name,variations.indicator`. The `id` is always part of the projection.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Projection {
+    tree: BTreeMap<String, Node>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    /// The whole value is selected.
+    All,
+    /// Only some fields of the value are selected.
+    Fields(BTreeMap<String, Node>),
+}
+
+impl Projection {
+    /// Parse the comma separated list of dotted paths.
+    pub fn parse(fields: &str) -> Result<Self> {
+        let mut projection = Self::default();
+        projection.tree.insert("id".to_owned(), Node::All);
+
+        for path in fields.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+            let segments: Vec<&str> = path.split('.').collect();
+
+            let is_valid = FIELDS.contains(&segments[0])
+                && segments.iter().all(|s| {
+                    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                });
+            if !is_valid {
+                return Err(UserError::ValidationError {
+                    message: format!("unknown field `{}`", path),
+                }
+                .into());
+            }
+
+            insert(&mut projection.tree, &segments);
+        }
+
+        Ok(projection)
+    }
+
+    /// Dotted paths of the projection, a path is never listed together with its parent.
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths = vec![];
+        collect(&self.tree, "", &mut paths);
+        paths
+    }
+
+    /// Keep only the selected fields of the serialized experiment. Arrays are projected
+    /// element by element.
+    pub fn apply(&self, value: Value) -> Value {
+        apply(&self.tree, value)
+    }
+}
+
+fn insert(tree: &mut BTreeMap<String, Node>, segments: &[&str]) {
+    let (head, rest) = match segments.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    if rest.is_empty() {
+        tree.insert(head.to_string(), Node::All);
+        return;
+    }
+
+    let node = tree
+        .entry(head.to_string())
+        .or_insert_with(|| Node::Fields(BTreeMap::new()));
+    if let Node::Fields(children) = node {
+        insert(children, rest);
+    }
+}
+
+fn collect(tree: &BTreeMap<String, Node>, prefix: &str, paths: &mut Vec<String>) {
+    for (name, node) in tree.iter() {
+        let path = format!("{}{}", prefix, name);
+        match node {
+            Node::All => paths.push(path),
+            Node::Fields(children) => collect(children, &format!("{}.", path), paths),
+        }
+    }
+}
+
+fn apply(tree: &BTreeMap<String, Node>, value: Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let projected: Map<String, Value> = object
+                .into_iter()
+                .filter_map(|(name, value)| match tree.get(&name) {
+                    Some(Node::All) => Some((name, value)),
+                    Some(Node::Fields(children)) => Some((name, apply(children, value))),
+                    None => None,
+                })
+                .collect();
+            Value::Object(projected)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(|v| apply(tree, v)).collect()),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_and_apply() {
+        let projection = Projection::parse("name, variations.indicator,variations.weight").unwrap();
+        assert_eq!(
+            projection.paths(),
+            vec!["id", "name", "variations.indicator", "variations.weight"]
+        );
+
+        let experiment = json!({
+            "id": "aaa",
+            "name": "banner",
+            "description": "long text",
+            "variations": [
+                {"indicator": "control", "weight": 1, "values": {"color": "red"}},
+                {"indicator": "treatment", "weight": 1, "values": {"color": "blue"}},
+            ],
+        });
+        assert_eq!(
+            projection.apply(experiment),
+            json!({
+                "id": "aaa",
+                "name": "banner",
+                "variations": [
+                    {"indicator": "control", "weight": 1},
+                    {"indicator": "treatment", "weight": 1},
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parent_selects_whole_value() {
+        let projection = Projection::parse("variations.indicator,variations").unwrap();
+        assert_eq!(projection.paths(), vec!["id", "variations"]);
+    }
+
+    #[test]
+    fn test_unknown_field() {
+        assert!(Projection::parse("name,secret").is_err());
+        assert!(Projection::parse("variations..weight").is_err());
+        assert!(Projection::parse("variations.$where").is_err());
+    }
+}
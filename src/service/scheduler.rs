@@ -0,0 +1,413 @@
+use std::time::Duration;
+
+use actix_web::rt::time;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use mockall::automock;
+
+use super::bandit;
+use super::event::{Event, Publisher};
+use super::experiment::{Experiment, Patch, Status, Store};
+
+/// Name of the lease the scheduler holds while acting on experiments.
+pub const LEASE_NAME: &str = "experiment_scheduler";
+
+/// Number of the experiments loaded at once by a tick.
+pub const PAGE_SIZE: i64 = 100;
+
+/// Scheduler settings.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// How often the experiments are checked.
+    pub interval: Duration,
+    /// How long an acquired lease stays valid without being renewed.
+    pub lease_ttl: Duration,
+    /// Identity of this instance when holding the lease.
+    pub holder: String,
+}
+
+/// Defined the contract of a lock shared between the server replicas,
+/// only the holder of a lease is allowed to act.
+#[automock]
+#[async_trait]
+pub trait Lease {
+    /// Acquire or renew the lease `name` for `holder`, returns false when another
+    /// holder owns an unexpired lease.
+    async fn acquire(&self, name: &str, holder: &str, ttl: Duration) -> Result<bool>;
+}
+
+/// Lease which is always granted, only suitable when a single instance is running.
+#[derive(Debug, Default, Clone)]
+pub struct LocalLease;
+
+#[async_trait]
+impl Lease for LocalLease {
+    async fn acquire(&self, _name: &str, _holder: &str, _ttl: Duration) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+/// Move every experiment whose interval opened or closed at `now` to its next status,
+/// recompute the weights of the running bandits which are due and publish an event
+/// per change. Returns the published events, an experiment which fails is left to the
+/// next tick and the others go on.
+pub async fn tick(
+    repo: &impl Store,
+    publisher: &(impl Publisher + ?Sized),
+    now: DateTime<Utc>,
+) -> Result<Vec<Event>> {
+    let statuses = [Status::Scheduled, Status::Running, Status::Paused];
+
+    let mut events = vec![];
+    let mut after = None;
+    loop {
+        let experiments = repo.list_by_status(&statuses, after, PAGE_SIZE).await?;
+        let is_last = (experiments.len() as i64) < PAGE_SIZE;
+        after = experiments.last().and_then(|e| e.id.clone());
+
+        for experiment in experiments {
+            let changes = match step(repo, experiment.clone(), now).await {
+                Ok(changes) => changes,
+                Err(e) => {
+                    println!(
+                        "scheduler: unable to move experiment {}: {}",
+                        experiment.id.unwrap_or_default(),
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            for event in changes {
+                if let Err(e) = publisher.publish(&event).await {
+                    println!("scheduler: unable to publish event: {}", e);
+                }
+                events.push(event);
+            }
+        }
+
+        if is_last || after.is_none() {
+            return Ok(events);
+        }
+    }
+}
+
+/// Apply the changes due at `now` to the experiment, returns their events. The status only
+/// moves from the one it was listed in, an experiment changed meanwhile waits for the next tick.
+async fn step(repo: &impl Store, experiment: Experiment, now: DateTime<Utc>) -> Result<Vec<Event>> {
+    let id = experiment.id.clone().unwrap_or_default();
+    let mut experiment = experiment;
+    let mut changes = vec![];
+
+    if let Some(next) = experiment.next_status(now) {
+        let from = experiment.status;
+        let patch = Patch::Status { from, to: next };
+        experiment = match repo.patch(&id, &experiment.channel_id, &patch).await? {
+            Some(patched) => patched,
+            None => return Ok(changes),
+        };
+        changes.push(Event::status_changed(
+            &id,
+            &experiment.channel_id,
+            from,
+            next,
+            now,
+        ));
+    }
+
+    let is_bandit_due = match experiment.classing.bandit {
+        Some(ref b) => {
+            experiment.status == Status::Running
+                && experiment.kill_switch.is_none()
+                && b.is_due(now)
+        }
+        None => false,
+    };
+    if is_bandit_due {
+        // only the weights are written, the rewards counted meanwhile are kept.
+        if let Some(change) = bandit::weigh(&experiment, &mut rand::thread_rng(), now)? {
+            let patch = Patch::Weights(change);
+            if let Some(patched) = repo.patch(&id, &experiment.channel_id, &patch).await? {
+                changes.push(Event::weights_changed(&patched, now));
+            }
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Run the scheduler forever, ticking only while this instance holds the lease.
+pub async fn run(
+    repo: &impl Store,
+    lease: &(impl Lease + ?Sized),
+    publisher: &(impl Publisher + ?Sized),
+    conf: &Config,
+) {
+    let mut interval = time::interval(conf.interval);
+
+    loop {
+        interval.tick().await;
+
+        match lease
+            .acquire(LEASE_NAME, &conf.holder, conf.lease_ttl)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => continue,
+            Err(e) => {
+                println!("scheduler: unable to acquire lease: {}", e);
+                continue;
+            }
+        }
+
+        if let Err(e) = tick(repo, publisher, Utc::now()).await {
+            println!("scheduler: tick failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::bandit::{Algorithm, Bandit};
+    use crate::service::event::{Kind, MockPublisher};
+    use crate::service::experiment::{
+        Classing, Interval, KillScope, KillSwitch, MockStore, Variance,
+    };
+    use chrono::Duration as ChronoDuration;
+
+    #[actix_web::test]
+    async fn test_tick_transitions() {
+        let now = Utc::now();
+        let opened = Experiment {
+            id: Some("opened".to_owned()),
+            active_interval: Some(Interval(Some(now - ChronoDuration::hours(1)), None)),
+            ..Default::default()
+        };
+        let closed = Experiment {
+            id: Some("closed".to_owned()),
+            status: Status::Running,
+            active_interval: Some(Interval(None, Some(now - ChronoDuration::hours(1)))),
+            ..Default::default()
+        };
+        let waiting = Experiment {
+            id: Some("waiting".to_owned()),
+            active_interval: Some(Interval(Some(now + ChronoDuration::hours(1)), None)),
+            ..Default::default()
+        };
+
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_list_by_status()
+            .return_once(move |_, _, _| Ok(vec![opened, closed, waiting]));
+        mock_store
+            .expect_patch()
+            .times(2)
+            .returning(|id, _, patch| match patch {
+                Patch::Status { to, .. } => Ok(Some(Experiment {
+                    id: Some(id.to_owned()),
+                    status: *to,
+                    ..Default::default()
+                })),
+                _ => unreachable!(),
+            });
+
+        let mut mock_publisher = MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .times(2)
+            .returning(|_| Ok(()));
+
+        let events = tick(&mock_store, &mock_publisher, now).await.unwrap();
+        let changes: Vec<(String, serde_json::Value)> = events
+            .into_iter()
+            .map(|e| (e.experiment_id, e.data["to"].clone()))
+            .collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                ("opened".to_owned(), serde_json::json!("running")),
+                ("closed".to_owned(), serde_json::json!("ended")),
+            ]
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_tick_pages_through_the_experiments() {
+        let now = Utc::now();
+        let waiting = move |i: i64| Experiment {
+            id: Some(format!("{:03}", i)),
+            active_interval: Some(Interval(Some(now + ChronoDuration::hours(1)), None)),
+            ..Default::default()
+        };
+
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_list_by_status()
+            .times(2)
+            .returning(move |_, after, limit| match after.as_deref() {
+                None => Ok((0..limit).map(waiting).collect()),
+                Some("099") => Ok(vec![waiting(limit)]),
+                Some(after) => panic!("unexpected page after {}", after),
+            });
+
+        let events = tick(&mock_store, &MockPublisher::new(), now).await.unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_tick_goes_on_after_a_failure() {
+        let now = Utc::now();
+        let opened = move |id: &str| Experiment {
+            id: Some(id.to_owned()),
+            active_interval: Some(Interval(Some(now - ChronoDuration::hours(1)), None)),
+            ..Default::default()
+        };
+
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_list_by_status()
+            .return_once(move |_, _, _| {
+                Ok(vec![opened("failing"), opened("edited"), opened("opened")])
+            });
+        mock_store
+            .expect_patch()
+            .withf(|_, _, patch| {
+                *patch
+                    == Patch::Status {
+                        from: Status::Scheduled,
+                        to: Status::Running,
+                    }
+            })
+            .times(3)
+            .returning(|id, _, _| match id {
+                "failing" => Err(anyhow::anyhow!("unavailable")),
+                // changed since it was listed, e.g. killed.
+                "edited" => Ok(None),
+                _ => Ok(Some(Experiment {
+                    id: Some(id.to_owned()),
+                    status: Status::Running,
+                    ..Default::default()
+                })),
+            });
+        mock_store.expect_update().never();
+
+        let mut mock_publisher = MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let events = tick(&mock_store, &mock_publisher, now).await.unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].experiment_id, "opened");
+    }
+
+    #[actix_web::test]
+    async fn test_tick_recomputes_bandit() {
+        let variance = |indicator: &str| Variance {
+            group_name: indicator.to_owned(),
+            description: String::default(),
+            indicator: indicator.to_owned(),
+            weight: 50,
+            values: Default::default(),
+            levels: Default::default(),
+        };
+        let running = Experiment {
+            id: Some("bandit".to_owned()),
+            status: Status::Running,
+            variations: vec![variance("control"), variance("treatment")],
+            classing: Classing {
+                strategy: bandit::STRATEGY.to_owned(),
+                persistent_mode: String::default(),
+                bandit: Some(Bandit {
+                    algorithm: Algorithm::EpsilonGreedy,
+                    epsilon: 0.2,
+                    min_weight: 0,
+                    max_weight: 100,
+                    recompute_interval_secs: 3600,
+                    frozen: false,
+                    rewards: Default::default(),
+                    history: vec![],
+                    recomputed_at: None,
+                }),
+            },
+            ..Default::default()
+        };
+
+        let weighed = running.clone();
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_list_by_status()
+            .return_once(move |_, _, _| Ok(vec![running]));
+        mock_store
+            .expect_patch()
+            .withf(
+                |_, _, patch| matches!(patch, Patch::Weights(change) if change.weights.len() == 2),
+            )
+            .times(1)
+            .returning(move |_, _, patch| {
+                let mut data = weighed.clone();
+                assert!(patch.apply(&mut data));
+                Ok(Some(data))
+            });
+        mock_store.expect_update().never();
+
+        let mut mock_publisher = MockPublisher::new();
+        mock_publisher
+            .expect_publish()
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let events = tick(&mock_store, &mock_publisher, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(events[0].kind, Kind::WeightsChanged);
+    }
+
+    #[actix_web::test]
+    async fn test_tick_skips_killed_bandit() {
+        let killed = Experiment {
+            id: Some("killed".to_owned()),
+            status: Status::Running,
+            kill_switch: Some(KillSwitch {
+                scope: KillScope::Experiment,
+                reason: "broken".to_owned(),
+                triggered_by: None,
+                triggered_at: Utc::now(),
+            }),
+            classing: Classing {
+                strategy: bandit::STRATEGY.to_owned(),
+                persistent_mode: String::default(),
+                bandit: Some(Bandit {
+                    algorithm: Algorithm::EpsilonGreedy,
+                    epsilon: 0.2,
+                    min_weight: 0,
+                    max_weight: 100,
+                    recompute_interval_secs: 3600,
+                    frozen: false,
+                    rewards: Default::default(),
+                    history: vec![],
+                    recomputed_at: None,
+                }),
+            },
+            ..Default::default()
+        };
+
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_list_by_status()
+            .return_once(move |_, _, _| Ok(vec![killed]));
+        mock_store.expect_patch().never();
+        mock_store.expect_update().never();
+
+        let mock_publisher = MockPublisher::new();
+
+        let events = tick(&mock_store, &mock_publisher, Utc::now())
+            .await
+            .unwrap();
+        assert!(events.is_empty());
+    }
+}
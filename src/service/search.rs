@@ -0,0 +1,126 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::experiment::{Experiment, Store, UserError};
+
+/// Upper bound of the hits returned by a search.
+pub const MAX_LIMIT: i64 = 100;
+
+/// Weight of a match per field, a match in the name counts more than one in a description.
+pub const NAME_WEIGHT: f64 = 10.0;
+pub const DESCRIPTION_WEIGHT: f64 = 5.0;
+pub const GROUP_NAME_WEIGHT: f64 = 3.0;
+pub const VARIATION_DESCRIPTION_WEIGHT: f64 = 1.0;
+pub const TAG_WEIGHT: f64 = 3.0;
+
+/// Defined struct represents an experiment matching a search and its relevance.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Hit {
+    pub experiment: Experiment,
+    pub score: f64,
+}
+
+/// Split the text into lowercase words.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Relevance of the experiment for the search terms, zero when no term matches.
+/// Stores without a text index of their own rank with it.
+pub fn score(experiment: &Experiment, terms: &[String]) -> f64 {
+    let mut fields: Vec<(f64, &str)> = vec![
+        (NAME_WEIGHT, &experiment.name),
+        (DESCRIPTION_WEIGHT, &experiment.description),
+    ];
+    for tag in experiment.tags.iter() {
+        fields.push((TAG_WEIGHT, tag));
+    }
+    for variation in experiment.variations.iter() {
+        fields.push((GROUP_NAME_WEIGHT, &variation.group_name));
+        fields.push((VARIATION_DESCRIPTION_WEIGHT, &variation.description));
+    }
+
+    fields
+        .into_iter()
+        .map(|(weight, text)| {
+            let tokens = tokenize(text);
+            let matches = tokens.iter().filter(|t| terms.contains(t)).count();
+            weight * matches as f64
+        })
+        .sum()
+}
+
+/// Rank the experiments against the query, the best hits first.
+pub fn rank(experiments: Vec<Experiment>, q: &str, limit: i64) -> Vec<Hit> {
+    let terms = tokenize(q);
+
+    let mut hits: Vec<Hit> = experiments
+        .into_iter()
+        .map(|experiment| Hit {
+            score: score(&experiment, &terms),
+            experiment,
+        })
+        .filter(|hit| hit.score > 0.0)
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits.truncate(limit.max(0) as usize);
+
+    hits
+}
+
+pub async fn search(repo: &impl Store, channel_id: &str, q: &str, limit: i64) -> Result<Vec<Hit>> {
+    if tokenize(q).is_empty() {
+        return Err(UserError::ValidationError {
+            message: "search query must contain a word".to_owned(),
+        }
+        .into());
+    }
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(UserError::ValidationError {
+            message: format!("limit must bound between 1 - {}", MAX_LIMIT),
+        }
+        .into());
+    }
+
+    repo.search(channel_id, q, limit).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::Variance;
+
+    #[test]
+    fn test_rank() {
+        let experiment = |name: &str, description: &str, group_name: &str| Experiment {
+            name: name.to_owned(),
+            description: description.to_owned(),
+            variations: vec![Variance {
+                group_name: group_name.to_owned(),
+                description: String::default(),
+                indicator: "control".to_owned(),
+                weight: 1,
+                values: Default::default(),
+                levels: Default::default(),
+            }],
+            ..Default::default()
+        };
+
+        let hits = rank(
+            vec![
+                experiment("Banner color", "homepage", "Red"),
+                experiment("Checkout button", "move the banner", "Green"),
+                experiment("Pricing", "annual plans", "Blue"),
+                experiment("Footer", "links", "Banner"),
+            ],
+            "BANNER",
+            10,
+        );
+
+        let names: Vec<&str> = hits.iter().map(|h| h.experiment.name.as_str()).collect();
+        assert_eq!(names, vec!["Banner color", "Checkout button", "Footer"]);
+    }
+}
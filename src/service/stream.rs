@@ -0,0 +1,241 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use tokio::sync::mpsc;
+
+use super::event::{Event, Publisher};
+
+/// Events kept for the clients resuming their stream unless configured otherwise.
+pub const DEFAULT_REPLAY_SIZE: usize = 1000;
+/// Events waiting for a client, a client falling further behind is disconnected and has
+/// to resume.
+pub const SUBSCRIBER_BUFFER: usize = 256;
+
+/// Defined struct represents an event as it is streamed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    /// `{generation}-{sequence}`, the generation tells the ids of another process apart.
+    pub id: String,
+    pub channel_id: String,
+    /// Kind of the event.
+    pub event: String,
+    /// The event as json.
+    pub data: String,
+}
+
+/// Where a stream starts.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Resume {
+    /// The events of the channel after the last one seen, none for a new stream.
+    Replay(Vec<Message>),
+    /// Some events after the last one seen left the buffer, the client must reload.
+    Reset,
+}
+
+pub struct Subscription {
+    pub resume: Resume,
+    pub receiver: mpsc::Receiver<Message>,
+}
+
+struct State {
+    generation: String,
+    sequence: u64,
+    /// Latest sequence gone from the replay buffer.
+    evicted: u64,
+    capacity: usize,
+    replay: VecDeque<(u64, Message)>,
+    subscribers: Vec<(String, mpsc::Sender<Message>)>,
+}
+
+/// Publisher pushing the events to the streams of their channel, keeping the latest ones
+/// so an interrupted stream can resume. The buffer is in the process memory, a stream
+/// resumed on another instance is reset.
+#[derive(Clone)]
+pub struct Broadcaster {
+    state: Arc<Mutex<State>>,
+}
+
+impl Broadcaster {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                generation: bson::oid::ObjectId::new().to_hex(),
+                sequence: 0,
+                evicted: 0,
+                capacity,
+                replay: VecDeque::new(),
+                subscribers: vec![],
+            })),
+        }
+    }
+
+    /// Stream the events of the channel, from after `last_event_id` when given.
+    pub fn subscribe(&self, channel_id: &str, last_event_id: Option<&str>) -> Subscription {
+        let (sender, receiver) = mpsc::channel(SUBSCRIBER_BUFFER);
+
+        let mut state = self.state.lock().unwrap();
+        let resume = match last_event_id {
+            None => Resume::Replay(vec![]),
+            Some(id) => match state.position_of(id) {
+                Some(last) => Resume::Replay(
+                    state
+                        .replay
+                        .iter()
+                        .filter(|(seq, m)| *seq > last && m.channel_id == channel_id)
+                        .map(|(_, m)| m.clone())
+                        .collect(),
+                ),
+                None => Resume::Reset,
+            },
+        };
+        state.subscribers.push((channel_id.to_owned(), sender));
+
+        Subscription { resume, receiver }
+    }
+
+    /// Number of the connected streams.
+    pub fn subscribers(&self) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.subscribers.retain(|(_, s)| !s.is_closed());
+        state.subscribers.len()
+    }
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_SIZE)
+    }
+}
+
+impl State {
+    /// Sequence of the event id when the events after it are all still buffered.
+    fn position_of(&self, id: &str) -> Option<u64> {
+        let (generation, sequence) = id.rsplit_once('-')?;
+        let sequence: u64 = sequence.parse().ok()?;
+        if generation != self.generation || sequence < self.evicted || sequence > self.sequence {
+            return None;
+        }
+        Some(sequence)
+    }
+}
+
+#[async_trait]
+impl Publisher for Broadcaster {
+    async fn publish(&self, event: &Event) -> Result<()> {
+        let data = serde_json::to_string(event)?;
+        let kind = serde_json::to_value(event.kind)?;
+
+        let mut state = self.state.lock().unwrap();
+        state.sequence += 1;
+        let sequence = state.sequence;
+        let message = Message {
+            id: format!("{}-{}", state.generation, sequence),
+            channel_id: event.channel_id.clone(),
+            event: kind.as_str().unwrap_or_default().to_owned(),
+            data,
+        };
+
+        state.replay.push_back((sequence, message.clone()));
+        while state.replay.len() > state.capacity {
+            if let Some((seq, _)) = state.replay.pop_front() {
+                state.evicted = seq;
+            }
+        }
+
+        // a closed or lagging stream is dropped.
+        state.subscribers.retain(|(channel_id, sender)| {
+            *channel_id != message.channel_id || sender.try_send(message.clone()).is_ok()
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn deleted(channel_id: &str) -> Event {
+        Event::deleted("62bb13dfea2b3ea78771e305", channel_id, Utc::now())
+    }
+
+    fn replayed(resume: Resume) -> Vec<Message> {
+        match resume {
+            Resume::Replay(messages) => messages,
+            Resume::Reset => panic!("the stream is reset"),
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_live_events_of_the_channel() {
+        let broadcaster = Broadcaster::default();
+        let mut subscription = broadcaster.subscribe("channel_a", None);
+        assert_eq!(subscription.resume, Resume::Replay(vec![]));
+
+        broadcaster.publish(&deleted("channel_b")).await.unwrap();
+        broadcaster.publish(&deleted("channel_a")).await.unwrap();
+
+        let message = subscription.receiver.recv().await.unwrap();
+        assert_eq!(message.channel_id, "channel_a");
+        assert_eq!(message.event, "deleted");
+        assert!(subscription.receiver.try_recv().is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_resume() {
+        let broadcaster = Broadcaster::default();
+        let mut first = broadcaster.subscribe("channel_a", None);
+        for channel_id in ["channel_a", "channel_b", "channel_a", "channel_a"] {
+            broadcaster.publish(&deleted(channel_id)).await.unwrap();
+        }
+        let seen = first.receiver.recv().await.unwrap();
+
+        let resumed = broadcaster.subscribe("channel_a", Some(&seen.id));
+        let messages = replayed(resumed.resume);
+        assert_eq!(messages.len(), 2);
+        assert!(messages.iter().all(|m| m.channel_id == "channel_a"));
+        assert_eq!(messages[0], first.receiver.recv().await.unwrap());
+
+        // nothing to replay after the latest event.
+        let latest = messages[1].id.clone();
+        let resumed = broadcaster.subscribe("channel_a", Some(&latest));
+        assert!(replayed(resumed.resume).is_empty());
+    }
+
+    #[actix_web::test]
+    async fn test_reset() {
+        let broadcaster = Broadcaster::new(2);
+        let mut subscription = broadcaster.subscribe("channel_a", None);
+        for _ in 0..4 {
+            broadcaster.publish(&deleted("channel_a")).await.unwrap();
+        }
+        let first = subscription.receiver.recv().await.unwrap();
+        let second = subscription.receiver.recv().await.unwrap();
+
+        // the events after the first one left the buffer.
+        let resumed = broadcaster.subscribe("channel_a", Some(&first.id));
+        assert_eq!(resumed.resume, Resume::Reset);
+        let resumed = broadcaster.subscribe("channel_a", Some(&second.id));
+        assert_eq!(replayed(resumed.resume).len(), 2);
+
+        // an id of another process, or no id at all.
+        for id in ["62bb13dfea2b3ea78771e305-1", "1", ""] {
+            let resumed = broadcaster.subscribe("channel_a", Some(id));
+            assert_eq!(resumed.resume, Resume::Reset);
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_closed_streams_are_dropped() {
+        let broadcaster = Broadcaster::default();
+        let subscription = broadcaster.subscribe("channel_a", None);
+        let _other = broadcaster.subscribe("channel_b", None);
+        assert_eq!(broadcaster.subscribers(), 2);
+
+        drop(subscription);
+        broadcaster.publish(&deleted("channel_a")).await.unwrap();
+        assert_eq!(broadcaster.subscribers(), 1);
+    }
+}
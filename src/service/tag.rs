@@ -0,0 +1,197 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use validator::ValidationError;
+
+use super::experiment::{Experiment, Patch, Store, UserError};
+
+pub const MAX_TAGS: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 32;
+
+/// Defined struct represents how many experiments of a channel carry a tag.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: u64,
+}
+
+/// Outcome of a bulk tag update for one experiment.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Outcome {
+    pub id: String,
+    pub ok: bool,
+    pub message: Option<String>,
+    /// The experiment as tagged, none when the update failed.
+    #[serde(skip)]
+    pub experiment: Option<Experiment>,
+}
+
+/// A tag is made of lowercase letters, digits and `-`, `_`, `:`, `.`
+/// like `team:growth` or `q3-2022`.
+pub fn is_valid(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.len() <= MAX_TAG_LENGTH
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "-_:.".contains(c))
+}
+
+pub fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    if tags.len() > MAX_TAGS {
+        return Err(ValidationError::new("too many tags"));
+    }
+    if !tags.iter().all(|t| is_valid(t)) {
+        return Err(ValidationError::new(
+            "tag must have length between 1 - 32 of a-z, 0-9, -, _, :, .",
+        ));
+    }
+    for (i, tag) in tags.iter().enumerate() {
+        if tags[..i].contains(tag) {
+            return Err(ValidationError::new("tags must be unique"));
+        }
+    }
+    Ok(())
+}
+
+/// Tags left once `remove` are removed from `tags` and the missing ones of `add` appended.
+pub fn merge(tags: &[String], add: &[String], remove: &[String]) -> Vec<String> {
+    let mut merged: Vec<String> = tags
+        .iter()
+        .filter(|t| !remove.contains(t))
+        .cloned()
+        .collect();
+    for tag in add {
+        if !merged.contains(tag) {
+            merged.push(tag.clone());
+        }
+    }
+    merged
+}
+
+/// Add and remove the tags of every given experiment of the channel. Each experiment is
+/// updated on its own, the outcome tells which ones failed and why.
+pub async fn bulk_update(
+    repo: &impl Store,
+    channel_id: &str,
+    ids: &[String],
+    add: &[String],
+    remove: &[String],
+) -> Result<Vec<Outcome>> {
+    for tag in add.iter().chain(remove.iter()) {
+        if !is_valid(tag) {
+            return Err(UserError::ValidationError {
+                message: format!("invalid tag `{}`", tag),
+            }
+            .into());
+        }
+    }
+
+    let mut outcomes = vec![];
+    for id in ids {
+        let outcome = match update_one(repo, id, channel_id, add, remove).await {
+            Ok(experiment) => Outcome {
+                id: id.clone(),
+                ok: true,
+                message: None,
+                experiment: Some(experiment),
+            },
+            Err(e) => Outcome {
+                id: id.clone(),
+                ok: false,
+                message: Some(e.to_string()),
+                experiment: None,
+            },
+        };
+        outcomes.push(outcome);
+    }
+
+    Ok(outcomes)
+}
+
+async fn update_one(
+    repo: &impl Store,
+    id: &str,
+    channel_id: &str,
+    add: &[String],
+    remove: &[String],
+) -> Result<Experiment> {
+    let patch = Patch::Tags {
+        add: merge(&[], add, &[]),
+        remove: remove.to_vec(),
+    };
+
+    // the tags are changed in place, so the edits made meanwhile are kept.
+    match repo.patch(id, channel_id, &patch).await? {
+        Some(experiment) => Ok(experiment),
+        None => Err(UserError::ValidationError {
+            message: format!("an experiment has at most {} tags", MAX_TAGS),
+        }
+        .into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{Experiment, MockStore};
+
+    #[test]
+    fn test_is_valid() {
+        assert!(is_valid("team:growth"));
+        assert!(is_valid("q3-2022"));
+        assert!(!is_valid("Team"));
+        assert!(!is_valid("has space"));
+        assert!(!is_valid(""));
+    }
+
+    #[test]
+    fn test_validate_tags_rejects_duplicates() {
+        assert!(validate_tags(&["a".to_owned(), "b".to_owned()]).is_ok());
+        assert!(validate_tags(&["a".to_owned(), "a".to_owned()]).is_err());
+    }
+
+    #[test]
+    fn test_merge() {
+        let tags = vec!["old".to_owned(), "keep".to_owned()];
+        let merged = merge(
+            &tags,
+            &["new".to_owned(), "keep".to_owned(), "new".to_owned()],
+            &["old".to_owned()],
+        );
+
+        assert_eq!(merged, vec!["keep".to_owned(), "new".to_owned()]);
+    }
+
+    #[actix_web::test]
+    async fn test_bulk_update() {
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_patch()
+            .returning(|id, _, patch| match id {
+                "found" => {
+                    let mut data = Experiment {
+                        tags: vec!["old".to_owned(), "keep".to_owned()],
+                        ..Default::default()
+                    };
+                    assert!(patch.apply(&mut data));
+                    assert_eq!(data.tags, vec!["keep".to_owned(), "new".to_owned()]);
+                    Ok(Some(data))
+                }
+                "full" => Ok(None),
+                _ => Err(crate::service::experiment::StoreError::DocumentNotfound.into()),
+            });
+        mock_store.expect_update().never();
+
+        let outcomes = bulk_update(
+            &mock_store,
+            "channel",
+            &["found".to_owned(), "full".to_owned(), "missing".to_owned()],
+            &["new".to_owned()],
+            &["old".to_owned()],
+        )
+        .await
+        .unwrap();
+
+        let oks: Vec<bool> = outcomes.iter().map(|o| o.ok).collect();
+        assert_eq!(oks, vec![true, false, false]);
+    }
+}
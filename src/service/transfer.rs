@@ -0,0 +1,516 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::experiment::{self, Experiment, Interval, Store, StoreError, UserError, Version};
+use super::key;
+
+/// Upper bound of the records accepted by one import.
+pub const MAX_IMPORT_RECORDS: usize = 1000;
+
+/// Columns of the csv export, only the flat fields of the experiment.
+pub const CSV_COLUMNS: [&str; 12] = [
+    "id",
+    "key",
+    "name",
+    "description",
+    "status",
+    "tags",
+    "strategy",
+    "variations",
+    "active_from",
+    "active_to",
+    "created_at",
+    "updated_at",
+];
+
+/// Format of the exported experiments. Only ndjson carries the whole experiment, so it is
+/// the one accepted back by the import.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Ndjson => "application/x-ndjson",
+            Format::Csv => "text/csv",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Ndjson => "ndjson",
+            Format::Csv => "csv",
+        }
+    }
+
+    /// Leading line of the export, if any.
+    pub fn header(&self) -> Option<String> {
+        match self {
+            Format::Ndjson => None,
+            Format::Csv => Some(format!("{}\n", CSV_COLUMNS.join(","))),
+        }
+    }
+
+    /// Encode the experiments, one line each.
+    pub fn encode(&self, experiments: &[Experiment]) -> Result<String> {
+        let mut out = String::new();
+        for e in experiments {
+            match self {
+                Format::Ndjson => out.push_str(&serde_json::to_string(e)?),
+                Format::Csv => out.push_str(&csv_row(e)),
+            }
+            out.push('\n');
+        }
+        Ok(out)
+    }
+}
+
+fn csv_row(e: &Experiment) -> String {
+    let (from, to) = match &e.active_interval {
+        Some(Interval(from, to)) => (*from, *to),
+        None => (None, None),
+    };
+    let time =
+        |t: Option<chrono::DateTime<chrono::Utc>>| t.map(|t| t.to_rfc3339()).unwrap_or_default();
+    let status = serde_json::to_value(e.status)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_owned))
+        .unwrap_or_default();
+
+    [
+        e.id.clone().unwrap_or_default(),
+        e.key.clone(),
+        e.name.clone(),
+        e.description.clone(),
+        status,
+        e.tags.join(";"),
+        e.classing.strategy.clone(),
+        e.variations.len().to_string(),
+        time(from),
+        time(to),
+        time(e.created_at),
+        time(e.updated_at),
+    ]
+    .iter()
+    .map(|v| csv_escape(v))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// What to do with an imported experiment whose id already exists in the channel.
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Keep the existing experiment.
+    #[default]
+    Skip,
+    /// Replace the existing experiment.
+    Overwrite,
+    /// Import nothing when any experiment exists.
+    Fail,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Created,
+    Overwritten,
+    Skipped,
+    /// The experiment exists while the mode is `fail`, or it changed while the import was
+    /// applied.
+    Conflict,
+    Invalid,
+    /// The record was valid but the store failed to write it.
+    Failed,
+}
+
+/// Result of one line of the import.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Record {
+    pub line: usize,
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub action: Action,
+    pub message: Option<String>,
+    /// The experiment as written, none unless the import applied the record.
+    #[serde(skip)]
+    pub written: Option<Experiment>,
+}
+
+/// Result of an import. On a dry run, or when a conflict fails it, `applied` is false and
+/// the actions are the ones which would have been taken.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Report {
+    pub dry_run: bool,
+    pub applied: bool,
+    pub records: Vec<Record>,
+}
+
+impl Report {
+    pub fn count(&self, action: Action) -> usize {
+        self.records.iter().filter(|r| r.action == action).count()
+    }
+}
+
+/// Import the ndjson experiments into the channel. Every record is checked before
+/// anything is written. The created experiments are owned by `owner`, the overwritten ones
+/// keep their owner and lifecycle like an update.
+pub async fn import(
+    repo: &impl Store,
+    channel_id: &str,
+    ndjson: &str,
+    mode: Mode,
+    dry_run: bool,
+    owner: &serde_json::Value,
+) -> Result<Report> {
+    let lines: Vec<(usize, &str)> = ndjson
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty())
+        .collect();
+
+    if lines.len() > MAX_IMPORT_RECORDS {
+        return Err(UserError::ValidationError {
+            message: format!("an import has at most {} records", MAX_IMPORT_RECORDS),
+        }
+        .into());
+    }
+
+    let mut planned = vec![];
+    let mut seen = HashSet::new();
+    let mut keys = HashSet::new();
+    for (line, text) in lines {
+        let (record, experiment) =
+            plan(repo, channel_id, line, text, mode, &mut seen, &mut keys).await?;
+        planned.push((record, experiment));
+    }
+
+    let has_conflict = planned.iter().any(|(r, _)| r.action == Action::Conflict);
+    let applied = !dry_run && !has_conflict;
+
+    let mut records = vec![];
+    for (mut record, experiment) in planned {
+        if let (true, Some((mut experiment, version))) = (applied, experiment) {
+            let result = match (record.action, version) {
+                (Action::Created, _) => {
+                    experiment.owner = Some(owner.clone());
+                    repo.save(&mut experiment).await.map(|_| true)
+                }
+                // the experiment replaces the one read by the plan, not a later one.
+                (Action::Overwritten, Some(version)) => {
+                    repo.update_if(&mut experiment, version).await.map(|_| true)
+                }
+                _ => Ok(false),
+            };
+            match result {
+                Ok(written) => record.written = written.then_some(experiment),
+                Err(e) => {
+                    record.action = match e.downcast_ref::<StoreError>() {
+                        Some(StoreError::Stale) => Action::Conflict,
+                        _ => Action::Failed,
+                    };
+                    record.message = Some(e.to_string());
+                }
+            }
+        }
+        records.push(record);
+    }
+
+    Ok(Report {
+        dry_run,
+        applied,
+        records,
+    })
+}
+
+/// Decide what to do with one line, returns the experiment to write if any and the version
+/// of the one it overwrites.
+async fn plan(
+    repo: &impl Store,
+    channel_id: &str,
+    line: usize,
+    text: &str,
+    mode: Mode,
+    seen: &mut HashSet<String>,
+    keys: &mut HashSet<String>,
+) -> Result<(Record, Option<(Experiment, Option<Version>)>)> {
+    let mut record = Record {
+        line,
+        id: None,
+        name: None,
+        action: Action::Invalid,
+        message: None,
+        written: None,
+    };
+
+    let mut experiment: Experiment = match serde_json::from_str(text) {
+        Ok(e) => e,
+        Err(e) => {
+            record.message = Some(e.to_string());
+            return Ok((record, None));
+        }
+    };
+    record.id = experiment.id.clone();
+    record.name = Some(experiment.name.clone());
+    experiment.channel_id = channel_id.to_owned();
+
+    let current = match &experiment.id {
+        None => None,
+        Some(id) => {
+            if !seen.insert(id.clone()) {
+                record.message = Some("duplicated id in the import".to_owned());
+                return Ok((record, None));
+            }
+
+            match repo.get_fresh(id, channel_id).await {
+                Ok(current) => Some(current),
+                Err(e) => match e.downcast_ref::<StoreError>() {
+                    Some(StoreError::DocumentNotfound) => None,
+                    Some(StoreError::UnauthorizedAccess) => {
+                        record.message = Some("id belongs to another channel".to_owned());
+                        return Ok((record, None));
+                    }
+                    Some(StoreError::InvalidInput { message }) => {
+                        record.message = Some(message.clone());
+                        return Ok((record, None));
+                    }
+                    _ => return Err(e),
+                },
+            }
+        }
+    };
+
+    record.action = match (&current, mode) {
+        (None, _) => Action::Created,
+        (Some(_), Mode::Skip) => Action::Skipped,
+        (Some(_), Mode::Overwrite) => Action::Overwritten,
+        (Some(_), Mode::Fail) => Action::Conflict,
+    };
+    if matches!(record.action, Action::Skipped | Action::Conflict) {
+        return Ok((record, None));
+    }
+
+    // the owner and the lifecycle of the overwritten experiment are kept, not the file's.
+    if let Some(current) = &current {
+        experiment::carry_over(current, &mut experiment);
+    }
+
+    // only the experiments to write need a free key and to be valid.
+    let checked = match key::assign(repo, &mut experiment, keys).await {
+        Ok(()) => experiment::check(&experiment),
+        Err(e) if is_rejection(&e) => Err(e),
+        Err(e) => return Err(e),
+    };
+    if let Err(e) = checked {
+        record.action = Action::Invalid;
+        record.message = Some(e.to_string());
+        return Ok((record, None));
+    }
+
+    let version = current.as_ref().map(Version::of);
+    Ok((record, Some((experiment, version))))
+}
+
+/// Whether the error is about the record itself rather than the store failing.
+fn is_rejection(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<UserError>().is_some()
+        || matches!(
+            e.downcast_ref::<StoreError>(),
+            Some(StoreError::Conflict { .. })
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::experiment::{Classing, MockStore, Variance};
+
+    fn experiment(id: &str) -> Experiment {
+        Experiment {
+            id: Some(id.to_owned()),
+            name: format!("experiment {}", id),
+            variations: vec![Variance {
+                group_name: "control".to_owned(),
+                description: String::new(),
+                indicator: "control".to_owned(),
+                weight: 100,
+                values: Default::default(),
+                levels: Default::default(),
+            }],
+            classing: Classing {
+                strategy: "random".to_owned(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn ndjson(experiments: &[Experiment]) -> String {
+        Format::Ndjson.encode(experiments).unwrap()
+    }
+
+    fn owner() -> serde_json::Value {
+        serde_json::json!({"id": "importer"})
+    }
+
+    fn mock_store() -> MockStore {
+        let mut mock_store = MockStore::new();
+        mock_store
+            .expect_get_by_key()
+            .returning(|_, _| Err(StoreError::DocumentNotfound.into()));
+        mock_store.expect_get_fresh().returning(|id, _| match id {
+            "existing" => Ok(experiment("existing")),
+            _ => Err(StoreError::DocumentNotfound.into()),
+        });
+        mock_store
+    }
+
+    #[test]
+    fn test_csv() {
+        let mut e = experiment("1");
+        e.description = "with, comma \"quoted\"".to_owned();
+        e.key = "experiment-1".to_owned();
+        e.tags = vec!["a".to_owned(), "b".to_owned()];
+
+        let csv = Format::Csv.encode(&[e]).unwrap();
+        assert_eq!(
+            csv,
+            "1,experiment-1,experiment 1,\"with, comma \"\"quoted\"\"\",scheduled,a;b,random,1,,,,\n"
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_import_skip() {
+        let mut mock_store = mock_store();
+        mock_store
+            .expect_save()
+            .withf(|e| {
+                e.id.as_deref() == Some("new")
+                    && e.channel_id == "channel"
+                    && e.owner == Some(owner())
+            })
+            .times(1)
+            .returning(|_| Ok("new".to_owned()));
+        mock_store.expect_update_if().never();
+
+        let mut invalid = experiment("invalid");
+        invalid.name = "no".to_owned();
+        let body = format!(
+            "{}not json\n",
+            ndjson(&[experiment("existing"), experiment("new"), invalid])
+        );
+
+        let report = import(&mock_store, "channel", &body, Mode::Skip, false, &owner())
+            .await
+            .unwrap();
+
+        assert!(report.applied);
+        let actions: Vec<Action> = report.records.iter().map(|r| r.action).collect();
+        assert_eq!(
+            actions,
+            vec![
+                Action::Skipped,
+                Action::Created,
+                Action::Invalid,
+                Action::Invalid
+            ]
+        );
+        assert_eq!(report.records[3].line, 4);
+    }
+
+    #[actix_web::test]
+    async fn test_import_overwrite() {
+        let mut mock_store = mock_store();
+        mock_store
+            .expect_update_if()
+            .withf(|e, version| {
+                e.id.as_deref() == Some("existing")
+                    && e.name == "renamed"
+                    && e.owner.is_none()
+                    && e.created_at == experiment("existing").created_at
+                    && *version == Version::of(&experiment("existing"))
+            })
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        // the owner and the dates of the file are not the ones kept.
+        let mut overwriting = experiment("existing");
+        overwriting.name = "renamed".to_owned();
+        overwriting.owner = Some(serde_json::json!({"id": "someone else"}));
+        overwriting.created_at = Some("2001-01-01T00:00:00Z".parse().unwrap());
+        let body = ndjson(&[overwriting]);
+        let report = import(
+            &mock_store,
+            "channel",
+            &body,
+            Mode::Overwrite,
+            false,
+            &owner(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.count(Action::Overwritten), 1);
+    }
+
+    #[actix_web::test]
+    async fn test_import_overwrite_changed_meanwhile() {
+        let mut mock_store = mock_store();
+        mock_store
+            .expect_update_if()
+            .times(1)
+            .returning(|_, _| Err(StoreError::Stale.into()));
+
+        let body = ndjson(&[experiment("existing")]);
+        let report = import(
+            &mock_store,
+            "channel",
+            &body,
+            Mode::Overwrite,
+            false,
+            &owner(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.count(Action::Conflict), 1);
+        assert!(report.records[0].written.is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_import_fail_and_dry_run() {
+        let mut mock_store = mock_store();
+        mock_store.expect_save().never();
+        mock_store.expect_update_if().never();
+
+        let body = ndjson(&[experiment("new"), experiment("existing")]);
+        let report = import(&mock_store, "channel", &body, Mode::Fail, false, &owner())
+            .await
+            .unwrap();
+        assert!(!report.applied);
+        assert_eq!(report.count(Action::Conflict), 1);
+
+        let body = ndjson(&[experiment("new")]);
+        let report = import(&mock_store, "channel", &body, Mode::Skip, true, &owner())
+            .await
+            .unwrap();
+        assert!(!report.applied);
+        assert_eq!(report.count(Action::Created), 1);
+    }
+}
@@ -0,0 +1,1004 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use actix_web::rt::time;
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use mockall::automock;
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use super::event::{Event, Kind, Publisher};
+use super::experiment::{StoreError, UserError};
+
+/// Header carrying the signature of a delivery, `sha256=<hex of the hmac>`.
+pub const SIGNATURE_HEADER: &str = "X-Enigma-Signature";
+/// Header carrying the unix time of the signature, part of the signed content.
+pub const TIMESTAMP_HEADER: &str = "X-Enigma-Timestamp";
+pub const EVENT_HEADER: &str = "X-Enigma-Event";
+pub const DELIVERY_HEADER: &str = "X-Enigma-Delivery";
+
+pub const MIN_SECRET_LENGTH: usize = 16;
+/// Deliveries kept per subscription by the local store.
+pub const MAX_DELIVERIES: usize = 100;
+
+/// Defined struct represents an url the events of a channel are posted to.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Subscription {
+    pub id: String,
+    pub channel_id: String,
+    pub url: String,
+    /// Key of the signatures, only shown when the subscription is created.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub secret: String,
+    /// Kinds of the events sent, every kind when empty.
+    pub events: Vec<Kind>,
+    pub active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Subscription {
+    pub fn accepts(&self, kind: Kind) -> bool {
+        self.events.is_empty() || self.events.contains(&kind)
+    }
+
+    /// The subscription without its secret.
+    pub fn redacted(mut self) -> Self {
+        self.secret = String::default();
+        self
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    /// Given up after too many attempts, or its subscription is gone.
+    Failed,
+}
+
+/// Defined struct represents an event sent to a subscription, with its attempts.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Delivery {
+    pub id: String,
+    pub subscription_id: String,
+    pub channel_id: String,
+    pub event: Event,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    /// Status of the response to the last attempt.
+    pub response_status: Option<u16>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+impl Delivery {
+    /// A new delivery of the event, due right away.
+    pub fn new(subscription: &Subscription, event: Event) -> Self {
+        let now = Utc::now();
+        Self {
+            id: bson::oid::ObjectId::new().to_hex(),
+            subscription_id: subscription.id.clone(),
+            channel_id: subscription.channel_id.clone(),
+            event,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: now,
+            last_error: None,
+            response_status: None,
+            created_at: now,
+            delivered_at: None,
+        }
+    }
+}
+
+/// Defined the contract of the storage of the subscriptions and their deliveries. A
+/// subscription of another channel is not found.
+#[automock]
+#[async_trait]
+pub trait Store {
+    async fn save(&self, subscription: &Subscription) -> Result<()>;
+    async fn get(&self, id: &str, channel_id: &str) -> Result<Subscription>;
+    async fn list(&self, channel_id: &str) -> Result<Vec<Subscription>>;
+    async fn update(&self, subscription: &Subscription) -> Result<()>;
+    /// Delete the subscription along with its deliveries.
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()>;
+    /// The active subscriptions of the channel of the event which accept its kind.
+    async fn matching(&self, event: &Event) -> Result<Vec<Subscription>>;
+    async fn enqueue(&self, delivery: &Delivery) -> Result<()>;
+    /// The latest deliveries of the subscription, the newest first.
+    async fn deliveries(
+        &self,
+        subscription_id: &str,
+        channel_id: &str,
+        limit: i64,
+    ) -> Result<Vec<Delivery>>;
+    /// Claim up to `limit` pending deliveries due at `now`, the oldest first. They are not
+    /// claimed again before `now + lease`.
+    async fn claim(&self, limit: i64, now: DateTime<Utc>, lease: Duration)
+        -> Result<Vec<Delivery>>;
+    /// Store the outcome of an attempt of the delivery.
+    async fn record(&self, delivery: &Delivery) -> Result<()>;
+}
+
+/// Webhook store kept in the process memory, only suitable when a single instance
+/// is running.
+#[derive(Debug, Default)]
+pub struct LocalStore {
+    subscriptions: Mutex<HashMap<String, Subscription>>,
+    deliveries: Mutex<Vec<Delivery>>,
+}
+
+#[async_trait]
+impl Store for LocalStore {
+    async fn save(&self, subscription: &Subscription) -> Result<()> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        if subscriptions.contains_key(&subscription.id) {
+            return Err(StoreError::Conflict {
+                message: format!("webhook {} already exists", subscription.id),
+            }
+            .into());
+        }
+        subscriptions.insert(subscription.id.clone(), subscription.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &str, channel_id: &str) -> Result<Subscription> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions
+            .get(id)
+            .filter(|s| s.channel_id == channel_id)
+            .cloned()
+            .ok_or_else(|| StoreError::DocumentNotfound.into())
+    }
+
+    async fn list(&self, channel_id: &str) -> Result<Vec<Subscription>> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        let mut list: Vec<Subscription> = subscriptions
+            .values()
+            .filter(|s| s.channel_id == channel_id)
+            .cloned()
+            .collect();
+        list.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(list)
+    }
+
+    async fn update(&self, subscription: &Subscription) -> Result<()> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        match subscriptions.get_mut(&subscription.id) {
+            Some(s) if s.channel_id == subscription.channel_id => {
+                *s = subscription.clone();
+                Ok(())
+            }
+            _ => Err(StoreError::DocumentNotfound.into()),
+        }
+    }
+
+    async fn delete(&self, id: &str, channel_id: &str) -> Result<()> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        match subscriptions.get(id) {
+            Some(s) if s.channel_id == channel_id => {
+                subscriptions.remove(id);
+                self.deliveries
+                    .lock()
+                    .unwrap()
+                    .retain(|d| d.subscription_id != id);
+                Ok(())
+            }
+            _ => Err(StoreError::DocumentNotfound.into()),
+        }
+    }
+
+    async fn matching(&self, event: &Event) -> Result<Vec<Subscription>> {
+        let subscriptions = self.subscriptions.lock().unwrap();
+        Ok(subscriptions
+            .values()
+            .filter(|s| s.channel_id == event.channel_id && s.active && s.accepts(event.kind))
+            .cloned()
+            .collect())
+    }
+
+    async fn enqueue(&self, delivery: &Delivery) -> Result<()> {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        deliveries.push(delivery.clone());
+
+        // drop the oldest finished deliveries of the subscription over the limit.
+        let of_subscription = |d: &Delivery| d.subscription_id == delivery.subscription_id;
+        let mut excess = deliveries
+            .iter()
+            .filter(|d| of_subscription(d))
+            .count()
+            .saturating_sub(MAX_DELIVERIES);
+        deliveries.retain(|d| {
+            if excess > 0 && of_subscription(d) && d.status != DeliveryStatus::Pending {
+                excess -= 1;
+                return false;
+            }
+            true
+        });
+        Ok(())
+    }
+
+    async fn deliveries(
+        &self,
+        subscription_id: &str,
+        channel_id: &str,
+        limit: i64,
+    ) -> Result<Vec<Delivery>> {
+        let deliveries = self.deliveries.lock().unwrap();
+        Ok(deliveries
+            .iter()
+            .rev()
+            .filter(|d| d.subscription_id == subscription_id && d.channel_id == channel_id)
+            .take(limit.max(0) as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn claim(
+        &self,
+        limit: i64,
+        now: DateTime<Utc>,
+        lease: Duration,
+    ) -> Result<Vec<Delivery>> {
+        let until = now + chrono::Duration::from_std(lease)?;
+        let mut deliveries = self.deliveries.lock().unwrap();
+        Ok(deliveries
+            .iter_mut()
+            .filter(|d| d.status == DeliveryStatus::Pending && d.next_attempt_at <= now)
+            .take(limit.max(0) as usize)
+            .map(|d| {
+                d.next_attempt_at = until;
+                d.clone()
+            })
+            .collect())
+    }
+
+    async fn record(&self, delivery: &Delivery) -> Result<()> {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        if let Some(d) = deliveries.iter_mut().find(|d| d.id == delivery.id) {
+            *d = delivery.clone();
+        }
+        Ok(())
+    }
+}
+
+/// Defined the contract of how the deliveries reach the subscriptions.
+#[automock]
+#[async_trait]
+pub trait Sender {
+    /// Check the url may receive the events, before a subscription to it is stored.
+    async fn check(&self, url: &str) -> Result<()>;
+    /// Post the event of the delivery to the url, signed with the secret, returns the status
+    /// of the response.
+    async fn send(&self, url: &str, secret: &str, delivery: &Delivery) -> Result<u16>;
+}
+
+/// Sender posting the events as json. Only public addresses are reached, the loopback,
+/// link-local and private ones are refused, once the host is resolved too, unless the host
+/// is allowed. The redirects are not followed.
+#[derive(Debug, Clone)]
+pub struct HttpSender {
+    timeout: Duration,
+    allowed_hosts: Vec<String>,
+}
+
+impl HttpSender {
+    pub fn new(timeout: Duration) -> Result<Self> {
+        Self::with_allowed_hosts(timeout, vec![])
+    }
+
+    /// Sender reaching the `allowed_hosts`, names or addresses, whatever they resolve to,
+    /// e.g. the services of the same network.
+    pub fn with_allowed_hosts(timeout: Duration, allowed_hosts: Vec<String>) -> Result<Self> {
+        Ok(Self {
+            timeout,
+            allowed_hosts,
+        })
+    }
+
+    fn is_allowed(&self, host: &str) -> bool {
+        self.allowed_hosts.iter().any(|h| h == host)
+    }
+
+    /// Client sending to the url, its host is resolved and checked here then the client
+    /// connects to these addresses, so the name cannot resolve elsewhere in between.
+    async fn client_for(&self, url: &reqwest::Url) -> Result<reqwest::Client> {
+        let builder = reqwest::Client::builder()
+            .timeout(self.timeout)
+            .redirect(reqwest::redirect::Policy::none());
+        let host = url.host_str().unwrap_or_default();
+        if self.is_allowed(host) {
+            return Ok(builder.build()?);
+        }
+
+        if let Some(ip) = literal_ip(url) {
+            if !is_public(ip) {
+                anyhow::bail!("{} is not a public address", ip);
+            }
+            return Ok(builder.build()?);
+        }
+
+        let port = url.port_or_known_default().unwrap_or_default();
+        let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port)).await?.collect();
+        if let Some(addr) = addrs.iter().find(|a| !is_public(a.ip())) {
+            anyhow::bail!("{} resolves to the non public {}", host, addr.ip());
+        }
+        Ok(builder.resolve_to_addrs(host, &addrs).build()?)
+    }
+}
+
+/// Address of the url given as one rather than a name.
+fn literal_ip(url: &reqwest::Url) -> Option<IpAddr> {
+    url.host_str()?
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+/// Whether the address is reachable on the internet, rather than a loopback, link-local,
+/// private, shared or otherwise reserved one.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                // shared address space of the carrier-grade NAT, 100.64.0.0/10.
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // unique local fc00::/7 and link-local fe80::/10.
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+#[async_trait]
+impl Sender for HttpSender {
+    /// The names are checked once resolved, when the events are sent, since they may
+    /// resolve to other addresses by then.
+    async fn check(&self, url: &str) -> Result<()> {
+        let url = reqwest::Url::parse(url)?;
+        let host = url.host_str().unwrap_or_default();
+        if self.is_allowed(host) {
+            return Ok(());
+        }
+
+        let is_reachable = match literal_ip(&url) {
+            Some(ip) => is_public(ip),
+            None => {
+                let name = host.trim_end_matches('.');
+                !name.is_empty() && name != "localhost" && !name.ends_with(".localhost")
+            }
+        };
+        if !is_reachable {
+            return Err(UserError::ValidationError {
+                message: "url must not reach a loopback, link-local or private address".to_owned(),
+            }
+            .into());
+        }
+        Ok(())
+    }
+
+    async fn send(&self, url: &str, secret: &str, delivery: &Delivery) -> Result<u16> {
+        let url = reqwest::Url::parse(url)?;
+        let client = self.client_for(&url).await?;
+
+        let body = serde_json::to_vec(&delivery.event)?;
+        let timestamp = Utc::now().timestamp();
+        let kind = serde_json::to_value(delivery.event.kind)?;
+
+        let resp = client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, sign(secret, timestamp, &body))
+            .header(TIMESTAMP_HEADER, timestamp.to_string())
+            .header(EVENT_HEADER, kind.as_str().unwrap_or_default())
+            .header(DELIVERY_HEADER, &delivery.id)
+            .body(body)
+            .send()
+            .await?;
+        Ok(resp.status().as_u16())
+    }
+}
+
+/// Signature of the body sent at the unix time: the hmac-sha256 of `{timestamp}.{body}`.
+pub fn sign(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac takes keys of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Publisher queueing a delivery of the event for each subscription accepting it.
+pub struct Notifier(pub Arc<dyn Store + Send + Sync>);
+
+#[async_trait]
+impl Publisher for Notifier {
+    async fn publish(&self, event: &Event) -> Result<()> {
+        for subscription in self.0.matching(event).await? {
+            self.0
+                .enqueue(&Delivery::new(&subscription, event.clone()))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Delivery worker settings.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// How often the due deliveries are looked for.
+    pub interval: Duration,
+    pub batch_size: i64,
+    /// How long a claimed delivery is left to the worker which claimed it.
+    pub lease: Duration,
+    /// Deliveries failing that many times are given up.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled by each failure up to `max_backoff`.
+    pub backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(1),
+            batch_size: 50,
+            lease: Duration::from_secs(60),
+            max_attempts: 8,
+            backoff: Duration::from_secs(5),
+            max_backoff: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+impl Config {
+    /// Delay before retrying a delivery which failed `attempts` times.
+    pub fn backoff_after(&self, attempts: u32) -> Duration {
+        let factor = 2_u32.saturating_pow(attempts.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+fn validate(subscription: &Subscription) -> Result<()> {
+    let invalid = |message: &str| -> Result<()> {
+        Err(UserError::ValidationError {
+            message: message.to_owned(),
+        }
+        .into())
+    };
+
+    match reqwest::Url::parse(&subscription.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {}
+        _ => return invalid("url must be an absolute http or https url"),
+    }
+    if subscription.secret.len() < MIN_SECRET_LENGTH {
+        return invalid(&format!(
+            "secret must be at least {} characters",
+            MIN_SECRET_LENGTH
+        ));
+    }
+    Ok(())
+}
+
+fn generate_secret() -> String {
+    let key: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+    format!("whsec_{}", key)
+}
+
+/// Create the subscription, with a generated secret when none is given. The url is checked
+/// against the `sender`.
+pub async fn create(
+    store: &(impl Store + ?Sized),
+    sender: &(impl Sender + ?Sized),
+    data: Subscription,
+) -> Result<Subscription> {
+    let mut data = data;
+    if data.secret.is_empty() {
+        data.secret = generate_secret();
+    }
+    validate(&data)?;
+    sender.check(&data.url).await?;
+
+    let now = Utc::now();
+    data.id = bson::oid::ObjectId::new().to_hex();
+    data.created_at = Some(now);
+    data.updated_at = Some(now);
+    store.save(&data).await?;
+
+    Ok(data)
+}
+
+/// Replace the subscription, its secret is kept unless a new one is given.
+pub async fn update(
+    store: &(impl Store + ?Sized),
+    sender: &(impl Sender + ?Sized),
+    current: &Subscription,
+    data: Subscription,
+) -> Result<Subscription> {
+    let mut data = data;
+    data.id = current.id.clone();
+    data.channel_id = current.channel_id.clone();
+    data.created_at = current.created_at;
+    if data.secret.is_empty() {
+        data.secret = current.secret.clone();
+    }
+    validate(&data)?;
+    sender.check(&data.url).await?;
+
+    data.updated_at = Some(Utc::now());
+    store.update(&data).await?;
+
+    Ok(data)
+}
+
+/// Attempt the delivery and store its outcome, a failed delivery is due again after its
+/// backoff until it failed `config.max_attempts` times.
+pub async fn attempt(
+    store: &(impl Store + ?Sized),
+    sender: &(impl Sender + ?Sized),
+    config: &Config,
+    subscription: &Subscription,
+    delivery: Delivery,
+    now: DateTime<Utc>,
+) -> Result<Delivery> {
+    let mut delivery = delivery;
+    delivery.attempts += 1;
+
+    let failure = match sender
+        .send(&subscription.url, &subscription.secret, &delivery)
+        .await
+    {
+        Ok(status) if (200..300).contains(&status) => {
+            delivery.response_status = Some(status);
+            None
+        }
+        Ok(status) => {
+            delivery.response_status = Some(status);
+            Some(format!("responded with status {}", status))
+        }
+        Err(e) => {
+            delivery.response_status = None;
+            Some(e.to_string())
+        }
+    };
+
+    match failure {
+        None => {
+            delivery.status = DeliveryStatus::Delivered;
+            delivery.delivered_at = Some(Utc::now());
+            delivery.last_error = None;
+        }
+        Some(error) => {
+            delivery.last_error = Some(error);
+            if delivery.attempts >= config.max_attempts {
+                delivery.status = DeliveryStatus::Failed;
+            } else {
+                let backoff = config.backoff_after(delivery.attempts);
+                delivery.next_attempt_at = now + chrono::Duration::from_std(backoff)?;
+            }
+        }
+    }
+
+    store.record(&delivery).await?;
+    Ok(delivery)
+}
+
+/// Attempt the due deliveries, returns the number delivered. The deliveries of a removed or
+/// inactive subscription are given up.
+pub async fn deliver_due(
+    store: &(impl Store + ?Sized),
+    sender: &(impl Sender + ?Sized),
+    config: &Config,
+    now: DateTime<Utc>,
+) -> Result<usize> {
+    let mut delivered = 0;
+    for delivery in store.claim(config.batch_size, now, config.lease).await? {
+        let subscription = match store
+            .get(&delivery.subscription_id, &delivery.channel_id)
+            .await
+        {
+            Ok(s) if s.active => s,
+            Ok(_) => {
+                give_up(store, delivery, "the webhook is inactive").await?;
+                continue;
+            }
+            Err(e) => match e.downcast_ref::<StoreError>() {
+                Some(StoreError::DocumentNotfound) => {
+                    give_up(store, delivery, "the webhook is removed").await?;
+                    continue;
+                }
+                _ => return Err(e),
+            },
+        };
+
+        let delivery = attempt(store, sender, config, &subscription, delivery, now).await?;
+        if delivery.status == DeliveryStatus::Delivered {
+            delivered += 1;
+        }
+    }
+
+    Ok(delivered)
+}
+
+async fn give_up(store: &(impl Store + ?Sized), delivery: Delivery, reason: &str) -> Result<()> {
+    let mut delivery = delivery;
+    delivery.status = DeliveryStatus::Failed;
+    delivery.last_error = Some(reason.to_owned());
+    store.record(&delivery).await
+}
+
+/// Send a test event to the subscription right away, it is retried as any delivery.
+pub async fn send_test(
+    store: &(impl Store + ?Sized),
+    sender: &(impl Sender + ?Sized),
+    config: &Config,
+    subscription: &Subscription,
+) -> Result<Delivery> {
+    let delivery = Delivery::new(subscription, Event::test(&subscription.channel_id));
+    store.enqueue(&delivery).await?;
+
+    attempt(store, sender, config, subscription, delivery, Utc::now()).await
+}
+
+/// Attempt the due deliveries forever, every `config.interval`.
+pub async fn run(store: &(impl Store + ?Sized), sender: &(impl Sender + ?Sized), config: &Config) {
+    let mut interval = time::interval(config.interval);
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = deliver_due(store, sender, config, Utc::now()).await {
+            println!("webhook delivery failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(channel_id: &str, events: Vec<Kind>) -> Subscription {
+        Subscription {
+            channel_id: channel_id.to_owned(),
+            url: "https://example.com/hook".to_owned(),
+            events,
+            active: true,
+            ..Default::default()
+        }
+    }
+
+    fn sender() -> HttpSender {
+        HttpSender::new(Duration::from_secs(5)).unwrap()
+    }
+
+    fn updated(channel_id: &str) -> Event {
+        Event::updated(&crate::service::experiment::Experiment {
+            id: Some("62bb13dfea2b3ea78771e305".to_owned()),
+            channel_id: channel_id.to_owned(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_sign() {
+        // the digest of `1656670000.{}` with the key `it's a secret key`.
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"it's a secret key").unwrap();
+        mac.update(b"1656670000.{}");
+        let expected = format!("sha256={}", hex::encode(mac.finalize().into_bytes()));
+
+        assert_eq!(sign("it's a secret key", 1656670000, b"{}"), expected);
+        assert_ne!(sign("it's a secret key", 1656670001, b"{}"), expected);
+        assert_ne!(sign("another secret key", 1656670000, b"{}"), expected);
+    }
+
+    #[actix_web::test]
+    async fn test_create_validates() {
+        let store = LocalStore::default();
+
+        let created = create(&store, &sender(), subscription("channel_a", vec![]))
+            .await
+            .unwrap();
+        assert!(created.secret.starts_with("whsec_"));
+        assert!(store.get(&created.id, "channel_a").await.is_ok());
+        assert!(store.get(&created.id, "channel_b").await.is_err());
+
+        for (url, secret) in [
+            ("ftp://example.com/hook", ""),
+            ("/hook", ""),
+            ("https://example.com/hook", "short"),
+            ("http://127.0.0.1/hook", ""),
+            ("http://localhost:8080/hook", ""),
+            ("http://10.0.0.1/hook", ""),
+            ("http://192.168.1.1/hook", ""),
+            ("http://169.254.169.254/latest/meta-data", ""),
+            ("http://[::1]/hook", ""),
+            ("http://[::ffff:127.0.0.1]/hook", ""),
+        ] {
+            let mut data = subscription("channel_a", vec![]);
+            data.url = url.to_owned();
+            data.secret = secret.to_owned();
+            let err = create(&store, &sender(), data).await.unwrap_err();
+            assert!(err.downcast_ref::<UserError>().is_some());
+        }
+    }
+
+    #[actix_web::test]
+    async fn test_update_keeps_the_secret() {
+        let store = LocalStore::default();
+        let current = create(&store, &sender(), subscription("channel_a", vec![]))
+            .await
+            .unwrap();
+
+        let mut data = subscription("channel_b", vec![Kind::Deleted]);
+        data.active = false;
+        let updated = update(&store, &sender(), &current, data).await.unwrap();
+        assert_eq!(updated.secret, current.secret);
+        assert_eq!(updated.channel_id, "channel_a");
+
+        let stored = store.get(&current.id, "channel_a").await.unwrap();
+        assert!(!stored.active);
+        assert_eq!(stored.events, vec![Kind::Deleted]);
+    }
+
+    #[actix_web::test]
+    async fn test_notifier_queues_the_matching() {
+        let store = Arc::new(LocalStore::default());
+        let all = create(store.as_ref(), &sender(), subscription("channel_a", vec![]))
+            .await
+            .unwrap();
+        let deletes = create(
+            store.as_ref(),
+            &sender(),
+            subscription("channel_a", vec![Kind::Deleted]),
+        )
+        .await
+        .unwrap();
+        let other = create(store.as_ref(), &sender(), subscription("channel_b", vec![]))
+            .await
+            .unwrap();
+
+        let notifier = Notifier(store.clone());
+        notifier.publish(&updated("channel_a")).await.unwrap();
+
+        let count = |id: String| {
+            let store = store.clone();
+            async move { store.deliveries(&id, "channel_a", 10).await.unwrap().len() }
+        };
+        assert_eq!(count(all.id).await, 1);
+        assert_eq!(count(deletes.id).await, 0);
+        assert_eq!(count(other.id).await, 0);
+    }
+
+    #[actix_web::test]
+    async fn test_deliver_due_retries_with_backoff() {
+        let store = LocalStore::default();
+        let config = Config {
+            max_attempts: 3,
+            backoff: Duration::from_secs(10),
+            ..Default::default()
+        };
+        let hook = create(&store, &sender(), subscription("channel_a", vec![]))
+            .await
+            .unwrap();
+        store
+            .enqueue(&Delivery::new(&hook, updated("channel_a")))
+            .await
+            .unwrap();
+
+        let mut sender = MockSender::new();
+        let mut calls = 0;
+        sender.expect_send().times(3).returning(move |_, _, _| {
+            calls += 1;
+            match calls {
+                1 => Ok(503),
+                2 => Err(anyhow::anyhow!("connection refused")),
+                _ => Ok(204),
+            }
+        });
+
+        let now = Utc::now();
+        assert_eq!(deliver_due(&store, &sender, &config, now).await.unwrap(), 0);
+        let delivery = store.deliveries(&hook.id, "channel_a", 1).await.unwrap()[0].clone();
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.response_status, Some(503));
+        assert_eq!(
+            delivery.next_attempt_at,
+            now + chrono::Duration::seconds(10)
+        );
+
+        // not due before its backoff.
+        let soon = now + chrono::Duration::seconds(5);
+        assert_eq!(
+            deliver_due(&store, &sender, &config, soon).await.unwrap(),
+            0
+        );
+
+        let later = now + chrono::Duration::seconds(10);
+        assert_eq!(
+            deliver_due(&store, &sender, &config, later).await.unwrap(),
+            0
+        );
+        let delivery = store.deliveries(&hook.id, "channel_a", 1).await.unwrap()[0].clone();
+        assert_eq!(delivery.attempts, 2);
+        assert_eq!(delivery.last_error.as_deref(), Some("connection refused"));
+        assert_eq!(
+            delivery.next_attempt_at,
+            later + chrono::Duration::seconds(20)
+        );
+
+        let last = later + chrono::Duration::seconds(20);
+        assert_eq!(
+            deliver_due(&store, &sender, &config, last).await.unwrap(),
+            1
+        );
+        let delivery = store.deliveries(&hook.id, "channel_a", 1).await.unwrap()[0].clone();
+        assert_eq!(delivery.status, DeliveryStatus::Delivered);
+        assert_eq!(delivery.attempts, 3);
+        assert!(delivery.last_error.is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_deliver_due_gives_up() {
+        let store = LocalStore::default();
+        let config = Config {
+            max_attempts: 1,
+            ..Default::default()
+        };
+        let hook = create(&store, &sender(), subscription("channel_a", vec![]))
+            .await
+            .unwrap();
+        store
+            .enqueue(&Delivery::new(&hook, updated("channel_a")))
+            .await
+            .unwrap();
+
+        let mut sender = MockSender::new();
+        sender.expect_send().times(1).returning(|_, _, _| Ok(500));
+        deliver_due(&store, &sender, &config, Utc::now())
+            .await
+            .unwrap();
+        let delivery = store.deliveries(&hook.id, "channel_a", 1).await.unwrap()[0].clone();
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+
+        // the deliveries of an inactive webhook are not sent.
+        let mut inactive = hook.clone();
+        inactive.active = false;
+        store.update(&inactive).await.unwrap();
+        store
+            .enqueue(&Delivery::new(&hook, updated("channel_a")))
+            .await
+            .unwrap();
+        deliver_due(&store, &MockSender::new(), &config, Utc::now())
+            .await
+            .unwrap();
+        let delivery = store.deliveries(&hook.id, "channel_a", 1).await.unwrap()[0].clone();
+        assert_eq!(delivery.status, DeliveryStatus::Failed);
+        assert_eq!(
+            delivery.last_error.as_deref(),
+            Some("the webhook is inactive")
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_deliveries_are_capped() {
+        let store = LocalStore::default();
+        let hook = create(&store, &sender(), subscription("channel_a", vec![]))
+            .await
+            .unwrap();
+        for _ in 0..MAX_DELIVERIES + 5 {
+            let mut delivery = Delivery::new(&hook, updated("channel_a"));
+            delivery.status = DeliveryStatus::Delivered;
+            store.enqueue(&delivery).await.unwrap();
+        }
+
+        let deliveries = store.deliveries(&hook.id, "channel_a", 1000).await.unwrap();
+        assert_eq!(deliveries.len(), MAX_DELIVERIES);
+    }
+
+    /// Signature, timestamp and body of the received deliveries.
+    type Received = Mutex<Vec<(String, String, Vec<u8>)>>;
+
+    #[actix_web::test]
+    async fn test_http_sender_signs() {
+        use actix_web::{web, App, HttpRequest, HttpResponse, HttpServer};
+
+        let received = web::Data::new(Received::default());
+        let state = received.clone();
+        let server = HttpServer::new(move || {
+            App::new().app_data(state.clone()).route(
+                "/hook",
+                web::post().to(
+                    |req: HttpRequest, body: web::Bytes, received: web::Data<Received>| async move {
+                        let header = |name: &str| {
+                            req.headers()
+                                .get(name)
+                                .and_then(|v| v.to_str().ok())
+                                .unwrap_or_default()
+                                .to_owned()
+                        };
+                        received.lock().unwrap().push((
+                            header(SIGNATURE_HEADER),
+                            header(TIMESTAMP_HEADER),
+                            body.to_vec(),
+                        ));
+                        HttpResponse::NoContent().finish()
+                    },
+                ),
+            )
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let addr = server.addrs()[0];
+        actix_web::rt::spawn(server.run());
+
+        let mut hook = subscription("channel_a", vec![]);
+        hook.url = format!("http://localhost:{}/hook", addr.port());
+        hook.secret = "it's a secret key".to_owned();
+        let delivery = Delivery::new(&hook, Event::test("channel_a"));
+
+        // the loopback is refused once resolved, unless allowed.
+        let err = sender()
+            .send(&hook.url, &hook.secret, &delivery)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("non public"));
+        let err = sender()
+            .send(&format!("http://{}/hook", addr), &hook.secret, &delivery)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("not a public address"));
+        assert!(received.lock().unwrap().is_empty());
+
+        let sender =
+            HttpSender::with_allowed_hosts(Duration::from_secs(5), vec!["localhost".to_owned()])
+                .unwrap();
+        assert!(sender.check(&hook.url).await.is_ok());
+        let status = sender
+            .send(&hook.url, &hook.secret, &delivery)
+            .await
+            .unwrap();
+        assert_eq!(status, 204);
+
+        let (signature, timestamp, body) = received.lock().unwrap()[0].clone();
+        assert_eq!(
+            signature,
+            sign(&hook.secret, timestamp.parse().unwrap(), &body)
+        );
+        let event: Event = serde_json::from_slice(&body).unwrap();
+        assert_eq!(event.kind, Kind::Test);
+    }
+}